@@ -1 +1,2 @@
+#!/bin/bash
 exit $1
@@ -0,0 +1,6 @@
+#!/bin/bash
+# Doubles a string until allocation fails. Only run this under a memory limit.
+x=a
+while true; do
+  x=$x$x
+done
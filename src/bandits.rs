@@ -1,180 +1,243 @@
-#![feature(test)]
-mod config;
-mod ibeta;
-mod insights;
-mod thompson;
-
 use argh::FromArgs;
-use config::{parse_config, save_config, Config, Script};
-use insights::{plot_top_3, plot_top_3_inverses, print_ranking, print_ranking_bias_runtime};
-use log::{debug, trace, warn};
+use bandits::{
+    choose_script,
+    config::{
+        capture_dir, carry_over_results, check_config_extension, config_schema, export_state,
+        import_state, is_stdio, merge_configs, normalize_biases, parse_config, parse_priors,
+        parse_state, save_config, save_csv, save_schema, save_state, try_parse_config, Config,
+        ConfigWatcher, RuntimeMetric, Script,
+    },
+    effective_bias, explain_selection,
+    history::open_history,
+    insights::{
+        plot_bias_tradeoff, plot_top_3, plot_top_3_inverses, print_bias_suggestions,
+        print_explain_top, print_first_interesting_steps, print_posterior_curve, print_ranking,
+        print_ranking_bias_runtime, print_ranking_explanation, print_ranking_greedy,
+        print_ranking_json, print_ranking_markdown, print_ranking_throughput, print_ranking_ucb1,
+        print_runtime_correlation, print_selection_probabilities, status_line, write_top_3_svg,
+        DisplayOptions, SortOrder, SummaryFormat,
+    },
+    lint::{lint_config, Severity, DEFAULT_MAX_BIAS},
+    reset_state, run_script, run_script_with_output,
+    schedule::local_minute_of_day,
+    seeded_rng, simulate, step,
+    thompson::{
+        empirical_rate, probability_better, Strategy, ThompsonInfo, DEFAULT_RUNTIME_REFERENCE_MS,
+    },
+    ExecutionOptions, Outcome, SelectionOptions, SelectionState,
+};
+use log::{debug, warn, LevelFilter};
 use ordered_float::NotNan;
 use serde::{Deserialize, Serialize};
-use std::{path::PathBuf, process::Command, time::Instant};
-use thompson::{thompson_sampling, thompson_sampling_bias_runtime, ThompsonInfo};
-
-fn choose_script(config: &Config, ignore_runtime: bool) -> usize {
-    let items = config
-        .scripts
-        .iter()
-        .filter(|x| x.limit.is_none() || x.limit.unwrap() < x.results.interesting)
-        .map(|x| &x.results)
-        .collect::<Vec<_>>();
-    let entries: &[&ThompsonInfo] = items.as_slice();
-    let runtime = config
-        .scripts
-        .iter()
-        .filter(|x| x.limit.is_none() || x.limit.unwrap() < x.results.interesting)
-        .map(|x| &x.avgruntime_ms)
-        .collect::<Vec<_>>();
-    let runtimes: &[&Option<NotNan<f64>>] = runtime.as_slice();
+use std::{
+    collections::HashSet,
+    io,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::{Duration, Instant},
+};
 
-    let user_biases = config.scripts.iter().map(|x| &x.bias).collect::<Vec<_>>();
-    let user_biases: &[&NotNan<f64>] = user_biases.as_slice();
-
-    if ignore_runtime {
-        thompson_sampling(entries, user_biases).unwrap()
+/// Print a progress message of `run`, on stderr when the config itself is being saved to stdout.
+fn report(to_stdout: bool, message: &str) {
+    if to_stdout {
+        eprintln!("{message}");
     } else {
-        thompson_sampling_bias_runtime(entries, runtimes, user_biases).unwrap()
+        println!("{message}");
     }
 }
 
-fn run_script(script: &Script) -> ScriptResult {
-    let mut parts = script.command.split_whitespace();
-    // Get the command (first part)
-    let command = parts.next().expect("No command provided");
-
-    // Get command arguments
-    let args: Vec<&str> = parts.collect();
-
-    // Execute the command
-    let start = Instant::now();
-    let output = Command::new(command)
-        .args(&args)
-        .output()
-        .expect("Failed to execute command");
-    let duration = start.elapsed();
-
-    // Print the output
-    if output.status.success() {
-        trace!("Command executed successfully!");
-        trace!("Output: {}", String::from_utf8_lossy(&output.stdout));
-        ScriptResult {
-            interesting: 0,
-            uninteresting: 1,
-            runtime_ms: duration.as_millis(),
-        }
-    } else if output.status.code() == Some(1) {
-        warn!("Command failed with exit status 1, logging as interesting.");
-        warn!("Error: {}", String::from_utf8_lossy(&output.stderr));
-        ScriptResult {
-            interesting: 1,
-            uninteresting: 0,
-            runtime_ms: duration.as_millis(),
-        }
+/// Write the top 3 plots to `path`, as an SVG image if it ends in `.svg` and as plain text otherwise. The
+/// inverse plot is included when given a runtime reference.
+fn save_plots(scripts: &[Script], runtime_reference_ms: Option<NotNan<f64>>, path: &Path) {
+    let mut data = vec![];
+    if path.extension().is_some_and(|extension| extension == "svg") {
+        write_top_3_svg(scripts, runtime_reference_ms, &mut data).unwrap();
     } else {
-        println!("Command failed with unknown exit status: {}", output.status);
-        println!("Error: {}", String::from_utf8_lossy(&output.stderr));
-        println!("Unrecognized exit status. Adding to avg iteration runtime but not logging as interesting/uninteresting");
-        ScriptResult {
-            interesting: 0,
-            uninteresting: 0,
-            runtime_ms: duration.as_millis(),
+        plot_top_3(scripts, &mut data).unwrap();
+        if let Some(runtime_reference_ms) = runtime_reference_ms {
+            plot_top_3_inverses(scripts, runtime_reference_ms, &mut data).unwrap();
         }
+        // Terminal colors would only clutter a file.
+        let colors = regex::bytes::Regex::new("\x1b\\[[0-9;]*m").unwrap();
+        data = colors.replace_all(&data, &b""[..]).into_owned();
+    }
+
+    let written = match path.parent() {
+        Some(parent) => std::fs::create_dir_all(parent),
+        None => Ok(()),
+    }
+    .and_then(|()| std::fs::write(path, data));
+    if let Err(err) = written {
+        eprintln!("Could not write plots to {}: {}", path.display(), err);
+        std::process::exit(1);
     }
 }
 
-fn update_state(existing_results: &mut Script, result: ScriptResult) {
-    let results = ThompsonInfo {
-        interesting: existing_results.results.interesting + result.interesting,
-        uninteresting: existing_results.results.uninteresting + result.uninteresting,
+/// How many points `summarize --curve` prints.
+const CURVE_POINTS: usize = 100;
+
+/// The runtime that gets a scaler of 1: from `--runtime-reference` if given, else the config's
+/// runtime_reference_ms, else 100ms.
+fn runtime_reference_ms(config: &Config, runtime_reference: Option<RunDuration>) -> NotNan<f64> {
+    let reference = match runtime_reference {
+        Some(RunDuration(reference)) => reference.as_secs_f64() * 1000.0,
+        None => config
+            .runtime_reference_ms
+            .map_or(DEFAULT_RUNTIME_REFERENCE_MS, |reference| *reference),
     };
+    if reference <= 0.0 {
+        eprintln!("The runtime reference must be positive, got {reference}ms");
+        std::process::exit(1);
+    }
+    NotNan::new(reference).unwrap()
+}
 
-    let total_runtime = existing_results
-        .avgruntime_ms
-        .unwrap_or(NotNan::new(0.0).unwrap())
-        * existing_results.runcount as f64;
-    existing_results.runcount += 1;
-    existing_results.avgruntime_ms =
-        Some((total_runtime + result.runtime_ms as f64) / existing_results.runcount as f64);
-    existing_results.results = results;
+/// For commands that only run a single script, there is nothing to fall back to if it can't start.
+fn exit_could_not_run(script: &Script, err: io::Error) -> ! {
+    eprintln!("Could not run {}: {}", script.name, err);
+    std::process::exit(1);
 }
 
-fn reset_state(config: &mut Config, script_name: Option<String>) {
-    if let Some(script_name) = script_name {
-        if config
-            .scripts
-            .iter()
-            .any(|script| script.name == script_name)
-        {
-            panic!("Could not find specified script {script_name} to reset")
-        }
+/// Run a script repeatedly without recording results and report whether its outcome is stable.
+fn check_determinism(script: &Script, runs: usize) {
+    println!(
+        "Diagnostic: running {} {} times. Results are not recorded.",
+        script.name, runs
+    );
 
-        // Reset only the specified script
-        config.scripts = config
-            .scripts
-            .clone()
-            .into_iter()
-            .map(|mut script| {
-                if script.name == script_name {
-                    let results = ThompsonInfo {
-                        interesting: 0,
-                        uninteresting: 0,
-                    };
+    let outcomes = (0..runs)
+        .map(|_| run_script(script).unwrap_or_else(|err| exit_could_not_run(script, err)))
+        .map(|result| result.outcome())
+        .collect::<Vec<_>>();
 
-                    script.runcount = 0;
-                    script.results = results;
-                    script.avgruntime_ms = None;
-                    script
-                } else {
-                    // Leave untouched
-                    script
-                }
-            })
-            .collect();
-    } else {
-        // Reset all scripts
-        config.scripts = config
-            .scripts
-            .clone()
-            .into_iter()
-            .map(|mut script| {
-                let results = ThompsonInfo {
-                    interesting: 0,
-                    uninteresting: 0,
-                };
+    for (label, outcome) in [
+        ("Interesting", Outcome::Interesting),
+        ("Uninteresting", Outcome::Uninteresting),
+        ("Unrecognized", Outcome::Unrecognized),
+        ("Timed out", Outcome::TimedOut),
+    ] {
+        let count = outcomes.iter().filter(|x| **x == outcome).count();
+        println!(
+            "- {}: {} ({:.1}%)",
+            label,
+            count,
+            count as f64 / runs as f64 * 100.
+        );
+    }
 
-                script.runcount = 0;
-                script.results = results;
-                script.avgruntime_ms = None;
-                script
-            })
-            .collect();
+    if outcomes.windows(2).all(|pair| pair[0] == pair[1]) {
+        println!("{} was deterministic across {} runs.", script.name, runs);
+    } else {
+        println!("{} Warning: Non-deterministic. Identical runs were classified differently, so its posterior will be noisy.", script.name);
     }
 }
 
-#[derive(Debug)]
-struct ScriptResult {
-    interesting: u64,
-    uninteresting: u64,
-    runtime_ms: u128,
+/// Run a script until it is interesting or `max_attempts` runs have been made, without recording results.
+fn hammer(script: &Script, max_attempts: usize) {
+    for attempt in 1..=max_attempts {
+        let (result, output) = run_script_with_output(script, None)
+            .unwrap_or_else(|err| exit_could_not_run(script, err));
+        if result.interesting > 0 {
+            println!(
+                "{} was interesting on attempt {} of {}, taking {}ms.",
+                script.name, attempt, max_attempts, result.runtime_ms
+            );
+            println!("stdout:\n{}", String::from_utf8_lossy(&output.stdout));
+            println!("stderr:\n{}", String::from_utf8_lossy(&output.stderr));
+            return;
+        }
+    }
+
+    println!(
+        "{} was not interesting within {} attempts.",
+        script.name, max_attempts
+    );
 }
 
-fn step(config: &mut Config, ignore_runtime: bool) {
-    if config.scripts.is_empty() {
-        debug!("ERROR: No scripts to execute. Exiting...");
-        return;
-    }
+/// Report which challengers are confidently more interesting than their champions, returning the
+/// indices of those to promote.
+fn find_promotions(config: &Config, opts: &PromoteOptions) -> Vec<usize> {
+    let mut promotions = vec![];
 
-    let script_index = choose_script(config, ignore_runtime);
+    for (index, challenger) in config.scripts.iter().enumerate() {
+        let Some(champion_name) = &challenger.challenger_of else {
+            continue;
+        };
+        let Some(champion) = config
+            .scripts
+            .iter()
+            .find(|script| &script.name == champion_name)
+        else {
+            println!(
+                "{} challenges {}, which is not in the config.",
+                challenger.name, champion_name
+            );
+            continue;
+        };
 
-    debug!("Running script {}...", script_index);
+        if challenger.runcount < opts.min_runs || champion.runcount < opts.min_runs {
+            println!(
+                "{} vs {}: needs {} runs of each, has {} and {}.",
+                challenger.name,
+                champion.name,
+                opts.min_runs,
+                challenger.runcount,
+                champion.runcount
+            );
+            continue;
+        }
 
-    let result = run_script(&config.scripts[script_index]);
+        let probability = probability_better(
+            &challenger.results,
+            &champion.results,
+            opts.samples,
+            &mut rand::thread_rng(),
+        );
+        if probability >= opts.threshold {
+            println!(
+                "{} beats {} with probability {:.4}. Recommend promoting it.",
+                challenger.name, champion.name, probability
+            );
+            promotions.push(index);
+        } else {
+            println!(
+                "{} beats {} with probability {:.4}, below the {:.4} threshold. Keep testing.",
+                challenger.name, champion.name, probability, opts.threshold
+            );
+        }
+    }
+
+    promotions
+}
 
-    debug!("Script {} finished. Result: {:?}", script_index, result);
+/// Replace each promoted challenger's champion with it. The champion keeps its name and `challenger_of`
+/// but takes everything else from the challenger, how it runs as well as its learned results, and the
+/// challenger is removed.
+fn promote(config: &mut Config, promotions: &[usize]) {
+    for index in promotions {
+        let challenger = config.scripts[*index].clone();
+        let champion_name = challenger.challenger_of.as_ref().unwrap();
+        let champion = config
+            .scripts
+            .iter_mut()
+            .find(|script| &script.name == champion_name)
+            .unwrap();
+
+        println!("Promoted {} over {}.", challenger.name, champion.name);
+        *champion = Script {
+            name: champion.name.clone(),
+            challenger_of: champion.challenger_of.clone(),
+            ..challenger
+        };
+    }
 
-    update_state(config.scripts.get_mut(script_index).unwrap(), result);
+    let promoted = promotions
+        .iter()
+        .map(|index| config.scripts[*index].name.clone())
+        .collect::<Vec<_>>();
+    config
+        .scripts
+        .retain(|script| !promoted.contains(&script.name));
 }
 
 #[derive(FromArgs, Debug)]
@@ -185,8 +248,50 @@ Prioritize bandits according to their likelihood to be interesting and runtime.
 struct Args {
     #[argh(subcommand)]
     subcommand: SubCommands,
+
+    /// runtime that gets a runtime scaler of 1, e.g. 100ms, 100s or 100m. Overrides the config's
+    /// runtime_reference_ms, and defaults to 100ms
+    #[argh(option)]
+    runtime_reference: Option<RunDuration>,
+
+    /// raise positive biases below this floor when selecting and ranking scripts. Zero biases are unchanged
+    #[argh(option)]
+    min_effective_bias: Option<f64>,
+
+    /// runs a script needs before its average runtime biases selection. Until then it is treated as
+    /// taking the reference runtime (default 1)
+    #[argh(option, default = "1")]
+    min_samples: usize,
+
+    /// log more: once for info, twice for debug and three times for trace. RUST_LOG overrides it
+    #[argh(switch, short = 'v')]
+    verbose: u8,
+
+    /// only log errors
+    #[argh(switch, short = 'q')]
+    quiet: bool,
 }
 
+/// Log warnings by default, more with each `-v` or only errors with `-q`. `RUST_LOG` filters take precedence.
+fn init_logger(verbose: u8, quiet: bool) {
+    let level = match (quiet, verbose) {
+        (true, _) => LevelFilter::Error,
+        (false, 0) => LevelFilter::Warn,
+        (false, 1) => LevelFilter::Info,
+        (false, 2) => LevelFilter::Debug,
+        (false, _) => LevelFilter::Trace,
+    };
+
+    let mut builder = env_logger::Builder::new();
+    builder.filter_level(level);
+    if let Ok(filters) = std::env::var("RUST_LOG") {
+        builder.parse_filters(&filters);
+    }
+    builder.init();
+}
+
+// Parsed once per process, and argh can't parse a boxed subcommand.
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, Serialize, Deserialize, FromArgs, PartialEq)]
 #[argh(subcommand)]
 enum SubCommands {
@@ -196,47 +301,214 @@ enum SubCommands {
     Reset(ResetOptions),
     Summarize(SummarizeOptions),
     Lint(LintOptions),
+    ConfigSet(ConfigSetOptions),
+    Doctor(DoctorOptions),
+    ExplainBias(ExplainBiasOptions),
+    Hammer(HammerOptions),
+    Analyze(AnalyzeOptions),
+    Export(ExportOptions),
+    ExportState(ExportStateOptions),
+    ImportState(ImportStateOptions),
+    Merge(MergeOptions),
+    Promote(PromoteOptions),
+    Status(StatusOptions),
+    Schema(SchemaOptions),
+    Normalize(NormalizeOptions),
+    Simulate(SimulateOptions),
 }
 
 #[derive(Debug, Serialize, Deserialize, FromArgs, PartialEq)]
 /// Repeatedly prioritize & run bandits according to their likelihood to discover interesting cases.
 #[argh(subcommand, name = "run")]
 struct RunOptions {
-    /// list of scripts to execute
+    /// list of scripts to execute, or - to read stdin
     #[argh(positional)]
     config: PathBuf,
 
-    /// output location for updated config
+    /// output location for updated config, or - to write stdout
     #[argh(option, short = 'o', default = "PathBuf::from(\"./new-config.json\")")]
     output: PathBuf,
 
-    /// number of command invocations to perform
-    #[argh(option, default = "10")]
-    steps: usize,
+    /// write the updated config back over the input config instead of the output location, ignoring -o
+    #[argh(switch)]
+    in_place: bool,
+
+    /// number of command invocations to perform (default 10, unlimited with --max-duration, --budget-ms,
+    /// --until-file-exists or --stop-after-interesting)
+    #[argh(option)]
+    steps: Option<usize>,
+
+    /// stop starting new invocations after this long, e.g. 90s, 30m or 8h
+    #[argh(option)]
+    max_duration: Option<RunDuration>,
+
+    /// the same as --max-duration with an ms suffix, e.g. --budget-ms 1500 for --max-duration 1500ms
+    #[argh(option)]
+    budget_ms: Option<u64>,
+
+    /// stop starting new invocations once this file exists
+    #[argh(option)]
+    until_file_exists: Option<PathBuf>,
+
+    /// stop once this many interesting results have been found in this run, across all scripts
+    #[argh(option)]
+    stop_after_interesting: Option<u64>,
+
+    /// reload the config between steps when the file changes, keeping the results found so far
+    #[argh(switch)]
+    watch: bool,
+
+    /// save the config to the output location this often while running, e.g. 1h
+    #[argh(option)]
+    checkpoint_every: Option<RunDuration>,
 
     /// ignore runtime when ranking scripts
     #[argh(switch, short = 'i')]
     ignore_runtime: bool,
+
+    /// before sampling, run the script with the widest 90% credible interval until all are narrower than this.
+    /// Scripts whose runs keep timing out or ending unclassified are only warmed up for a bounded number of runs
+    #[argh(option)]
+    warmup_until_width: Option<f64>,
+
+    /// cycle through scripts in order instead of sampling, skipping weak scripts (see --skip-below)
+    #[argh(switch)]
+    interleave: bool,
+
+    /// with --interleave, skip scripts whose posterior mean is below this fraction of the best
+    #[argh(option, default = "0.5")]
+    skip_below: f64,
+
+    /// kill any script still running after this many milliseconds, overriding each script's timeout_ms
+    #[argh(option)]
+    timeout: Option<u64>,
+
+    /// print the last N lines of stdout and stderr of each interesting run
+    #[argh(option)]
+    tail_output: Option<usize>,
+
+    /// append a JSON line per step (step, script, result, runtime, timestamp) to this file
+    #[argh(option)]
+    history: Option<PathBuf>,
+
+    /// also write the final plots to this file, as text or as an svg image if it ends in .svg
+    #[argh(option)]
+    plot_out: Option<PathBuf>,
+
+    /// seed for every random choice, so runs with the same seed and config pick the same scripts
+    #[argh(option)]
+    seed: Option<u64>,
+
+    /// run up to this many scripts at once, all sampled before any of their results are recorded
+    #[argh(option, default = "1")]
+    jobs: usize,
+
+    /// print the script each step would select, without running anything or saving. Pair with --seed to
+    /// preview a reproducible run
+    #[argh(switch)]
+    dry_run: bool,
+
+    /// json list of per-script beta priors (name, alpha, beta) to sample with. Stored counts are unchanged
+    #[argh(option)]
+    priors: Option<PathBuf>,
+
+    /// selection strategy: thompson (default), ucb1, epsilon-greedy:<epsilon> (e.g. epsilon-greedy:0.1),
+    /// or the round-robin and uniform baselines
+    #[argh(option, default = "Strategy::default()")]
+    strategy: Strategy,
+
+    /// don't select a script again until this many other selections have been made, unless nothing else
+    /// can run
+    #[argh(option, default = "0")]
+    cooldown: usize,
+
+    /// print how every candidate scored each time a script is chosen, marking the chosen one with *
+    #[argh(switch)]
+    explain: bool,
+
+    /// only run scripts with this tag. Repeat to run scripts with any of several tags
+    #[argh(option)]
+    tag: Vec<String>,
+}
+
+/// A wall-clock duration given on the command line as a number followed by ms, s, m or h.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+struct RunDuration(Duration);
+
+impl FromStr for RunDuration {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let split = s
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(s.len());
+        let (value, unit) = s.split_at(split);
+        let value: f64 = value.parse().map_err(|_| {
+            format!("Invalid duration {s}. Expected a number followed by ms, s, m or h")
+        })?;
+        let seconds = match unit {
+            "ms" => value / 1000.0,
+            "s" => value,
+            "m" => value * 60.0,
+            "h" => value * 3600.0,
+            _ => {
+                return Err(format!(
+                    "Unknown duration unit in {s}. Expected one of ms, s, m, h"
+                ))
+            }
+        };
+        Duration::try_from_secs_f64(seconds)
+            .map(RunDuration)
+            .map_err(|_| format!("Invalid duration {s}. It is too long to represent"))
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, FromArgs, PartialEq)]
 /// Summarize the config file
 #[argh(subcommand, name = "summarize")]
 struct SummarizeOptions {
-    /// list of scripts to execute
+    /// list of scripts to execute, or - to read stdin
     #[argh(positional)]
     config: PathBuf,
 
     /// ignore runtime when ranking scripts
     #[argh(switch, short = 'i')]
     ignore_runtime: bool,
+
+    /// rank by expected interesting cases per second of runtime
+    #[argh(switch)]
+    by_throughput: bool,
+
+    /// display order: sample (default), mean, runtime, name, or runcount
+    #[argh(option, default = "SortOrder::default()")]
+    sort: SortOrder,
+
+    /// print biases that would give the scripts an equal chance of being selected. The config is not modified
+    #[argh(switch)]
+    suggest_bias: bool,
+
+    /// print the posterior of this script as x,density CSV instead of the summary
+    #[argh(option)]
+    curve: Option<String>,
+
+    /// also write the plots to this file, as text or as an svg image if it ends in .svg
+    #[argh(option)]
+    plot_out: Option<PathBuf>,
+
+    /// only summarize scripts with this tag. Repeat to summarize scripts with any of several tags
+    #[argh(option)]
+    tag: Vec<String>,
+
+    /// output format: text (default), or markdown for a table of the ranking without plots
+    #[argh(option, default = "SummaryFormat::default()")]
+    format: SummaryFormat,
 }
 
 #[derive(Debug, Serialize, Deserialize, FromArgs, PartialEq)]
 /// Repeatedly prioritize & run bandits according to their likelihood to discover interesting cases.
 #[argh(subcommand, name = "rank")]
 struct RankOptions {
-    /// list of scripts to rank
+    /// list of scripts to rank, or - to read stdin
     #[argh(positional)]
     config: PathBuf,
 
@@ -247,6 +519,39 @@ struct RankOptions {
     /// verbose
     #[argh(switch, short = 'v')]
     verbose: bool,
+
+    /// explain why the top ranked script leads, comparing the median scores of the top two
+    #[argh(switch)]
+    explain_top: bool,
+
+    /// print the sampled percentile, runtime scaler, bias and score the ranking was ordered by for every
+    /// script
+    #[argh(switch)]
+    explain: bool,
+
+    /// display order: sample (default), mean, runtime, name, or runcount
+    #[argh(option, default = "SortOrder::default()")]
+    sort: SortOrder,
+
+    /// list scripts with fewer runs separately as under-sampled. They are still sampled when running
+    #[argh(option, default = "0")]
+    min_runs: u64,
+
+    /// selection strategy: thompson (default), ucb1, or epsilon-greedy:<epsilon>, e.g. epsilon-greedy:0.1
+    #[argh(option, default = "Strategy::default()")]
+    strategy: Strategy,
+
+    /// seed for sampling, so rankings with the same seed and config are identical
+    #[argh(option)]
+    seed: Option<u64>,
+
+    /// print the ranking as a JSON array of every active script, best first, instead of text
+    #[argh(switch)]
+    json: bool,
+
+    /// only rank scripts with this tag. Repeat to rank scripts with any of several tags
+    #[argh(option)]
+    tag: Vec<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, FromArgs, PartialEq)]
@@ -277,6 +582,11 @@ struct NewOptions {
     /// test=command mapping
     #[argh(option, short = 't', from_str_fn(parse_mapping))]
     tests: Vec<(String, String)>,
+
+    /// file with one test=command mapping per line, added before any -t mappings. Blank lines and lines
+    /// starting with # are skipped
+    #[argh(option)]
+    from_file: Option<PathBuf>,
 }
 
 fn parse_mapping(s: &str) -> Result<(String, String), String> {
@@ -288,72 +598,782 @@ fn parse_mapping(s: &str) -> Result<(String, String), String> {
     }
 }
 
+/// Read `test=command` mappings from a file, one per line, exiting on an unreadable file or bad line.
+fn parse_mapping_file(path: &PathBuf) -> Vec<(String, String)> {
+    let data = std::fs::read_to_string(path).unwrap_or_else(|err| {
+        eprintln!("Could not read {}: {}", path.display(), err);
+        std::process::exit(1);
+    });
+    data.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+        .map(|(index, line)| {
+            parse_mapping(line.trim()).unwrap_or_else(|err| {
+                eprintln!("{}, line {}: {}", path.display(), index + 1, err);
+                std::process::exit(1);
+            })
+        })
+        .collect()
+}
+
 #[derive(Debug, Serialize, Deserialize, FromArgs, PartialEq)]
 /// Lint an existing config file to ensure it is valid
 #[argh(subcommand, name = "lint")]
 struct LintOptions {
-    /// config to lint
+    /// config to lint, or - to read stdin
+    #[argh(positional)]
+    config: PathBuf,
+
+    /// exit with code 1 if any ERROR was found, e.g. for a pre-commit hook
+    #[argh(switch)]
+    strict: bool,
+
+    /// warn about biases above this. Defaults to 1000
+    #[argh(option, default = "DEFAULT_MAX_BIAS")]
+    max_bias: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromArgs, PartialEq)]
+/// Update fields of a script in an existing config file
+#[argh(subcommand, name = "config-set")]
+struct ConfigSetOptions {
+    /// config to update
+    #[argh(positional)]
+    config: PathBuf,
+
+    /// name of the script to update
+    #[argh(option)]
+    script: String,
+
+    /// new command
+    #[argh(option)]
+    command: Option<String>,
+
+    /// new bias
+    #[argh(option)]
+    bias: Option<NotNan<f64>>,
+
+    /// new limit
+    #[argh(option)]
+    limit: Option<u64>,
+
+    /// remove the script's limit, so it runs however many interesting cases it finds
+    #[argh(switch)]
+    clear_limit: bool,
+
+    /// output location for updated config
+    #[argh(option, short = 'o', default = "PathBuf::from(\"./new-config.json\")")]
+    output: PathBuf,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromArgs, PartialEq)]
+/// Run diagnostics against scripts in a config file. Results are not recorded.
+#[argh(subcommand, name = "doctor")]
+struct DoctorOptions {
+    /// config containing the script
+    #[argh(positional)]
+    config: PathBuf,
+
+    /// name of the script to diagnose
+    #[argh(option)]
+    script: String,
+
+    /// run the script repeatedly and report whether its outcome changes
+    #[argh(switch)]
+    check_determinism: bool,
+
+    /// number of runs for the determinism check
+    #[argh(option, default = "10")]
+    runs: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromArgs, PartialEq)]
+/// Rerun one script until it is interesting, to reproduce a finding. Results are not saved
+#[argh(subcommand, name = "hammer")]
+struct HammerOptions {
+    /// config containing the script
+    #[argh(positional)]
+    config: PathBuf,
+
+    /// name of the script to run
+    #[argh(option)]
+    script: String,
+
+    /// give up after this many runs
+    #[argh(option, default = "100")]
+    max_attempts: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromArgs, PartialEq)]
+/// Write each script's results as a CSV row, for spreadsheets
+#[argh(subcommand, name = "export")]
+struct ExportOptions {
+    /// config to export from
+    #[argh(positional)]
+    config: PathBuf,
+
+    /// output location for the CSV
+    #[argh(option, short = 'o', default = "PathBuf::from(\"./results.csv\")")]
+    output: PathBuf,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromArgs, PartialEq)]
+/// Print a JSON Schema of the config format, for editors to validate configs with
+#[argh(subcommand, name = "schema")]
+struct SchemaOptions {
+    /// write the schema to this file instead of stdout
+    #[argh(option, short = 'o')]
+    output: Option<PathBuf>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromArgs, PartialEq)]
+/// Save only the learned results of each script, keyed by name
+#[argh(subcommand, name = "export-state")]
+struct ExportStateOptions {
+    /// config to export from
+    #[argh(positional)]
+    config: PathBuf,
+
+    /// output location for the state
+    #[argh(option, short = 'o', default = "PathBuf::from(\"./state.json\")")]
+    output: PathBuf,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromArgs, PartialEq)]
+/// Fold results saved by export-state back into a config, matching scripts by name
+#[argh(subcommand, name = "import-state")]
+struct ImportStateOptions {
+    /// config to update
+    #[argh(positional)]
+    config: PathBuf,
+
+    /// state saved by export-state
+    #[argh(option)]
+    state: PathBuf,
+
+    /// replace existing results instead of adding to them
+    #[argh(switch)]
+    replace: bool,
+
+    /// output location for updated config
+    #[argh(option, short = 'o', default = "PathBuf::from(\"./new-config.json\")")]
+    output: PathBuf,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromArgs, PartialEq)]
+/// Combine the results of two configs, matching scripts by name
+#[argh(subcommand, name = "merge")]
+struct MergeOptions {
+    /// config whose settings are kept
+    #[argh(positional)]
+    first: PathBuf,
+
+    /// config whose results are added in
+    #[argh(positional)]
+    second: PathBuf,
+
+    /// output location for the merged config
+    #[argh(option, short = 'o', default = "PathBuf::from(\"./merged.json\")")]
+    output: PathBuf,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromArgs, PartialEq)]
+/// Scale the positive biases of a config so they average 1.0, keeping their ratios
+#[argh(subcommand, name = "normalize")]
+struct NormalizeOptions {
+    /// config to normalize
+    #[argh(positional)]
+    config: PathBuf,
+
+    /// output location for the normalized config
+    #[argh(option, short = 'o', default = "PathBuf::from(\"./new-config.json\")")]
+    output: PathBuf,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromArgs, PartialEq)]
+/// Simulate a run against made up interesting rates, without running any command, to try out settings
+#[argh(subcommand, name = "simulate")]
+struct SimulateOptions {
+    /// config to simulate, starting from its results. The config is not modified
+    #[argh(positional)]
+    config: PathBuf,
+
+    /// true interesting rate of a script as name=probability, e.g. fuzz=0.3. Needed for every script
+    #[argh(option)]
+    truth: Vec<Truth>,
+
+    /// number of steps to simulate
+    #[argh(option, default = "1000")]
+    steps: usize,
+
+    /// ignore runtime when selecting scripts
+    #[argh(switch, short = 'i')]
+    ignore_runtime: bool,
+
+    /// selection strategy: thompson (default), ucb1, epsilon-greedy:<epsilon> (e.g. epsilon-greedy:0.1),
+    /// or the round-robin and uniform baselines
+    #[argh(option, default = "Strategy::default()")]
+    strategy: Strategy,
+
+    /// seed for selection and the simulated results, so simulations with the same seed are identical
+    #[argh(option)]
+    seed: Option<u64>,
+}
+
+/// A script's true interesting rate for `simulate`, given as `name=probability`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+struct Truth {
+    name: String,
+    probability: f64,
+}
+
+impl FromStr for Truth {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((name, probability)) = s.rsplit_once('=') else {
+            return Err(format!(
+                "Invalid truth {s}. Expected name=probability, e.g. fuzz=0.3"
+            ));
+        };
+        match probability.parse::<f64>() {
+            Ok(probability) if (0.0..=1.0).contains(&probability) => Ok(Truth {
+                name: name.to_string(),
+                probability,
+            }),
+            _ => Err(format!(
+                "Invalid probability {probability} for {name}. Expected a number from 0 to 1"
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, FromArgs, PartialEq)]
+/// Compare challenger scripts against their champions and recommend promoting confident winners
+#[argh(subcommand, name = "promote")]
+struct PromoteOptions {
+    /// config containing challenger scripts
+    #[argh(positional)]
+    config: PathBuf,
+
+    /// probability the challenger is more interesting needed to promote it
+    #[argh(option, default = "0.95")]
+    threshold: f64,
+
+    /// runs both scripts need before they are compared
+    #[argh(option, default = "20")]
+    min_runs: u64,
+
+    /// monte carlo samples used to estimate the probability
+    #[argh(option, default = "10000")]
+    samples: usize,
+
+    /// replace each beaten champion with its challenger and save the config
+    #[argh(switch)]
+    auto_promote: bool,
+
+    /// output location for the config updated by --auto-promote
+    #[argh(option, short = 'o', default = "PathBuf::from(\"./new-config.json\")")]
+    output: PathBuf,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromArgs, PartialEq)]
+/// Print a one-line summary of a config, without plots
+#[argh(subcommand, name = "status")]
+struct StatusOptions {
+    /// config to summarize
     #[argh(positional)]
     config: PathBuf,
+
+    /// seed for sampling the best script
+    #[argh(option)]
+    seed: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromArgs, PartialEq)]
+/// Check whether faster scripts tend to be more interesting
+#[argh(subcommand, name = "analyze")]
+struct AnalyzeOptions {
+    /// config to analyze
+    #[argh(positional)]
+    config: PathBuf,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromArgs, PartialEq)]
+/// Plot how a script's score changes with its bias against the top competing script
+#[argh(subcommand, name = "explain-bias")]
+struct ExplainBiasOptions {
+    /// config containing the script
+    #[argh(positional)]
+    config: PathBuf,
+
+    /// name of the script to plot
+    #[argh(option)]
+    script: String,
+
+    /// largest bias to plot. Defaults to twice the bias needed to overtake the top competitor
+    #[argh(option)]
+    max_bias: Option<f64>,
+
+    /// also write the plot to this svg file
+    #[argh(option)]
+    image: Option<PathBuf>,
+}
+
+/// argh reads any argument starting with `-` as an option, so a bare `-` for a config read from stdin is moved
+/// after a `--`, where it is read as the positional. `-` as the value of `-o` is left in place.
+fn stdin_config_last(args: Vec<String>) -> Vec<String> {
+    if args.iter().any(|arg| arg == "--") {
+        return args;
+    }
+    let mut moved = false;
+    let mut kept: Vec<String> = Vec::with_capacity(args.len() + 1);
+    for arg in args {
+        let is_output = matches!(kept.last().map(String::as_str), Some("-o" | "--output"));
+        if arg == "-" && !is_output {
+            moved = true;
+        } else {
+            kept.push(arg);
+        }
+    }
+    if moved {
+        kept.extend(["--".to_string(), "-".to_string()]);
+    }
+    kept
+}
+
+fn parse_args() -> Args {
+    let strings: Vec<String> = std::env::args().collect();
+    let cmd = Path::new(&strings[0])
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or(&strings[0])
+        .to_string();
+    let strings = stdin_config_last(strings[1..].to_vec());
+    let strs: Vec<&str> = strings.iter().map(String::as_str).collect();
+    Args::from_args(&[&cmd], &strs).unwrap_or_else(|early_exit| match early_exit.status {
+        Ok(()) => {
+            println!("{}", early_exit.output);
+            std::process::exit(0)
+        }
+        Err(()) => {
+            eprintln!(
+                "{}\nRun {cmd} --help for more information.",
+                early_exit.output
+            );
+            std::process::exit(1)
+        }
+    })
 }
 
 fn main() {
-    let args: Args = argh::from_env();
+    let args = parse_args();
+    init_logger(args.verbose, args.quiet);
 
     match args.subcommand {
         SubCommands::New(new_opts) => {
+            let mut tests = new_opts
+                .from_file
+                .as_ref()
+                .map(parse_mapping_file)
+                .unwrap_or_default();
+            tests.extend(new_opts.tests);
+
+            let mut names = HashSet::new();
+            if let Some((name, _)) = tests.iter().find(|(name, _)| !names.insert(name)) {
+                eprintln!("Duplicate test name {name}. Each script needs a unique name.");
+                std::process::exit(1);
+            }
+
             let config = Config {
-                scripts: new_opts
-                    .tests
+                scripts: tests
                     .iter()
                     .map(|(test_name, test_command)| Script {
                         name: test_name.to_string(),
                         command: test_command.to_string(),
+                        shell: false,
+                        empty_unset_variables: false,
                         results: ThompsonInfo {
-                            interesting: 0,
-                            uninteresting: 0,
+                            interesting: 0.0,
+                            uninteresting: 0.0,
                         },
+                        prior_interesting: 1.0,
+                        prior_uninteresting: 1.0,
                         runcount: 0,
                         avgruntime_ms: None,
+                        total_runtime_ms: 0,
+                        avgpeakmem_kb: None,
                         bias: NotNan::new(1.0).unwrap(),
+                        cost_per_ms: None,
                         limit: None,
+                        interesting_codes: vec![],
+                        uninteresting_codes: vec![],
+                        penalty_codes: vec![],
+                        retries: 0,
+                        retry_on_codes: vec![],
+                        success_is_interesting: false,
+                        crash_is_interesting: false,
+                        timeout_ms: None,
+                        rlimit_as_mb: None,
+                        rlimit_cpu_s: None,
+                        rlimit_fsize_mb: None,
+                        challenger_of: None,
+                        schedule: None,
+                        tags: vec![],
+                        depends_on: vec![],
+                        dependency_threshold: None,
+                        stderr_line_threshold: None,
+                        count_blank_stderr_lines: false,
+                        decay: None,
+                        weighted: false,
+                        max_runtime_ms: None,
+                        interesting_regex: None,
+                        first_interesting_step: None,
+                        args: None,
+                        run_limit: None,
+                        runtime_median: None,
+                        recent_runtimes_ms: vec![],
                     })
                     .collect(),
+                runtime_reference_ms: None,
+                capture_interesting_output: false,
+                runtime_metric: RuntimeMetric::default(),
             };
 
             save_config(&config, &new_opts.path);
         }
-        SubCommands::Run(run_opts) => {
+        SubCommands::Run(mut run_opts) => {
+            if run_opts.jobs == 0 {
+                eprintln!("--jobs must be at least 1");
+                std::process::exit(1);
+            }
+            if run_opts.in_place {
+                if is_stdio(&run_opts.config) {
+                    eprintln!("--in-place needs a config file to write back to, not stdin");
+                    std::process::exit(1);
+                }
+                run_opts.output = run_opts.config.clone();
+            }
+            if let Some(budget_ms) = run_opts.budget_ms {
+                if run_opts.max_duration.is_some() {
+                    eprintln!("--budget-ms and --max-duration can't be used together");
+                    std::process::exit(1);
+                }
+                run_opts.max_duration = Some(RunDuration(Duration::from_millis(budget_ms)));
+            }
+            if let Err(err) = check_config_extension(&run_opts.output) {
+                eprintln!("{err}");
+                std::process::exit(1);
+            }
+
             let mut config = parse_config(&run_opts.config);
+            if config.scripts.is_empty() {
+                println!("No scripts in config. Exiting...");
+                return;
+            }
+
+            let runtime_reference_ms = runtime_reference_ms(&config, args.runtime_reference);
+            let priors = run_opts
+                .priors
+                .as_ref()
+                .map(|path| {
+                    parse_priors(path).unwrap_or_else(|err| {
+                        eprintln!("{err}");
+                        std::process::exit(1);
+                    })
+                })
+                .unwrap_or_default();
+
+            for name in priors.keys() {
+                if !config.scripts.iter().any(|script| &script.name == name) {
+                    warn!("Prior given for unknown script {name}");
+                }
+            }
+
+            let options = SelectionOptions {
+                priors,
+                ignore_runtime: run_opts.ignore_runtime,
+                runtime_reference_ms,
+                min_effective_bias: args.min_effective_bias,
+                warmup_until_width: run_opts.warmup_until_width,
+                interleave_skip_below: run_opts.interleave.then_some(run_opts.skip_below),
+                strategy: run_opts.strategy,
+                min_samples: args.min_samples,
+                cooldown: run_opts.cooldown,
+                tags: run_opts.tag.clone(),
+            };
+            let mut state = SelectionState::new(run_opts.seed);
+
+            if run_opts.dry_run {
+                // Results never change, so every step samples from the same posterior.
+                for completed in 1..=run_opts.steps.unwrap_or(10) {
+                    let Some(index) =
+                        choose_script(&config, &options, &mut state, local_minute_of_day())
+                    else {
+                        println!("No runnable script is under its limit and inside its schedule.");
+                        break;
+                    };
+                    state.last_selected = Some(index);
+                    if run_opts.explain {
+                        explain_selection(&config, &state, index);
+                    }
+                    println!("Step {completed}: {}", config.scripts[index].name);
+                }
+                return;
+            }
+
+            let to_stdout = is_stdio(&run_opts.output);
+            if to_stdout && run_opts.explain {
+                eprintln!("--explain prints to stdout, so it can't be combined with writing the config to stdout");
+                std::process::exit(1);
+            }
+            if to_stdout && run_opts.tail_output.is_some() {
+                eprintln!("--tail-output prints to stdout, so it can't be combined with writing the config to stdout");
+                std::process::exit(1);
+            }
+            let history = run_opts.history.as_ref().map(|path| {
+                open_history(path).unwrap_or_else(|err| {
+                    eprintln!("Could not open history log {}: {}", path.display(), err);
+                    std::process::exit(1);
+                })
+            });
+            let execution = ExecutionOptions {
+                tail_output: run_opts.tail_output,
+                timeout_ms: run_opts.timeout,
+                history,
+                capture_dir: config
+                    .capture_interesting_output
+                    .then(|| capture_dir(&run_opts.output)),
+                explain: run_opts.explain,
+            };
+
+            let unbounded = run_opts.max_duration.is_some()
+                || run_opts.until_file_exists.is_some()
+                || run_opts.stop_after_interesting.is_some();
+            let steps = match run_opts.steps {
+                Some(steps) => Some(steps),
+                None if unbounded => None,
+                None => Some(10),
+            };
+            if run_opts.watch && is_stdio(&run_opts.config) {
+                eprintln!("--watch needs a config file to watch, not stdin");
+                std::process::exit(1);
+            }
+            let mut watcher = run_opts.watch.then(|| {
+                ConfigWatcher::new(&run_opts.config).unwrap_or_else(|err| {
+                    eprintln!("Could not watch {}: {err}", run_opts.config.display());
+                    std::process::exit(1);
+                })
+            });
+            let start = Instant::now();
+            let mut last_checkpoint = start;
+            let mut completed = 0;
+
+            while steps.is_none_or(|steps| completed < steps) {
+                if watcher.as_mut().is_some_and(ConfigWatcher::changed) {
+                    match try_parse_config(&run_opts.config) {
+                        Ok(mut fresh) => {
+                            carry_over_results(&config, &mut fresh);
+                            config = fresh;
+                            state.scripts_changed();
+                            report(
+                                to_stdout,
+                                &format!(
+                                    "Reloaded {} after {completed} steps.",
+                                    run_opts.config.display()
+                                ),
+                            );
+                        }
+                        Err(err) => {
+                            warn!("{err}. Keeping the last config that could be read.")
+                        }
+                    }
+                }
+                if let Some(RunDuration(max_duration)) = run_opts.max_duration {
+                    if start.elapsed() >= max_duration {
+                        report(
+                            to_stdout,
+                            &format!(
+                                "Reached max duration after {completed} steps, saving and exiting."
+                            ),
+                        );
+                        break;
+                    }
+                }
+                if let Some(path) = &run_opts.until_file_exists {
+                    if path.exists() {
+                        report(
+                            to_stdout,
+                            &format!(
+                                "Found {} after {completed} steps, saving and exiting.",
+                                path.display()
+                            ),
+                        );
+                        break;
+                    }
+                }
+
+                let jobs =
+                    steps.map_or(run_opts.jobs, |steps| run_opts.jobs.min(steps - completed));
+                let ran = step(&mut config, &options, &mut state, &execution, jobs);
+                if ran == 0 {
+                    if !config.scripts.is_empty()
+                        && config.scripts.iter().all(|script| !script.is_active())
+                    {
+                        report(
+                            to_stdout,
+                            &format!("All scripts reached their limits, stopping early after {completed} steps, saving and exiting."),
+                        );
+                    } else {
+                        report(
+                            to_stdout,
+                            "No runnable script is under its limit and inside its schedule, saving and exiting.",
+                        );
+                    }
+                    break;
+                }
+                completed += ran;
 
-            for _ in 0..run_opts.steps {
-                step(&mut config, run_opts.ignore_runtime);
+                if let Some(threshold) = run_opts.stop_after_interesting {
+                    if state.interesting_found >= threshold {
+                        report(
+                            to_stdout,
+                            &format!("Found {} interesting results after {completed} steps, saving and exiting.",
+                            state.interesting_found),
+                        );
+                        break;
+                    }
+                }
+
+                if let Some(RunDuration(checkpoint_every)) = run_opts.checkpoint_every {
+                    // Checkpoints to stdout would print the config over and over.
+                    if !to_stdout && last_checkpoint.elapsed() >= checkpoint_every {
+                        debug!("Checkpointing after {} steps", completed);
+                        save_config(&config, &run_opts.output);
+                        last_checkpoint = Instant::now();
+                        // Checkpointing over the watched config isn't an edit to reload.
+                        if let Some(watcher) = watcher
+                            .as_mut()
+                            .filter(|_| run_opts.output == run_opts.config)
+                        {
+                            watcher.saved();
+                        }
+                    }
+                }
             }
 
+            if steps == Some(completed) && unbounded {
+                report(
+                    to_stdout,
+                    &format!("Completed {completed} steps, saving and exiting."),
+                );
+            }
+            if run_opts.history.is_some() {
+                report(
+                    to_stdout,
+                    &format!(
+                        "Estimated regret: {:.2} interesting cases over {} steps.",
+                        state.regret.cumulative(&config.scripts),
+                        state.regret.steps()
+                    ),
+                );
+            }
             save_config(&config, &run_opts.output);
+            if to_stdout {
+                return;
+            }
 
             let config = parse_config(&run_opts.output);
 
-            plot_top_3(&config.scripts);
+            plot_top_3(&config.scripts, &mut std::io::stdout()).unwrap();
             if !run_opts.ignore_runtime {
-                plot_top_3_inverses(&config.scripts);
+                plot_top_3_inverses(
+                    &config.scripts,
+                    runtime_reference_ms,
+                    &mut std::io::stdout(),
+                )
+                .unwrap();
+            }
+            if let Some(path) = &run_opts.plot_out {
+                save_plots(
+                    &config.scripts,
+                    (!run_opts.ignore_runtime).then_some(runtime_reference_ms),
+                    path,
+                );
             }
         }
         SubCommands::Rank(rank_opts) => {
-            let config = parse_config(&rank_opts.config);
+            let mut config = parse_config(&rank_opts.config);
+            if config.scripts.is_empty() {
+                println!("No scripts in config. Exiting...");
+                return;
+            }
 
-            if rank_opts.ignore_runtime {
-                print_ranking(&config.scripts, rank_opts.verbose);
+            config
+                .scripts
+                .retain(|script| script.has_any_tag(&rank_opts.tag));
+            // Rank with the biases selection would use.
+            for script in &mut config.scripts {
+                script.bias = effective_bias(script.bias, args.min_effective_bias);
+            }
+            let runtime_reference_ms = runtime_reference_ms(&config, args.runtime_reference);
+
+            let display = DisplayOptions {
+                sort: rank_opts.sort,
+                min_runs: rank_opts.min_runs,
+                verbose: rank_opts.verbose,
+            };
+
+            let mut rng = seeded_rng(rank_opts.seed);
+            if rank_opts.json {
+                if rank_opts.strategy != Strategy::Thompson {
+                    eprintln!("--json is only supported with the thompson strategy");
+                    std::process::exit(1);
+                }
+                print_ranking_json(
+                    &config.scripts,
+                    rank_opts.ignore_runtime,
+                    config.runtime_metric,
+                    runtime_reference_ms,
+                    args.min_samples,
+                    &mut rng,
+                );
+                return;
+            }
+
+            if matches!(rank_opts.strategy, Strategy::RoundRobin | Strategy::Uniform) {
+                eprintln!("The round-robin and uniform strategies don't rank scripts");
+                std::process::exit(1);
+            }
+            if rank_opts.explain && rank_opts.strategy != Strategy::Thompson {
+                eprintln!("--explain is only supported with the thompson strategy");
+                std::process::exit(1);
+            }
+            // The ranking draws from its rng in script order, so a copy reproduces the same samples.
+            let mut explain_rng = rng.clone();
+
+            let ranking = if let Strategy::EpsilonGreedy(_) = rank_opts.strategy {
+                print_ranking_greedy(&config.scripts, &display)
+            } else if rank_opts.strategy == Strategy::Ucb1 {
+                print_ranking_ucb1(&config.scripts, &display)
+            } else if rank_opts.ignore_runtime {
+                print_ranking(&config.scripts, &display, &mut rng)
             } else {
                 if rank_opts.verbose {
-                    plot_top_3_inverses(&config.scripts);
+                    plot_top_3_inverses(
+                        &config.scripts,
+                        runtime_reference_ms,
+                        &mut std::io::stdout(),
+                    )
+                    .unwrap();
                 }
 
                 let runtime = config
                     .scripts
                     .iter()
-                    .map(|x| &x.avgruntime_ms)
+                    .map(|x| x.runtime_cost(config.runtime_metric))
                     .collect::<Vec<_>>();
+                let runtime = runtime.iter().collect::<Vec<_>>();
                 let runtimes: &[&Option<NotNan<f64>>] = runtime.as_slice();
 
                 let user_biases = config.scripts.iter().map(|x| &x.bias).collect::<Vec<_>>();
@@ -363,7 +1383,30 @@ fn main() {
                     &config.scripts,
                     runtimes,
                     user_biases,
-                    rank_opts.verbose,
+                    runtime_reference_ms,
+                    args.min_samples,
+                    &display,
+                    &mut rng,
+                )
+            };
+
+            if rank_opts.explain {
+                print_ranking_explanation(
+                    &config.scripts,
+                    rank_opts.ignore_runtime,
+                    config.runtime_metric,
+                    runtime_reference_ms,
+                    args.min_samples,
+                    &mut explain_rng,
+                );
+            }
+
+            if rank_opts.explain_top {
+                print_explain_top(
+                    &config.scripts,
+                    &ranking,
+                    rank_opts.ignore_runtime,
+                    runtime_reference_ms,
                 );
             }
         }
@@ -375,54 +1418,422 @@ fn main() {
                 return;
             }
 
-            reset_state(&mut config, reset_opts.script);
+            if let Err(err) = reset_state(&mut config, reset_opts.script) {
+                eprintln!("{err}");
+                std::process::exit(1);
+            }
 
             save_config(&config, &reset_opts.output);
         }
         SubCommands::Summarize(summarize_opts) => {
-            let config = parse_config(&summarize_opts.config);
+            let mut config = parse_config(&summarize_opts.config);
+            if config.scripts.is_empty() {
+                println!("No scripts in config. Exiting...");
+                return;
+            }
+
+            config
+                .scripts
+                .retain(|script| script.has_any_tag(&summarize_opts.tag));
+            let runtime_reference_ms = runtime_reference_ms(&config, args.runtime_reference);
+
+            if let Some(name) = &summarize_opts.curve {
+                let Some(script) = config.scripts.iter().find(|script| &script.name == name) else {
+                    eprintln!("Could not find script {name} in config");
+                    std::process::exit(1);
+                };
+                print_posterior_curve(script, CURVE_POINTS);
+                return;
+            }
+
+            if let Some(path) = &summarize_opts.plot_out {
+                let inverses = !(summarize_opts.by_throughput || summarize_opts.ignore_runtime);
+                save_plots(
+                    &config.scripts,
+                    inverses.then_some(runtime_reference_ms),
+                    path,
+                );
+            }
+
+            if summarize_opts.format == SummaryFormat::Markdown {
+                if summarize_opts.by_throughput {
+                    eprintln!("--format markdown can't be combined with --by-throughput");
+                    std::process::exit(1);
+                }
+                print_ranking_markdown(
+                    &config.scripts,
+                    summarize_opts.ignore_runtime,
+                    config.runtime_metric,
+                    runtime_reference_ms,
+                    args.min_samples,
+                    &mut rand::thread_rng(),
+                );
+                return;
+            }
+
+            plot_top_3(&config.scripts, &mut std::io::stdout()).unwrap();
 
-            plot_top_3(&config.scripts);
+            let display = DisplayOptions {
+                sort: summarize_opts.sort,
+                min_runs: 0,
+                verbose: true,
+            };
 
-            if summarize_opts.ignore_runtime {
-                print_ranking(&config.scripts, true);
+            if summarize_opts.by_throughput {
+                print_ranking_throughput(&config.scripts);
+            } else if summarize_opts.ignore_runtime {
+                print_ranking(&config.scripts, &display, &mut rand::thread_rng());
+                print_selection_probabilities(
+                    &config.scripts,
+                    None,
+                    runtime_reference_ms,
+                    args.min_samples,
+                    &mut rand::thread_rng(),
+                );
             } else {
-                plot_top_3_inverses(&config.scripts);
+                plot_top_3_inverses(
+                    &config.scripts,
+                    runtime_reference_ms,
+                    &mut std::io::stdout(),
+                )
+                .unwrap();
 
                 let runtime = config
                     .scripts
                     .iter()
-                    .map(|x| &x.avgruntime_ms)
+                    .map(|x| x.runtime_cost(config.runtime_metric))
                     .collect::<Vec<_>>();
+                let runtime = runtime.iter().collect::<Vec<_>>();
                 let runtimes: &[&Option<NotNan<f64>>] = runtime.as_slice();
 
                 let user_biases = config.scripts.iter().map(|x| &x.bias).collect::<Vec<_>>();
                 let user_biases: &[&NotNan<f64>] = user_biases.as_slice();
 
-                print_ranking_bias_runtime(&config.scripts, runtimes, user_biases, true);
+                print_ranking_bias_runtime(
+                    &config.scripts,
+                    runtimes,
+                    user_biases,
+                    runtime_reference_ms,
+                    args.min_samples,
+                    &display,
+                    &mut rand::thread_rng(),
+                );
+                print_selection_probabilities(
+                    &config.scripts,
+                    Some(runtimes),
+                    runtime_reference_ms,
+                    args.min_samples,
+                    &mut rand::thread_rng(),
+                );
+            }
+
+            print_first_interesting_steps(&config.scripts);
+
+            if summarize_opts.suggest_bias {
+                print_bias_suggestions(
+                    &config.scripts,
+                    summarize_opts.ignore_runtime,
+                    runtime_reference_ms,
+                    &mut rand::thread_rng(),
+                );
             }
         }
         SubCommands::Lint(lint_opts) => {
             let config = parse_config(&lint_opts.config);
-            let mut seen_zero = false;
-            for script in config.scripts {
-                if script.bias == 0. {
-                    println!("{} Warning: A bias of 0 will only run after all other scripts reach their limit.", script.name);
-                    if seen_zero {
-                        println!("{} ERROR: Multiple scripts with bias zero will not be ranked relative to each other. YOU PROBABLY DON'T WANT THIS.", script.name);
-                        println!("{:1$}They will always be randomly run with equal probability regardless of interestingness/runtime.", "", script.name.len() + 8);
-                    }
-                    seen_zero = true;
+            let findings = lint_config(&config, lint_opts.max_bias);
+            for finding in &findings {
+                println!("{finding}");
+            }
+
+            if lint_opts.strict
+                && findings
+                    .iter()
+                    .any(|finding| finding.severity == Severity::Error)
+            {
+                std::process::exit(1);
+            }
+        }
+        SubCommands::ConfigSet(set_opts) => {
+            if set_opts.limit.is_some() && set_opts.clear_limit {
+                eprintln!("--limit and --clear-limit can't be used together");
+                std::process::exit(1);
+            }
+            let mut config = parse_config(&set_opts.config);
+
+            let Some(script) = config
+                .scripts
+                .iter_mut()
+                .find(|script| script.name == set_opts.script)
+            else {
+                eprintln!("Could not find script {} in config", set_opts.script);
+                std::process::exit(1);
+            };
+
+            if let Some(command) = set_opts.command {
+                script.command = command;
+            }
+            if let Some(bias) = set_opts.bias {
+                script.bias = bias;
+            }
+            if let Some(limit) = set_opts.limit {
+                script.limit = Some(limit);
+            }
+            if set_opts.clear_limit {
+                script.limit = None;
+            }
+
+            let errors = lint_config(&config, DEFAULT_MAX_BIAS)
+                .into_iter()
+                .filter(|finding| {
+                    finding.script == set_opts.script && finding.severity == Severity::Error
+                })
+                .collect::<Vec<_>>();
+            if !errors.is_empty() {
+                for finding in errors {
+                    eprintln!("{finding}");
                 }
+                std::process::exit(1);
+            }
+
+            save_config(&config, &set_opts.output);
+        }
+        SubCommands::ExplainBias(explain_opts) => {
+            let config = parse_config(&explain_opts.config);
+            let runtime_reference_ms = runtime_reference_ms(&config, args.runtime_reference);
+
+            let Some(index) = config
+                .scripts
+                .iter()
+                .position(|script| script.name == explain_opts.script)
+            else {
+                eprintln!("Could not find script {} in config", explain_opts.script);
+                std::process::exit(1);
+            };
+
+            plot_bias_tradeoff(
+                &config.scripts,
+                index,
+                explain_opts.max_bias,
+                runtime_reference_ms,
+                explain_opts.image.as_ref(),
+            );
+        }
+        SubCommands::Doctor(doctor_opts) => {
+            let config = parse_config(&doctor_opts.config);
+
+            let Some(script) = config
+                .scripts
+                .iter()
+                .find(|script| script.name == doctor_opts.script)
+            else {
+                eprintln!("Could not find script {} in config", doctor_opts.script);
+                std::process::exit(1);
+            };
+
+            if doctor_opts.check_determinism {
+                check_determinism(script, doctor_opts.runs);
+            } else {
+                println!("No diagnostics selected. Try --check-determinism.");
+            }
+        }
+        SubCommands::Hammer(hammer_opts) => {
+            let config = parse_config(&hammer_opts.config);
+
+            let Some(script) = config
+                .scripts
+                .iter()
+                .find(|script| script.name == hammer_opts.script)
+            else {
+                eprintln!("Could not find script {} in config", hammer_opts.script);
+                std::process::exit(1);
+            };
+
+            hammer(script, hammer_opts.max_attempts);
+        }
+        SubCommands::Status(status_opts) => {
+            let config = parse_config(&status_opts.config);
+            println!(
+                "{}",
+                status_line(&config.scripts, &mut seeded_rng(status_opts.seed))
+            );
+        }
+        SubCommands::Analyze(analyze_opts) => {
+            let config = parse_config(&analyze_opts.config);
+            print_runtime_correlation(&config.scripts);
+        }
+        SubCommands::Promote(promote_opts) => {
+            let mut config = parse_config(&promote_opts.config);
+            let promotions = find_promotions(&config, &promote_opts);
+
+            if promote_opts.auto_promote && !promotions.is_empty() {
+                promote(&mut config, &promotions);
+                save_config(&config, &promote_opts.output);
+            }
+        }
+        SubCommands::Export(export_opts) => {
+            let config = parse_config(&export_opts.config);
+            save_csv(&config, &export_opts.output);
+        }
+        SubCommands::Schema(schema_opts) => match &schema_opts.output {
+            Some(path) => save_schema(path),
+            None => println!("{}", config_schema()),
+        },
+        SubCommands::ExportState(export_opts) => {
+            let config = parse_config(&export_opts.config);
+            save_state(&export_state(&config), &export_opts.output);
+        }
+        SubCommands::ImportState(import_opts) => {
+            let mut config = parse_config(&import_opts.config);
+            let state = parse_state(&import_opts.state).unwrap_or_else(|err| {
+                eprintln!("{err}");
+                std::process::exit(1);
+            });
 
-                if script.bias < NotNan::new(0.).unwrap() {
-                    println!("{} ERROR: A negative bias rewards tests that take more time to find an interesting case.", script.name);
+            for name in import_state(&mut config, &state, import_opts.replace) {
+                eprintln!("Warning: state for {name} does not match any script in the config");
+            }
+            for script in &config.scripts {
+                if !state.contains_key(&script.name) {
+                    eprintln!(
+                        "Warning: no state for {}, leaving it unchanged",
+                        script.name
+                    );
                 }
+            }
+
+            save_config(&config, &import_opts.output);
+        }
+        SubCommands::Merge(merge_opts) => {
+            let first = parse_config(&merge_opts.first);
+            let second = parse_config(&merge_opts.second);
+
+            let (merged, mismatched) = merge_configs(&first, &second);
+            for name in mismatched {
+                eprintln!(
+                    "Warning: bias or limit of {name} differ, keeping the values from {}",
+                    merge_opts.first.display()
+                );
+            }
 
-                if script.limit == Some(0) {
-                    println!("{} Warning: Limit of 0. This will stop this script from ever running. Leave undefined to have no limit.", script.name)
+            save_config(&merged, &merge_opts.output);
+        }
+        SubCommands::Simulate(simulate_opts) => {
+            let mut config = parse_config(&simulate_opts.config);
+            if config.scripts.is_empty() {
+                println!("No scripts to simulate. Exiting...");
+                return;
+            }
+
+            for truth in &simulate_opts.truth {
+                if !config
+                    .scripts
+                    .iter()
+                    .any(|script| script.name == truth.name)
+                {
+                    eprintln!("Could not find script {} in config", truth.name);
+                    std::process::exit(1);
                 }
             }
+            let truths = config
+                .scripts
+                .iter()
+                .map(|script| {
+                    let truth = simulate_opts
+                        .truth
+                        .iter()
+                        .find(|truth| truth.name == script.name);
+                    truth.map(|truth| truth.probability).unwrap_or_else(|| {
+                        eprintln!("No --truth given for {}", script.name);
+                        std::process::exit(1);
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            let options = SelectionOptions {
+                ignore_runtime: simulate_opts.ignore_runtime,
+                runtime_reference_ms: runtime_reference_ms(&config, args.runtime_reference),
+                min_effective_bias: args.min_effective_bias,
+                strategy: simulate_opts.strategy,
+                min_samples: args.min_samples,
+                ..SelectionOptions::default()
+            };
+            let mut state = SelectionState::new(simulate_opts.seed);
+            let simulation = simulate(
+                &mut config,
+                &options,
+                &mut state,
+                &truths,
+                simulate_opts.steps,
+                &mut seeded_rng(simulate_opts.seed),
+            );
+
+            println!("Simulated {} steps.", simulation.steps);
+            let mut order = (0..config.scripts.len()).collect::<Vec<_>>();
+            order.sort_by_key(|index| std::cmp::Reverse(simulation.runs[*index]));
+            for (rank, index) in order.iter().enumerate() {
+                let script = &config.scripts[*index];
+                let observed = empirical_rate(&script.results)
+                    .map_or("none".to_string(), |rate| format!("{rate:.4}"));
+                println!(
+                    "{}: {}\n- Runs: {}\n- Observed rate: {observed}, truth {}",
+                    rank + 1,
+                    script.name,
+                    simulation.runs[*index],
+                    truths[*index]
+                );
+            }
+            let best = (0..truths.len())
+                .max_by(|a, b| truths[*a].total_cmp(&truths[*b]))
+                .unwrap();
+            println!(
+                "Regret: {:.2} expected interesting cases missed against always running {}.",
+                simulation.regret, config.scripts[best].name
+            );
+        }
+        SubCommands::Normalize(normalize_opts) => {
+            let mut config = parse_config(&normalize_opts.config);
+
+            for name in normalize_biases(&mut config) {
+                eprintln!("Skipping {name}: a bias of 0 or below has its own meaning and is kept");
+            }
+
+            save_config(&config, &normalize_opts.output);
         }
     }
 }
+
+#[test]
+fn test_run_duration_from_str() {
+    assert_eq!(
+        "8h".parse::<RunDuration>(),
+        Ok(RunDuration(Duration::from_secs(8 * 3600)))
+    );
+    assert_eq!(
+        "1.5m".parse::<RunDuration>(),
+        Ok(RunDuration(Duration::from_secs(90)))
+    );
+    assert_eq!(
+        "250ms".parse::<RunDuration>(),
+        Ok(RunDuration(Duration::from_millis(250)))
+    );
+    assert!("8".parse::<RunDuration>().is_err());
+    assert!("h".parse::<RunDuration>().is_err());
+    assert!("8d".parse::<RunDuration>().is_err());
+    assert!("1e30s".parse::<RunDuration>().is_err());
+    assert!("100000000000000000000000h".parse::<RunDuration>().is_err());
+}
+
+#[test]
+fn test_stdin_config_last() {
+    let args = |line: &str| line.split(' ').map(String::from).collect::<Vec<_>>();
+    assert_eq!(stdin_config_last(args("lint -")), args("lint -- -"));
+    assert_eq!(
+        stdin_config_last(args("run - --steps 1 -o -")),
+        args("run --steps 1 -o - -- -")
+    );
+    assert_eq!(
+        stdin_config_last(args("run config.json -o -")),
+        args("run config.json -o -")
+    );
+    assert_eq!(stdin_config_last(args("lint -- -")), args("lint -- -"));
+}
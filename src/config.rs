@@ -1,45 +1,979 @@
 use std::{
+    collections::{BTreeMap, HashMap},
     fs::File,
     io::{Read, Write},
-    path::PathBuf,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver},
 };
 
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
 use ordered_float::NotNan;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use crate::thompson::ThompsonInfo;
+use crate::{
+    median::StreamingMedian,
+    process::ResourceLimits,
+    schedule::Schedule,
+    thompson::{serialize_count, Prior, ThompsonInfo},
+};
+
+/// File formats a config can be stored in.
+enum ConfigFormat {
+    Json,
+    Toml,
+}
+
+/// Choose the format from the file extension. Paths without one are JSON.
+fn config_format(path: &Path) -> Result<ConfigFormat, String> {
+    match path.extension().and_then(|extension| extension.to_str()) {
+        None | Some("json") => Ok(ConfigFormat::Json),
+        Some("toml") => Ok(ConfigFormat::Toml),
+        Some(extension) => Err(format!(
+            "Unsupported config extension .{extension} for {}. Expected .json or .toml",
+            path.display()
+        )),
+    }
+}
+
+/// Check a config can be saved to `path`, before doing work whose results would be lost.
+pub fn check_config_extension(path: &Path) -> Result<(), String> {
+    config_format(path).map(|_| ())
+}
 
+/// A config path of `-` reads the config from stdin or saves it to stdout, as JSON.
+pub fn is_stdio(path: &Path) -> bool {
+    path.as_os_str() == "-"
+}
+
+/// Read and check a config, exiting with the reason on stderr if it can't be used.
 pub fn parse_config(config_path: &PathBuf) -> Config {
-    let mut file = File::open(config_path).unwrap();
+    try_parse_config(config_path).unwrap_or_else(|err| {
+        eprintln!("{err}");
+        std::process::exit(1);
+    })
+}
+
+/// Read a config, rejecting any that can't be parsed or has an infinite bias.
+pub fn try_parse_config(config_path: &PathBuf) -> Result<Config, String> {
     let mut data = String::new();
-    file.read_to_string(&mut data).unwrap();
+    let read = if is_stdio(config_path) {
+        std::io::stdin().read_to_string(&mut data)
+    } else {
+        File::open(config_path).and_then(|mut file| file.read_to_string(&mut data))
+    };
+    read.map_err(|err| format!("Could not read {}: {err}", config_path.display()))?;
 
-    let config: Config = serde_json::from_str(&data).unwrap();
-    config
+    let config: Config = match config_format(config_path)? {
+        ConfigFormat::Json => serde_json::from_str(&data).map_err(|err| err.to_string()),
+        ConfigFormat::Toml => toml::from_str(&data).map_err(|err| err.to_string()),
+    }
+    .map_err(|err| format!("Could not parse {}: {err}", config_path.display()))?;
+
+    if let RuntimeMetric::Percentile(percentile) = config.runtime_metric {
+        if !(0. ..=100.).contains(&percentile) {
+            return Err(format!(
+                "Could not parse {}: a runtime percentile of {percentile} is not between 0 and 100.",
+                config_path.display()
+            ));
+        }
+    }
+
+    if let Some(script) = config.scripts.iter().find(|script| {
+        script
+            .cost_per_ms
+            .is_some_and(|cost| !cost.is_finite() || cost <= 0.)
+    }) {
+        return Err(format!(
+            "Could not parse {}: the cost_per_ms of {} must be above 0 and finite.",
+            config_path.display(),
+            script.name
+        ));
+    }
+
+    if let Some(script) = config.scripts.iter().find(|script| {
+        ![script.prior_interesting, script.prior_uninteresting]
+            .iter()
+            .all(|count| count.is_finite() && *count > 0.)
+    }) {
+        return Err(format!(
+            "Could not parse {}: the prior of {} must be above 0 and finite.",
+            config_path.display(),
+            script.name
+        ));
+    }
+
+    // An infinite bias would outweigh every other script however its results go.
+    if let Some(script) = config
+        .scripts
+        .iter()
+        .find(|script| script.bias.is_infinite())
+    {
+        return Err(format!(
+            "Could not parse {}: the bias of {} is {}. Biases must be finite.",
+            config_path.display(),
+            script.name,
+            script.bias
+        ));
+    }
+
+    Ok(config)
 }
 
+/// Save a config, exiting with the reason on stderr if it can't be saved.
 pub fn save_config(config: &Config, path: &PathBuf) {
-    let data = serde_json::to_string_pretty(config).unwrap();
+    try_save_config(config, path).unwrap_or_else(|err| {
+        eprintln!("{err}");
+        std::process::exit(1);
+    });
+}
+
+/// Save a config in the format of its extension, or print it to stdout as JSON for `-`.
+pub fn try_save_config(config: &Config, path: &PathBuf) -> Result<(), String> {
+    let data = match config_format(path)? {
+        ConfigFormat::Json => serde_json::to_string_pretty(config).unwrap(),
+        ConfigFormat::Toml => toml::to_string_pretty(config).unwrap(),
+    };
+    if is_stdio(path) {
+        println!("{data}");
+        return Ok(());
+    }
+    write_atomically(&data, path);
+    Ok(())
+}
 
+fn write_atomically(data: &str, path: &PathBuf) {
     let prefix = path.parent().unwrap();
     std::fs::create_dir_all(prefix).unwrap();
-    let mut file = File::create(path).unwrap();
+
+    // Write next to the destination and rename over it, so an interrupted save never leaves a truncated config.
+    let mut temp_path = path.clone().into_os_string();
+    temp_path.push(".tmp");
+    let mut file = File::create(&temp_path).unwrap();
 
     file.write_all(data.as_bytes()).unwrap();
+    file.sync_all().unwrap();
+    std::fs::rename(&temp_path, path).unwrap();
+}
+
+/// Learned results of one script: interesting, uninteresting, runcount, avgruntime_ms and total_runtime_ms.
+///
+/// State exported before the total was kept leaves it out, and its total is recovered from the average.
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+pub struct ScriptState(
+    #[serde(serialize_with = "serialize_count")] pub f64,
+    #[serde(serialize_with = "serialize_count")] pub f64,
+    pub u64,
+    pub Option<NotNan<f64>>,
+    #[serde(default)] pub u64,
+);
+
+/// Learned results of every script, keyed by script name.
+pub type State = BTreeMap<String, ScriptState>;
+
+pub fn export_state(config: &Config) -> State {
+    config
+        .scripts
+        .iter()
+        .map(|script| {
+            (
+                script.name.clone(),
+                ScriptState(
+                    script.results.interesting,
+                    script.results.uninteresting,
+                    script.runcount,
+                    script.avgruntime_ms,
+                    script.recorded_runtime_ms(),
+                ),
+            )
+        })
+        .collect()
+}
+
+pub fn save_state(state: &State, path: &PathBuf) {
+    let data = serde_json::to_string_pretty(state).unwrap();
+    write_atomically(&data, path);
+}
+
+pub fn parse_state(state_path: &PathBuf) -> Result<State, String> {
+    let data = std::fs::read_to_string(state_path)
+        .map_err(|err| format!("Could not read {}: {err}", state_path.display()))?;
+
+    serde_json::from_str(&data)
+        .map_err(|err| format!("Could not parse {}: {err}", state_path.display()))
+}
+
+/// Fold exported state back into a config by script name, returning the names with no matching script.
+///
+/// Counts are added to the existing ones and runtimes averaged by runcount, unless `replace` is set.
+/// Sums that would overflow stop at the largest count.
+pub fn import_state(config: &mut Config, state: &State, replace: bool) -> Vec<String> {
+    for script in config.scripts.iter_mut() {
+        let Some(ScriptState(
+            interesting,
+            uninteresting,
+            runcount,
+            avgruntime_ms,
+            total_runtime_ms,
+        )) = state.get(&script.name)
+        else {
+            continue;
+        };
+
+        let imported_runtime_ms = avgruntime_ms.map(|avg| match total_runtime_ms {
+            // Older state only carries the average, so the sum is recovered from it.
+            0 => (*avg * *runcount as f64).round() as u64,
+            total => *total,
+        });
+        if replace {
+            script.results = ThompsonInfo {
+                interesting: *interesting,
+                uninteresting: *uninteresting,
+            };
+            script.runcount = *runcount;
+            script.total_runtime_ms = 0;
+            script.avgruntime_ms = None;
+            if let Some(imported) = imported_runtime_ms {
+                script.set_total_runtime_ms(imported);
+            }
+        } else {
+            let had_runtime = script.avgruntime_ms.is_some();
+            let existing_runtime_ms = script.recorded_runtime_ms();
+            script.results = ThompsonInfo {
+                interesting: script.results.interesting + interesting,
+                uninteresting: script.results.uninteresting + uninteresting,
+            };
+            script.runcount = script.runcount.saturating_add(*runcount);
+            if had_runtime || imported_runtime_ms.is_some() {
+                script.set_total_runtime_ms(
+                    existing_runtime_ms.saturating_add(imported_runtime_ms.unwrap_or_default()),
+                );
+            }
+        }
+    }
+
+    state
+        .keys()
+        .filter(|name| !config.scripts.iter().any(|script| &script.name == *name))
+        .cloned()
+        .collect()
+}
+
+/// Keep the results `previous` accumulated for each script of `fresh` with the same name, so a config
+/// edited during a run takes effect without losing what the run found. Everything else, such as the bias,
+/// comes from `fresh`. Scripts new in `fresh` keep their own results.
+pub fn carry_over_results(previous: &Config, fresh: &mut Config) {
+    for script in fresh.scripts.iter_mut() {
+        let Some(old) = previous.scripts.iter().find(|old| old.name == script.name) else {
+            continue;
+        };
+        script.results = old.results.clone();
+        script.runcount = old.runcount;
+        script.avgruntime_ms = old.avgruntime_ms;
+        script.total_runtime_ms = old.total_runtime_ms;
+        script.avgpeakmem_kb = old.avgpeakmem_kb;
+        script.first_interesting_step = old.first_interesting_step;
+        script.runtime_median = old.runtime_median.clone();
+        script.recent_runtimes_ms = old.recent_runtimes_ms.clone();
+    }
+}
+
+/// Notices edits to a config file through file system events.
+///
+/// The config's directory is watched rather than the file, so saves that replace the file, as editors and
+/// `save_config` do, are still seen. An event only counts as an edit if the contents differ from the ones
+/// last seen, so saving the same config again isn't one.
+pub struct ConfigWatcher {
+    path: PathBuf,
+    contents: Option<String>,
+    events: Receiver<notify::Result<Event>>,
+    /// Kept so events keep arriving until the watcher is dropped.
+    _watcher: RecommendedWatcher,
+}
+
+impl ConfigWatcher {
+    pub fn new(path: &Path) -> notify::Result<Self> {
+        let (sender, events) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(sender)?;
+        let directory = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        watcher.watch(directory, RecursiveMode::NonRecursive)?;
+        Ok(ConfigWatcher {
+            path: path.to_path_buf(),
+            contents: std::fs::read_to_string(path).ok(),
+            events,
+            _watcher: watcher,
+        })
+    }
+
+    /// Whether the file was edited since the last call, or since the watcher was created.
+    pub fn changed(&mut self) -> bool {
+        let touched = self.events.try_iter().any(|event| {
+            event.is_ok_and(|event| {
+                !event.kind.is_access()
+                    && event
+                        .paths
+                        .iter()
+                        .any(|path| path.file_name() == self.path.file_name())
+            })
+        });
+        if !touched {
+            return false;
+        }
+        let contents = std::fs::read_to_string(&self.path).ok();
+        let changed = contents != self.contents;
+        self.contents = contents;
+        changed
+    }
+
+    /// Take the file as it is now as seen, after saving over it, so the save isn't reported as an edit.
+    pub fn saved(&mut self) {
+        self.contents = std::fs::read_to_string(&self.path).ok();
+    }
+}
+
+/// Scale every positive bias by the same factor so they average 1.0, keeping their ratios. Zero and negative
+/// biases have their own meaning, see `lint_config`, so they are left alone and their scripts' names returned.
+pub fn normalize_biases(config: &mut Config) -> Vec<String> {
+    let positive = config
+        .scripts
+        .iter()
+        .filter(|script| *script.bias > 0.0)
+        .map(|script| *script.bias)
+        .collect::<Vec<_>>();
+    if !positive.is_empty() {
+        let mean = positive.iter().sum::<f64>() / positive.len() as f64;
+        for script in config
+            .scripts
+            .iter_mut()
+            .filter(|script| *script.bias > 0.0)
+        {
+            script.bias /= mean;
+        }
+    }
+
+    config
+        .scripts
+        .iter()
+        .filter(|script| *script.bias <= 0.0)
+        .map(|script| script.name.clone())
+        .collect()
+}
+
+/// Fold the results `other` recorded into `script`, as if `script` had also made `other`'s runs.
+///
+/// Counts and runtime sums are added, stopping at the largest count, and peak memory is averaged by
+/// runcount. The earlier first interesting step is kept. Two streaming medians can't be combined, so the
+/// one built from more runtimes is kept, and `other`'s recent runtimes follow `script`'s.
+fn merge_results(script: &mut Script, other: &Script) {
+    let runcount = script.runcount.saturating_add(other.runcount);
+    script.avgpeakmem_kb = match (script.avgpeakmem_kb, other.avgpeakmem_kb) {
+        (Some(mine), Some(theirs)) if runcount > 0 => {
+            Some((mine * script.runcount as f64 + theirs * other.runcount as f64) / runcount as f64)
+        }
+        (mine, theirs) => mine.or(theirs),
+    };
+    script.first_interesting_step =
+        match (script.first_interesting_step, other.first_interesting_step) {
+            (Some(mine), Some(theirs)) => Some(mine.min(theirs)),
+            (mine, theirs) => mine.or(theirs),
+        };
+
+    let had_runtime = script.avgruntime_ms.is_some() || other.avgruntime_ms.is_some();
+    let total_runtime_ms = script
+        .recorded_runtime_ms()
+        .saturating_add(other.recorded_runtime_ms());
+    script.results = ThompsonInfo {
+        interesting: script.results.interesting + other.results.interesting,
+        uninteresting: script.results.uninteresting + other.results.uninteresting,
+    };
+    script.runcount = runcount;
+    if had_runtime {
+        script.set_total_runtime_ms(total_runtime_ms);
+    }
+
+    let recorded =
+        |median: &Option<StreamingMedian>| median.as_ref().map_or(0, StreamingMedian::count);
+    if recorded(&other.runtime_median) > recorded(&script.runtime_median) {
+        script.runtime_median = other.runtime_median.clone();
+    }
+    script
+        .recent_runtimes_ms
+        .extend_from_slice(&other.recent_runtimes_ms);
+    let excess = script
+        .recent_runtimes_ms
+        .len()
+        .saturating_sub(RECENT_RUNTIMES);
+    script.recent_runtimes_ms.drain(..excess);
+}
+
+/// Combine the results of two configs, such as the same campaign run on two machines.
+///
+/// Scripts are matched by name and everything they recorded is combined, see `merge_results`. Scripts in only
+/// one config are carried over unchanged. Everything else comes from `first`, and the names of matched scripts
+/// whose bias or limit disagree are returned.
+pub fn merge_configs(first: &Config, second: &Config) -> (Config, Vec<String>) {
+    let mut merged = first.clone();
+
+    let mut mismatched = vec![];
+    for script in &second.scripts {
+        match merged.scripts[..first.scripts.len()]
+            .iter_mut()
+            .find(|other| other.name == script.name)
+        {
+            Some(other) => {
+                if other.bias != script.bias || other.limit != script.limit {
+                    mismatched.push(script.name.clone());
+                }
+                merge_results(other, script);
+            }
+            None => merged.scripts.push(script.clone()),
+        }
+    }
+
+    (merged, mismatched)
+}
+
+/// Where `run` saves captured output for a run saving its config to `output`: `results.json` captures into
+/// `results.captures/`, and saving to stdout captures into `captures/`.
+pub fn capture_dir(output: &Path) -> PathBuf {
+    if is_stdio(output) {
+        return PathBuf::from("captures");
+    }
+    output.with_extension("captures")
+}
+
+/// JSON Schema of the config format, for editors to validate and autocomplete configs with.
+pub fn config_schema() -> String {
+    serde_json::to_string_pretty(&schemars::schema_for!(Config)).unwrap()
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
+pub fn save_schema(path: &PathBuf) {
+    write_atomically(&config_schema(), path);
+}
+
+/// One script's row in a CSV export.
+#[derive(Serialize)]
+struct CsvRow<'a> {
+    name: &'a str,
+    command: &'a str,
+    #[serde(serialize_with = "serialize_count")]
+    interesting: f64,
+    #[serde(serialize_with = "serialize_count")]
+    uninteresting: f64,
+    runcount: u64,
+    avgruntime_ms: Option<NotNan<f64>>,
+    bias: NotNan<f64>,
+    limit: Option<u64>,
+}
+
+/// Write one row per script, with a header. Unknown runtimes and missing limits are blank cells.
+pub fn export_csv(config: &Config) -> String {
+    let mut writer = csv::Writer::from_writer(vec![]);
+    for script in &config.scripts {
+        writer
+            .serialize(CsvRow {
+                name: &script.name,
+                command: &script.command,
+                interesting: script.results.interesting,
+                uninteresting: script.results.uninteresting,
+                runcount: script.runcount,
+                avgruntime_ms: script.avgruntime_ms,
+                bias: script.bias,
+                limit: script.limit,
+            })
+            .unwrap();
+    }
+    String::from_utf8(writer.into_inner().unwrap()).unwrap()
+}
+
+pub fn save_csv(config: &Config, path: &PathBuf) {
+    write_atomically(&export_csv(config), path);
+}
+
+/// Load an external overlay of per-script beta priors, keyed by script name, rejecting any that can't be
+/// read or has an alpha or beta that isn't positive.
+pub fn parse_priors(priors_path: &PathBuf) -> Result<HashMap<String, Prior>, String> {
+    let data = std::fs::read_to_string(priors_path)
+        .map_err(|err| format!("Could not read {}: {err}", priors_path.display()))?;
+
+    let entries: Vec<PriorEntry> = serde_json::from_str(&data)
+        .map_err(|err| format!("Could not parse {}: {err}", priors_path.display()))?;
+    entries
+        .into_iter()
+        .map(|entry| {
+            if !(entry.alpha > 0. && entry.beta > 0.) {
+                return Err(format!(
+                    "Could not parse {}: the prior for {} must have a positive alpha and beta.",
+                    priors_path.display(),
+                    entry.name
+                ));
+            }
+            Ok((
+                entry.name,
+                Prior {
+                    alpha: entry.alpha,
+                    beta: entry.beta,
+                },
+            ))
+        })
+        .collect()
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct PriorEntry {
+    name: String,
+    alpha: f64,
+    beta: f64,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, JsonSchema)]
 pub struct Config {
     pub scripts: Vec<Script>,
+    /// Runtime in milliseconds that gets a runtime scaler of 1. Defaults to 100, and `--runtime-reference`
+    /// overrides it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub runtime_reference_ms: Option<NotNan<f64>>,
+    /// Save the tail of every interesting run's output next to the run's output config. See
+    /// `capture_dir`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub capture_interesting_output: bool,
+    /// Which runtime statistic of each script the runtime scaler uses.
+    #[serde(default, skip_serializing_if = "RuntimeMetric::is_mean")]
+    pub runtime_metric: RuntimeMetric,
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+/// How many recent runtimes each script keeps for `RuntimeMetric::Percentile`.
+pub const RECENT_RUNTIMES: usize = 100;
+
+/// A statistic summarizing a script's runtimes.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum RuntimeMetric {
+    /// `avgruntime_ms`.
+    #[default]
+    Mean,
+    /// A streaming estimate of the median, for scripts whose runtimes are skewed by occasional slow runs.
+    Median,
+    /// This percentile, from 0 to 100, of the script's most recent runtimes, to penalize scripts for their
+    /// slowest runs.
+    Percentile(f64),
+}
+
+impl RuntimeMetric {
+    fn is_mean(&self) -> bool {
+        *self == RuntimeMetric::Mean
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, JsonSchema)]
 pub struct Script {
     pub name: String,
     pub command: String,
+    /// Run `command` through the platform shell (`sh -c` or `cmd /C`) instead of spawning it directly.
+    #[serde(default)]
+    pub shell: bool,
+    /// Expand references to unset environment variables in `command` to nothing instead of refusing to
+    /// start the script.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub empty_unset_variables: bool,
     pub results: ThompsonInfo,
+    /// Interesting cases the script is believed to have found before any run, the alpha of its prior.
+    /// Overridden by `run --priors`.
+    #[serde(
+        default = "default_prior_count",
+        skip_serializing_if = "is_default_prior_count"
+    )]
+    pub prior_interesting: f64,
+    /// Uninteresting runs the script is believed to have had before any run, the beta of its prior.
+    #[serde(
+        default = "default_prior_count",
+        skip_serializing_if = "is_default_prior_count"
+    )]
+    pub prior_uninteresting: f64,
     pub runcount: u64,
+    /// `total_runtime_ms / runcount`, kept in the config for ranking and reading.
     pub avgruntime_ms: Option<NotNan<f64>>,
+    /// Sum of every run's runtime. The average is derived from it, so it doesn't drift over many runs.
+    #[serde(default)]
+    pub total_runtime_ms: u64,
+    /// Average peak resident set size. Only recorded on Unix.
+    pub avgpeakmem_kb: Option<NotNan<f64>>,
     pub bias: NotNan<f64>,
+    /// Cost of a millisecond of the script's runtime, e.g. for scripts on metered or larger machines. The
+    /// runtime scaler then favors cheap scripts over fast ones. Scripts without it cost 1 per millisecond.
+    pub cost_per_ms: Option<f64>,
     pub limit: Option<u64>,
+    /// Exit codes that mark a run interesting. Empty means only 1.
+    #[serde(default)]
+    pub interesting_codes: Vec<i32>,
+    /// Exit codes that mark a run uninteresting. Empty means only 0.
+    #[serde(default)]
+    pub uninteresting_codes: Vec<i32>,
+    /// Exit codes that mark a run uninteresting and take back one earlier interesting case, for runs that
+    /// found something bad. Checked before the other codes.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub penalty_codes: Vec<i32>,
+    /// Run the script again, up to this many times, when it exits with one of `retry_on_codes`, for
+    /// transient failures like a port in use. Only the last attempt is classified, with the runtime of every
+    /// attempt.
+    #[serde(default)]
+    pub retries: u8,
+    /// Exit codes that mark a failed start worth retrying. See `retries`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub retry_on_codes: Vec<i32>,
+    /// Swap the default exit codes, so 0 is interesting and 1 uninteresting, for checks that exit 0 on a
+    /// hit. `interesting_codes` and `uninteresting_codes` still take precedence when set.
+    #[serde(default)]
+    pub success_is_interesting: bool,
+    /// Count a run killed by a signal, such as a SIGSEGV crash, as interesting. Unix only.
+    #[serde(default)]
+    pub crash_is_interesting: bool,
+    /// Kill the script if it runs longer than this. Timed out runs count towards `avgruntime_ms` only.
+    pub timeout_ms: Option<u64>,
+    /// Maximum address space of the script's process in megabytes. Unix only.
+    pub rlimit_as_mb: Option<u64>,
+    /// Maximum CPU time of the script's process in seconds. Unix only.
+    pub rlimit_cpu_s: Option<u64>,
+    /// Maximum size of a file written by the script's process in megabytes. Unix only.
+    pub rlimit_fsize_mb: Option<u64>,
+    /// Name of the champion script this script is a tweaked variant of. See the `promote` command.
+    pub challenger_of: Option<String>,
+    /// Local time windows the script may be selected in, e.g. `22:00-06:00`. See `Schedule`.
+    pub schedule: Option<String>,
+    /// Labels grouping scripts, e.g. by the component they target, for running one group with `--tag`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Names of scripts whose findings this script builds on. It isn't selected until each of them has
+    /// found `dependency_threshold` interesting cases.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub depends_on: Vec<String>,
+    /// Interesting cases each script in `depends_on` needs first. Defaults to 1.
+    pub dependency_threshold: Option<f64>,
+    /// When set, a run is interesting if it writes more than this many stderr lines, whatever its exit status.
+    pub stderr_line_threshold: Option<u64>,
+    /// Whether blank lines count towards `stderr_line_threshold`.
+    #[serde(default)]
+    pub count_blank_stderr_lines: bool,
+    /// Multiply the existing `results` by this, between 0 and 1, before adding each new result, so old
+    /// findings fade for targets whose interestingness changes over time.
+    pub decay: Option<f64>,
+    /// Score each run by the integer at the end of the last line of its stdout, such as a severity, adding
+    /// that many interesting cases. Runs that don't print one are classified as usual.
+    #[serde(default)]
+    pub weighted: bool,
+    /// Stop selecting the script once its average runtime is above this many milliseconds.
+    pub max_runtime_ms: Option<u64>,
+    /// A run is interesting if this regex matches its stdout or stderr, whatever its exit status. Runs that
+    /// don't match are classified as usual.
+    pub interesting_regex: Option<String>,
+    /// The step of the `run` in which the script first found something interesting. Never overwritten
+    /// once set, so it measures time to the first finding.
+    pub first_interesting_step: Option<u64>,
+    /// Arguments passed to the program as is. When set, `command` is only the program to run and is not
+    /// split on whitespace or run through the shell.
+    pub args: Option<Vec<String>>,
+    /// Stop selecting the script once it has run this many times, whatever it found.
+    pub run_limit: Option<u64>,
+    /// Estimated median runtime, used with `"runtime_metric": "median"`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub runtime_median: Option<StreamingMedian>,
+    /// The last `RECENT_RUNTIMES` runtimes, oldest first, used with `"runtime_metric": {"percentile": p}`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub recent_runtimes_ms: Vec<u64>,
+}
+
+/// Both counts of the uniform Beta(1, 1) prior.
+fn default_prior_count() -> f64 {
+    1.0
+}
+
+fn is_default_prior_count(count: &f64) -> bool {
+    *count == default_prior_count()
+}
+
+impl Script {
+    /// The beta distribution parameters the script's results are added to when sampling.
+    pub fn prior(&self) -> Prior {
+        Prior {
+            alpha: self.prior_interesting,
+            beta: self.prior_uninteresting,
+        }
+    }
+
+    /// Scripts stop being selected once they have found `limit` interesting cases or run `run_limit` times,
+    /// whichever comes first.
+    pub fn is_active(&self) -> bool {
+        self.limit
+            .is_none_or(|limit| self.results.interesting < limit as f64)
+            && self
+                .run_limit
+                .is_none_or(|run_limit| self.runcount < run_limit)
+    }
+
+    /// Whether the script carries any of `tags`. An empty filter matches every script.
+    pub fn has_any_tag(&self, tags: &[String]) -> bool {
+        tags.is_empty() || self.tags.iter().any(|tag| tags.contains(tag))
+    }
+
+    /// Whether every script in `depends_on` has found enough interesting cases. A dependency missing from
+    /// `scripts` is never met.
+    pub fn dependencies_met(&self, scripts: &[Script]) -> bool {
+        let threshold = self.dependency_threshold.unwrap_or(1.);
+        self.depends_on.iter().all(|name| {
+            scripts
+                .iter()
+                .any(|script| &script.name == name && script.results.interesting >= threshold)
+        })
+    }
+
+    /// Whether the average runtime has grown past `max_runtime_ms`.
+    pub fn is_too_slow(&self) -> bool {
+        match (self.avgruntime_ms, self.max_runtime_ms) {
+            (Some(runtime), Some(max_runtime_ms)) => *runtime > max_runtime_ms as f64,
+            _ => false,
+        }
+    }
+
+    /// The runtime the runtime scaler uses under `metric`. A script without a median estimate or recent
+    /// runtimes yet, e.g. from a config saved before they were recorded, falls back to its mean.
+    pub fn runtime_ms(&self, metric: RuntimeMetric) -> Option<NotNan<f64>> {
+        let median = self
+            .runtime_median
+            .as_ref()
+            .and_then(StreamingMedian::estimate)
+            .and_then(|median| NotNan::new(median).ok());
+        match metric {
+            RuntimeMetric::Mean => self.avgruntime_ms,
+            RuntimeMetric::Median => median.or(self.avgruntime_ms),
+            RuntimeMetric::Percentile(percentile) => self
+                .recent_runtime_percentile(percentile)
+                .or(self.avgruntime_ms),
+        }
+    }
+
+    /// The runtime under `metric` weighted by `cost_per_ms`, the penalty the runtime scaler divides by.
+    pub fn runtime_cost(&self, metric: RuntimeMetric) -> Option<NotNan<f64>> {
+        let runtime = self.runtime_ms(metric)?;
+        Some(runtime * self.cost_per_ms.unwrap_or(1.))
+    }
+
+    /// The `percentile` of `recent_runtimes_ms`, interpolating between the two nearest runtimes.
+    fn recent_runtime_percentile(&self, percentile: f64) -> Option<NotNan<f64>> {
+        let mut runtimes = self.recent_runtimes_ms.clone();
+        runtimes.sort_unstable();
+        let rank = percentile / 100. * runtimes.len().checked_sub(1)? as f64;
+        let (below, above) = (
+            runtimes[rank.floor() as usize],
+            runtimes[rank.ceil() as usize],
+        );
+        NotNan::new(below as f64 + (above - below) as f64 * rank.fract()).ok()
+    }
+
+    /// Sum of every recorded runtime. Configs saved before `total_runtime_ms` existed only have the
+    /// average, so the sum is recovered from it.
+    pub fn recorded_runtime_ms(&self) -> u64 {
+        match self.avgruntime_ms {
+            Some(avg) if self.total_runtime_ms == 0 => (*avg * self.runcount as f64).round() as u64,
+            _ => self.total_runtime_ms,
+        }
+    }
+
+    /// Store a new runtime sum and the average derived from it.
+    pub fn set_total_runtime_ms(&mut self, total_runtime_ms: u64) {
+        self.total_runtime_ms = total_runtime_ms;
+        self.avgruntime_ms = (self.runcount > 0)
+            .then(|| NotNan::new(total_runtime_ms as f64 / self.runcount as f64).unwrap());
+    }
+
+    pub fn is_interesting_code(&self, code: i32) -> bool {
+        if self.interesting_codes.is_empty() {
+            code == if self.success_is_interesting { 0 } else { 1 }
+        } else {
+            self.interesting_codes.contains(&code)
+        }
+    }
+
+    pub fn is_uninteresting_code(&self, code: i32) -> bool {
+        if self.uninteresting_codes.is_empty() {
+            code == if self.success_is_interesting { 1 } else { 0 }
+        } else {
+            self.uninteresting_codes.contains(&code)
+        }
+    }
+
+    pub fn resource_limits(&self) -> ResourceLimits {
+        ResourceLimits {
+            address_space_mb: self.rlimit_as_mb,
+            cpu_s: self.rlimit_cpu_s,
+            file_size_mb: self.rlimit_fsize_mb,
+        }
+    }
+
+    /// Whether the script's schedule allows it to run at `minute` past local midnight. An invalid schedule
+    /// allows no time, see `lint`.
+    pub fn in_schedule(&self, minute: u32) -> bool {
+        self.schedule.as_ref().is_none_or(|schedule| {
+            schedule
+                .parse::<Schedule>()
+                .is_ok_and(|schedule| schedule.contains(minute))
+        })
+    }
+}
+
+#[test]
+fn test_toml_round_trip() {
+    let config = parse_config(&PathBuf::from("./tests/analyze/fast-interesting.json"));
+    let path = std::env::temp_dir().join(format!("bandits-round-trip-{}.toml", std::process::id()));
+
+    save_config(&config, &path);
+    let round_tripped = parse_config(&path);
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(
+        serde_json::to_value(&round_tripped).unwrap(),
+        serde_json::to_value(&config).unwrap()
+    );
+}
+
+#[test]
+fn test_infinite_bias_is_rejected() {
+    let err = try_parse_config(&PathBuf::from("./tests/toml/infinite-bias.toml")).unwrap_err();
+    assert_eq!(
+        err,
+        "Could not parse ./tests/toml/infinite-bias.toml: the bias of exit 1 is inf. Biases must be finite."
+    );
+}
+
+#[test]
+fn test_normalize_biases() {
+    let script = |name: &str, bias: f64| {
+        let mut script = crate::test_script(name, 0, 0);
+        script["bias"] = bias.into();
+        script
+    };
+    let mut config: Config = serde_json::from_value(serde_json::json!({
+        "scripts": [
+            script("a", 2.0),
+            script("b", 6.0),
+            script("zero", 0.0),
+            script("c", 0.5),
+            script("negative", -1.0)
+        ]
+    }))
+    .unwrap();
+
+    let skipped = normalize_biases(&mut config);
+    assert_eq!(skipped, vec!["zero", "negative"]);
+
+    let biases = config
+        .scripts
+        .iter()
+        .map(|script| *script.bias)
+        .collect::<Vec<_>>();
+    let mean = (biases[0] + biases[1] + biases[3]) / 3.0;
+    assert!((mean - 1.0).abs() < 1e-12);
+    // Ratios between the positive biases are unchanged.
+    assert!((biases[1] / biases[0] - 3.0).abs() < 1e-12);
+    assert_eq!((biases[2], biases[4]), (0.0, -1.0));
+}
+
+#[test]
+fn test_import_state_keeps_exact_total_and_saturates() {
+    let mut script = crate::test_script("a", 1, 1);
+    script["avgruntime_ms"] = 10.0.into();
+    script["total_runtime_ms"] = 20.into();
+    let mut config: Config =
+        serde_json::from_value(serde_json::json!({ "scripts": [script] })).unwrap();
+
+    // 1000ms over 3 runs has no exact average, but the total is carried as is.
+    let state = State::from([(
+        "a".to_string(),
+        ScriptState(1.0, 2.0, 3, Some(NotNan::new(333.333).unwrap()), 1000),
+    )]);
+    import_state(&mut config, &state, false);
+    assert_eq!(config.scripts[0].runcount, 5);
+    assert_eq!(config.scripts[0].total_runtime_ms, 1020);
+
+    let state = State::from([(
+        "a".to_string(),
+        ScriptState(
+            0.0,
+            0.0,
+            u64::MAX,
+            Some(NotNan::new(1.0).unwrap()),
+            u64::MAX,
+        ),
+    )]);
+    import_state(&mut config, &state, false);
+    assert_eq!(config.scripts[0].runcount, u64::MAX);
+    assert_eq!(config.scripts[0].total_runtime_ms, u64::MAX);
+}
+
+#[test]
+fn test_merge_configs_combines_recorded_fields() {
+    let config = |runs: &[u64], peak_mem_kb: f64, first_interesting_step: u64| -> Config {
+        let mut config: Config = serde_json::from_value(serde_json::json!({
+            "scripts": [crate::test_script("a", 0, 0)]
+        }))
+        .unwrap();
+        let script = &mut config.scripts[0];
+        for runtime in runs {
+            crate::update_state(
+                script,
+                crate::ScriptResult {
+                    interesting: 0,
+                    uninteresting: 1,
+                    penalty: 0,
+                    runtime_ms: *runtime as u128,
+                    peak_mem_kb: None,
+                    timed_out: false,
+                },
+                0,
+            );
+        }
+        script.avgpeakmem_kb = Some(NotNan::new(peak_mem_kb).unwrap());
+        script.first_interesting_step = Some(first_interesting_step);
+        config
+    };
+    let first = config(&[10, 20], 100.0, 7);
+    let second = config(&[30, 40, 50, 60, 70, 80], 300.0, 3);
+
+    let (merged, _) = merge_configs(&first, &second);
+    let merged = &merged.scripts[0];
+    assert_eq!(merged.runcount, 8);
+    assert_eq!(merged.avgruntime_ms, Some(NotNan::new(45.0).unwrap()));
+    // (2 * 100 + 6 * 300) / 8
+    assert_eq!(merged.avgpeakmem_kb, Some(NotNan::new(250.0).unwrap()));
+    assert_eq!(merged.first_interesting_step, Some(3));
+    assert_eq!(
+        merged.recent_runtimes_ms,
+        vec![10, 20, 30, 40, 50, 60, 70, 80]
+    );
+    // The second script's median was built from more runtimes.
+    assert_eq!(merged.runtime_median, second.scripts[0].runtime_median);
+}
+
+#[test]
+fn test_merge_configs_adds_exact_runtime_totals() {
+    let config = |runcount: u64, total_runtime_ms: u64| -> Config {
+        let mut script = crate::test_script("a", 0, runcount);
+        script["total_runtime_ms"] = total_runtime_ms.into();
+        script["avgruntime_ms"] = (total_runtime_ms as f64 / runcount as f64).into();
+        serde_json::from_value(serde_json::json!({ "scripts": [script] })).unwrap()
+    };
+
+    // Neither average is exact, but the totals are added as they are.
+    let (merged, _) = merge_configs(&config(3, 1000), &config(7, 2001));
+    assert_eq!(merged.scripts[0].total_runtime_ms, 3001);
+    assert_eq!(merged.scripts[0].runcount, 10);
+    assert_eq!(
+        merged.scripts[0].avgruntime_ms,
+        Some(NotNan::new(300.1).unwrap())
+    );
+
+    let (merged, _) = merge_configs(&config(1, u64::MAX), &config(1, u64::MAX));
+    assert_eq!(merged.scripts[0].total_runtime_ms, u64::MAX);
 }
@@ -1,9 +1,73 @@
 extern crate test;
 #[cfg(test)]
 use float_cmp::approx_eq;
+use std::{cell::RefCell, collections::HashMap};
 #[cfg(test)]
 use test::{black_box, Bencher};
 
+/// Intervals each cached quantile function is split into, interpolating linearly within one.
+const INTERVALS: usize = 128;
+
+/// Intervals at either end of the quantile function, where it can be too steep to interpolate, that are
+/// always inverted exactly. They hold 1 in 16 samples.
+const EXACT_INTERVALS: usize = 4;
+
+/// Mantissa bits of alpha and beta a cached distribution is keyed by. Parameters within about 1 part in
+/// 2^11 of each other share one, so counts that decay by a fraction every update still hit the cache.
+const PARAMETER_BITS: u32 = 10;
+
+/// Beta distributions cached per thread. The cache is cleared once it is full, bounding its memory to a
+/// few megabytes however many distinct distributions are sampled.
+const MAX_CACHED: usize = 8192;
+
+thread_local! {
+    /// Quantiles of each beta distribution at the interval boundaries, keyed by alpha and beta rounded to
+    /// `PARAMETER_BITS`, filled in as they are first needed. Unfilled boundaries are NaN.
+    static QUANTILES: RefCell<HashMap<(u64, u64), Vec<f64>>> = RefCell::new(HashMap::new());
+}
+
+/// `puruspe::invbetai(p, a, b)`, interpolated between cached quantiles.
+///
+/// Sampling a distribution whose parameters have barely changed since it was last sampled then costs a
+/// lookup instead of an inversion. The result is within 1e-3 of the exact quantile.
+pub fn cached_invbetai(p: f64, a: f64, b: f64) -> f64 {
+    let position = p * INTERVALS as f64;
+    let index = position as usize;
+    if !(EXACT_INTERVALS..INTERVALS - EXACT_INTERVALS).contains(&index) {
+        return puruspe::invbetai(p, a, b);
+    }
+
+    QUANTILES.with(|quantiles| {
+        let mut quantiles = quantiles.borrow_mut();
+        let key = (quantize(a), quantize(b));
+        if quantiles.len() >= MAX_CACHED && !quantiles.contains_key(&key) {
+            quantiles.clear();
+        }
+        let boundaries = quantiles
+            .entry(key)
+            .or_insert_with(|| vec![f64::NAN; INTERVALS + 1]);
+        let mut quantile = |boundary: usize| {
+            if boundaries[boundary].is_nan() {
+                boundaries[boundary] = puruspe::invbetai(
+                    boundary as f64 / INTERVALS as f64,
+                    f64::from_bits(key.0),
+                    f64::from_bits(key.1),
+                );
+            }
+            boundaries[boundary]
+        };
+        let (low, high) = (quantile(index), quantile(index + 1));
+        low + (high - low) * position.fract()
+    })
+}
+
+/// `value`, a positive beta parameter, rounded to the nearest float with `PARAMETER_BITS` mantissa bits.
+fn quantize(value: f64) -> u64 {
+    let dropped = f64::MANTISSA_DIGITS - 1 - PARAMETER_BITS;
+    let bits = value.to_bits() + (1 << (dropped - 1));
+    bits >> dropped << dropped
+}
+
 #[test]
 fn beta_inverse() {
     let a = 2;
@@ -18,7 +82,12 @@ fn beta_inverse() {
         p * 100.0
     );
 
-    assert!(approx_eq!(f64, result, 0.707106781186548, ulps = 100_000));
+    assert!(approx_eq!(
+        f64,
+        result,
+        std::f64::consts::FRAC_1_SQRT_2,
+        ulps = 100_000
+    ));
 }
 
 #[test]
@@ -53,3 +122,87 @@ fn basic_benchmark(ben: &mut Bencher) {
         black_box(result);
     });
 }
+
+#[test]
+fn cached_matches_exact() {
+    let parameters = [
+        (1.0, 1.0),
+        (2.0, 1.0),
+        (0.5, 0.5),
+        (1.0, 1000.0),
+        (5.0, 40.0),
+        (1001.0, 1001.0),
+        (250.5, 3.25),
+        (1.1, 1.0),
+        (12.0, 1.5),
+    ];
+    for (a, b) in parameters {
+        // Twice, so the second pass interpolates between cached quantiles.
+        for _ in 0..2 {
+            for step in 0..1000 {
+                let p = (step as f64 + 0.5) / 1000.0;
+                let exact = puruspe::invbetai(p, a, b);
+                let cached = cached_invbetai(p, a, b);
+                assert!(
+                    (exact - cached).abs() < 1e-3,
+                    "B({a}, {b}) at {p}: {cached} instead of {exact}"
+                );
+            }
+        }
+    }
+}
+
+/// Sample one distribution at many points, as repeated selection between updates does.
+#[bench]
+fn bench_invbetai_many_points(ben: &mut Bencher) {
+    let points = (0..100)
+        .map(|i| (i as f64 + 0.5) / 100.0)
+        .collect::<Vec<_>>();
+
+    ben.iter(|| {
+        for p in &points {
+            black_box(puruspe::invbetai(*p, 20.0, 80.0));
+        }
+    });
+}
+
+#[bench]
+fn bench_cached_invbetai_many_points(ben: &mut Bencher) {
+    let points = (0..100)
+        .map(|i| (i as f64 + 0.5) / 100.0)
+        .collect::<Vec<_>>();
+
+    ben.iter(|| {
+        for p in &points {
+            black_box(cached_invbetai(*p, 20.0, 80.0));
+        }
+    });
+}
+
+#[test]
+fn cached_within_bound_on_grid() {
+    // Parameters from 0.3 to about 200, off whole numbers as decayed counts are.
+    for i in 0..12 {
+        let a = 0.3 * 1.8f64.powi(i) + 0.013 * i as f64;
+        for j in 0..12 {
+            let b = 0.37 * 1.8f64.powi(j) + 0.007 * j as f64;
+            for step in 0..100 {
+                let p = (step as f64 + 0.5) / 100.0;
+                let exact = puruspe::invbetai(p, a, b);
+                let cached = cached_invbetai(p, a, b);
+                assert!(
+                    (exact - cached).abs() < 1e-3,
+                    "B({a}, {b}) at {p}: {cached} instead of {exact}"
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn nearby_parameters_share_cache() {
+    // A count decayed by a hair still hits the cached distribution.
+    assert_eq!(quantize(20.0), quantize(20.0 * (1.0 - 1e-5)));
+    assert_eq!(quantize(0.5), quantize(0.5 * (1.0 + 1e-5)));
+    assert_ne!(quantize(20.0), quantize(20.1));
+}
@@ -1,21 +1,61 @@
 use colored::Colorize;
 use log::error;
 use ordered_float::NotNan;
+use rand::Rng;
 use rgb::RGB8;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::File,
+    io::{self, Write},
+    path::PathBuf,
+    str::FromStr,
+};
 use textplots::ColorPlot;
 
 use crate::{
+    config::RuntimeMetric,
     thompson::{
-        dist_area_at_percentile, skew_percentile, thompson_ranking, thompson_ranking_bias_runtime,
-        ThompsonInfo,
+        best_score, dist_area_at_percentile, empirical_rate, greedy_ranking, posterior_mean,
+        sample_breakdowns, sample_breakdowns_bias_runtime, sample_posterior_curve,
+        selection_probabilities, serialize_count, skew_percentile, thompson_ranking,
+        thompson_ranking_bias_runtime, thompson_sampling, thompson_sampling_bias_runtime,
+        thompson_scores, thompson_scores_bias_runtime, time_scaler, ucb1_ranking, ucb1_score,
+        Prior, RuntimeScoring, ScoreBreakdown, ThompsonInfo,
     },
     Script,
 };
 
-pub fn plot_top_3(scripts: &[Script]) {
+/// Colors of the most run, second and third most run scripts in the top 3 plots.
+const TOP_3_COLORS: [RGB8; 3] = [
+    RGB8 {
+        r: 100,
+        g: 250,
+        b: 200,
+    },
+    RGB8 {
+        r: 200,
+        g: 250,
+        b: 100,
+    },
+    RGB8 {
+        r: 200,
+        g: 100,
+        b: 250,
+    },
+];
+
+/// Draw a finished chart to `out`, as `Chart::display` does to stdout.
+fn render_chart(chart: &mut textplots::Chart, out: &mut impl Write) -> io::Result<()> {
+    chart.axis();
+    chart.figures();
+    writeln!(out, "{chart}")
+}
+
+/// Plot the posteriors of the 3 most run scripts to `out`.
+pub fn plot_top_3(scripts: &[Script], out: &mut impl Write) -> io::Result<()> {
     if scripts.len() < 3 {
         error!("Cannot plot top 3 with less than 3 scripts.");
-        return;
+        return Ok(());
     }
 
     let mut scripts = scripts.to_owned();
@@ -26,61 +66,51 @@ pub fn plot_top_3(scripts: &[Script]) {
 
     use textplots::{Chart, Shape};
 
-    println!("Plot of top 3 run scripts. Interesting cases (area under curve).");
+    writeln!(
+        out,
+        "Plot of top 3 run scripts. Interesting cases (area under curve)."
+    )?;
 
-    let colors = [
-        RGB8 {
-            r: 100,
-            g: 250,
-            b: 200,
-        },
-        RGB8 {
-            r: 200,
-            g: 250,
-            b: 100,
-        },
-        RGB8 {
-            r: 200,
-            g: 100,
-            b: 250,
-        },
-    ];
+    let colors = TOP_3_COLORS;
 
-    Chart::new(120, 60, 0.0, 1.0)
-        .linecolorplot(
-            &Shape::Continuous(Box::new(|x| {
-                puruspe::betai(
-                    (most_run_scripts[2].results.uninteresting + 1) as f64,
-                    (most_run_scripts[2].results.interesting + 1) as f64,
-                    x.into(),
-                ) as f32
-            })),
-            colors[2],
-        )
-        .linecolorplot(
-            &Shape::Continuous(Box::new(|x| {
-                puruspe::betai(
-                    (most_run_scripts[1].results.uninteresting + 1) as f64,
-                    (most_run_scripts[1].results.interesting + 1) as f64,
-                    x.into(),
-                ) as f32
-            })),
-            colors[1],
-        )
-        .linecolorplot(
-            &Shape::Continuous(Box::new(|x| {
-                puruspe::betai(
-                    (most_run_scripts[0].results.uninteresting + 1) as f64,
-                    (most_run_scripts[0].results.interesting + 1) as f64,
-                    x.into(),
-                ) as f32
-            })),
-            colors[0],
-        )
-        .display();
+    render_chart(
+        Chart::new(120, 60, 0.0, 1.0)
+            .linecolorplot(
+                &Shape::Continuous(Box::new(|x| {
+                    puruspe::betai(
+                        most_run_scripts[2].results.uninteresting + 1.0,
+                        most_run_scripts[2].results.interesting + 1.0,
+                        x.into(),
+                    ) as f32
+                })),
+                colors[2],
+            )
+            .linecolorplot(
+                &Shape::Continuous(Box::new(|x| {
+                    puruspe::betai(
+                        most_run_scripts[1].results.uninteresting + 1.0,
+                        most_run_scripts[1].results.interesting + 1.0,
+                        x.into(),
+                    ) as f32
+                })),
+                colors[1],
+            )
+            .linecolorplot(
+                &Shape::Continuous(Box::new(|x| {
+                    puruspe::betai(
+                        most_run_scripts[0].results.uninteresting + 1.0,
+                        most_run_scripts[0].results.interesting + 1.0,
+                        x.into(),
+                    ) as f32
+                })),
+                colors[0],
+            ),
+        out,
+    )?;
 
-    println!("Top 3 run scripts:");
-    println!(
+    writeln!(out, "Top 3 run scripts:")?;
+    writeln!(
+        out,
         "1: {} {} {}ms",
         most_run_scripts[0].runcount,
         most_run_scripts[0]
@@ -89,8 +119,9 @@ pub fn plot_top_3(scripts: &[Script]) {
         most_run_scripts[0]
             .avgruntime_ms
             .unwrap_or(NotNan::new(-1.0).unwrap())
-    );
-    println!(
+    )?;
+    writeln!(
+        out,
         "2: {} {} {}ms",
         most_run_scripts[1].runcount,
         most_run_scripts[1]
@@ -99,8 +130,9 @@ pub fn plot_top_3(scripts: &[Script]) {
         most_run_scripts[1]
             .avgruntime_ms
             .unwrap_or(NotNan::new(-1.0).unwrap())
-    );
-    println!(
+    )?;
+    writeln!(
+        out,
         "3: {} {} {}ms",
         most_run_scripts[2].runcount,
         most_run_scripts[2]
@@ -109,13 +141,21 @@ pub fn plot_top_3(scripts: &[Script]) {
         most_run_scripts[2]
             .avgruntime_ms
             .unwrap_or(NotNan::new(-1.0).unwrap())
-    );
+    )
 }
 
-pub fn plot_top_3_inverses(scripts: &[Script]) {
+/// Plot the runtime and bias scaled scores of the 3 most run scripts to `out`.
+pub fn plot_top_3_inverses(
+    scripts: &[Script],
+    runtime_reference_ms: NotNan<f64>,
+    out: &mut impl Write,
+) -> io::Result<()> {
     if scripts.len() < 3 {
-        println!("Cannot plot the top 3 inverses with less than 3 scripts.");
-        return;
+        writeln!(
+            out,
+            "Cannot plot the top 3 inverses with less than 3 scripts."
+        )?;
+        return Ok(());
     }
 
     let mut scripts = scripts.to_owned();
@@ -126,87 +166,78 @@ pub fn plot_top_3_inverses(scripts: &[Script]) {
 
     use textplots::{Chart, Shape};
 
-    println!(
+    writeln!(
+        out,
         "Plot of inverse 3 run scripts. Minimizing time per interesting case (area under curve)."
-    );
+    )?;
 
-    let colors = [
-        RGB8 {
-            r: 100,
-            g: 250,
-            b: 200,
-        },
-        RGB8 {
-            r: 200,
-            g: 250,
-            b: 100,
-        },
-        RGB8 {
-            r: 200,
-            g: 100,
-            b: 250,
-        },
-    ];
+    let colors = TOP_3_COLORS;
 
-    Chart::new(120, 60, 0.0, 1.0)
-        .linecolorplot(
-            &Shape::Continuous(Box::new(|x| {
-                f32::from(
-                    skew_percentile(
-                        NotNan::new(puruspe::invbetai(
-                            x.into(),
-                            (most_run_scripts[2].results.interesting + 1) as f64,
-                            (most_run_scripts[2].results.uninteresting + 1) as f64,
-                        ))
-                        .unwrap(),
-                        &most_run_scripts[2].avgruntime_ms,
-                        &most_run_scripts[2].bias,
+    render_chart(
+        Chart::new(120, 60, 0.0, 1.0)
+            .linecolorplot(
+                &Shape::Continuous(Box::new(|x| {
+                    f32::from(
+                        skew_percentile(
+                            NotNan::new(puruspe::invbetai(
+                                x.into(),
+                                most_run_scripts[2].results.interesting + 1.0,
+                                most_run_scripts[2].results.uninteresting + 1.0,
+                            ))
+                            .unwrap(),
+                            &most_run_scripts[2].avgruntime_ms,
+                            &most_run_scripts[2].bias,
+                            runtime_reference_ms,
+                        )
+                        .as_f32(),
                     )
-                    .as_f32(),
-                )
-            })),
-            colors[2],
-        )
-        .linecolorplot(
-            &Shape::Continuous(Box::new(|x| {
-                f32::from(
-                    skew_percentile(
-                        NotNan::new(puruspe::invbetai(
-                            x.into(),
-                            (most_run_scripts[1].results.interesting + 1) as f64,
-                            (most_run_scripts[1].results.uninteresting + 1) as f64,
-                        ))
-                        .unwrap(),
-                        &most_run_scripts[1].avgruntime_ms,
-                        &most_run_scripts[1].bias,
+                })),
+                colors[2],
+            )
+            .linecolorplot(
+                &Shape::Continuous(Box::new(|x| {
+                    f32::from(
+                        skew_percentile(
+                            NotNan::new(puruspe::invbetai(
+                                x.into(),
+                                most_run_scripts[1].results.interesting + 1.0,
+                                most_run_scripts[1].results.uninteresting + 1.0,
+                            ))
+                            .unwrap(),
+                            &most_run_scripts[1].avgruntime_ms,
+                            &most_run_scripts[1].bias,
+                            runtime_reference_ms,
+                        )
+                        .as_f32(),
                     )
-                    .as_f32(),
-                )
-            })),
-            colors[1],
-        )
-        .linecolorplot(
-            &Shape::Continuous(Box::new(|x| {
-                f32::from(
-                    skew_percentile(
-                        NotNan::new(puruspe::invbetai(
-                            x.into(),
-                            (most_run_scripts[0].results.interesting + 1) as f64,
-                            (most_run_scripts[0].results.uninteresting + 1) as f64,
-                        ))
-                        .unwrap(),
-                        &most_run_scripts[0].avgruntime_ms,
-                        &most_run_scripts[0].bias,
+                })),
+                colors[1],
+            )
+            .linecolorplot(
+                &Shape::Continuous(Box::new(|x| {
+                    f32::from(
+                        skew_percentile(
+                            NotNan::new(puruspe::invbetai(
+                                x.into(),
+                                most_run_scripts[0].results.interesting + 1.0,
+                                most_run_scripts[0].results.uninteresting + 1.0,
+                            ))
+                            .unwrap(),
+                            &most_run_scripts[0].avgruntime_ms,
+                            &most_run_scripts[0].bias,
+                            runtime_reference_ms,
+                        )
+                        .as_f32(),
                     )
-                    .as_f32(),
-                )
-            })),
-            colors[0],
-        )
-        .display();
+                })),
+                colors[0],
+            ),
+        out,
+    )?;
 
-    println!("Top 3 run scripts:");
-    println!(
+    writeln!(out, "Top 3 run scripts:")?;
+    writeln!(
+        out,
         "1: {} {} {}ms",
         most_run_scripts[0].runcount,
         most_run_scripts[0]
@@ -215,8 +246,9 @@ pub fn plot_top_3_inverses(scripts: &[Script]) {
         most_run_scripts[0]
             .avgruntime_ms
             .unwrap_or(NotNan::new(-1.0).unwrap())
-    );
-    println!(
+    )?;
+    writeln!(
+        out,
         "2: {} {} {}ms",
         most_run_scripts[1].runcount,
         most_run_scripts[1]
@@ -225,8 +257,9 @@ pub fn plot_top_3_inverses(scripts: &[Script]) {
         most_run_scripts[1]
             .avgruntime_ms
             .unwrap_or(NotNan::new(-1.0).unwrap())
-    );
-    println!(
+    )?;
+    writeln!(
+        out,
         "3: {} {} {}ms",
         most_run_scripts[2].runcount,
         most_run_scripts[2]
@@ -235,69 +268,1415 @@ pub fn plot_top_3_inverses(scripts: &[Script]) {
         most_run_scripts[2]
             .avgruntime_ms
             .unwrap_or(NotNan::new(-1.0).unwrap())
-    );
+    )
+}
+
+fn print_peak_memory(script: &Script) {
+    match script.avgpeakmem_kb {
+        Some(peak_mem_kb) => println!("- Avg peak memory: {:.0}kB", peak_mem_kb),
+        None => println!("- Avg peak memory: unknown"),
+    }
+}
+
+/// How `summarize` prints its results.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SummaryFormat {
+    /// Plots followed by the ranking.
+    #[default]
+    Text,
+    /// A markdown table of the ranking, without plots.
+    Markdown,
+}
+
+impl FromStr for SummaryFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(SummaryFormat::Text),
+            "markdown" => Ok(SummaryFormat::Markdown),
+            _ => Err(format!(
+                "Unknown format {s}. Expected one of text, markdown"
+            )),
+        }
+    }
+}
+
+/// Order to display a ranking in. Orders other than `Sample` still show each script's sampled rank.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortOrder {
+    #[default]
+    Sample,
+    Mean,
+    Runtime,
+    Name,
+    Runcount,
+}
+
+impl FromStr for SortOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sample" => Ok(SortOrder::Sample),
+            "mean" => Ok(SortOrder::Mean),
+            "runtime" => Ok(SortOrder::Runtime),
+            "name" => Ok(SortOrder::Name),
+            "runcount" => Ok(SortOrder::Runcount),
+            _ => Err(format!(
+                "Unknown sort {s}. Expected one of sample, mean, runtime, name, runcount"
+            )),
+        }
+    }
+}
+
+/// Reorder a sampled ranking for display. Returns (sampled rank, script index) pairs.
+///
+/// Ties are broken by name. Scripts with an unknown runtime are listed last when sorting by runtime.
+fn display_order(scripts: &[Script], ranking: &[usize], sort: SortOrder) -> Vec<(usize, usize)> {
+    let mut order = ranking
+        .iter()
+        .enumerate()
+        .map(|(rank, index)| (rank + 1, *index))
+        .collect::<Vec<_>>();
+
+    let name = |index: usize| &scripts[index].name;
+    match sort {
+        SortOrder::Sample => {}
+        SortOrder::Mean => order.sort_by(|(_, a), (_, b)| {
+            posterior_mean(&scripts[*b].results)
+                .total_cmp(&posterior_mean(&scripts[*a].results))
+                .then_with(|| name(*a).cmp(name(*b)))
+        }),
+        SortOrder::Runtime => order.sort_by_key(|(_, index)| {
+            let runtime = scripts[*index].avgruntime_ms;
+            (runtime.is_none(), runtime, name(*index))
+        }),
+        SortOrder::Name => order.sort_by_key(|(_, index)| name(*index)),
+        SortOrder::Runcount => order.sort_by(|(_, a), (_, b)| {
+            scripts[*b]
+                .runcount
+                .cmp(&scripts[*a].runcount)
+                .then_with(|| name(*a).cmp(name(*b)))
+        }),
+    }
+
+    order
+}
+
+/// How a ranking is printed. None of these affect the ranking itself.
+pub struct DisplayOptions {
+    pub sort: SortOrder,
+    /// Scripts with fewer runs are listed separately as under-sampled.
+    pub min_runs: u64,
+    pub verbose: bool,
+}
+
+/// Split a ranking into display order, holding back scripts with fewer than `min_runs` runs.
+fn split_under_sampled(
+    scripts: &[Script],
+    ranking: &[usize],
+    display: &DisplayOptions,
+) -> (Vec<(usize, usize)>, Vec<usize>) {
+    let (shown, under_sampled): (Vec<_>, Vec<_>) = display_order(scripts, ranking, display.sort)
+        .into_iter()
+        .partition(|(_, script)| scripts[*script].runcount >= display.min_runs);
+
+    (
+        shown,
+        under_sampled
+            .into_iter()
+            .map(|(_, script)| script)
+            .collect(),
+    )
+}
+
+fn print_names(scripts: &[Script], shown: &[(usize, usize)], sort: SortOrder) {
+    for (rank, script) in shown {
+        if sort == SortOrder::Sample {
+            println!("{}", scripts[*script].name);
+        } else {
+            println!("{} (sampled rank {})", scripts[*script].name, rank);
+        }
+    }
+}
+
+fn print_under_sampled(scripts: &[Script], under_sampled: &[usize], min_runs: u64) {
+    if under_sampled.is_empty() {
+        return;
+    }
+
+    println!("Under-sampled (fewer than {} runs):", min_runs);
+    for script in under_sampled {
+        println!("{}", scripts[*script].name);
+    }
+}
+
+/// Indices of scripts that can still be selected, i.e. haven't reached their limit.
+fn active_scripts(scripts: &[Script]) -> Vec<usize> {
+    scripts
+        .iter()
+        .enumerate()
+        .filter(|(_, x)| x.is_active())
+        .map(|(index, _)| index)
+        .collect()
+}
+
+/// The config priors of the scripts at `indexes`, so rankings sample each script like `run` does.
+fn script_priors(scripts: &[Script], indexes: &[usize]) -> Vec<Prior> {
+    indexes.iter().map(|x| scripts[*x].prior()).collect()
 }
 
 pub fn print_ranking_bias_runtime(
     scripts: &[Script],
     runtimes: &[&Option<NotNan<f64>>],
     user_biases: &[&NotNan<f64>],
-    verbose: bool,
-) {
-    let items = scripts
+    runtime_reference_ms: NotNan<f64>,
+    min_samples: usize,
+    display: &DisplayOptions,
+    rng: &mut impl Rng,
+) -> Vec<usize> {
+    let active = active_scripts(scripts);
+    let items = active
         .iter()
-        .filter(|x| x.limit.is_none() || x.limit.unwrap() < x.results.interesting)
-        .map(|x| &x.results)
+        .map(|x| &scripts[*x].results)
         .collect::<Vec<_>>();
     let entries: &[&ThompsonInfo] = items.as_slice();
-    let ranking = thompson_ranking_bias_runtime(entries, runtimes, user_biases);
+    let runtimes = active.iter().map(|x| runtimes[*x]).collect::<Vec<_>>();
+    let runcounts = active
+        .iter()
+        .map(|x| scripts[*x].runcount)
+        .collect::<Vec<_>>();
+    let user_biases = active.iter().map(|x| user_biases[*x]).collect::<Vec<_>>();
+    let priors = script_priors(scripts, &active);
+    let priors = priors.iter().collect::<Vec<_>>();
+    let ranking = thompson_ranking_bias_runtime(
+        entries,
+        &RuntimeScoring {
+            priors: &priors,
+            runtimes: &runtimes,
+            runcounts: &runcounts,
+            user_biases: &user_biases,
+            runtime_reference_ms,
+            min_samples,
+        },
+        rng,
+    )
+    .into_iter()
+    .map(|x| active[x])
+    .collect::<Vec<_>>();
 
-    if verbose {
+    let (shown, under_sampled) = split_under_sampled(scripts, &ranking, display);
+
+    if display.verbose {
         println!("Ranking (biased by runtime):");
 
-        for (i, script) in ranking.iter().enumerate() {
+        for (i, (rank, script)) in shown.iter().enumerate() {
             println!("{}: {}", i + 1, scripts[*script].name,);
+            if display.sort != SortOrder::Sample {
+                println!("- Sampled rank: {}", rank);
+            }
             println!(
-                "- 50th percentile: {:.4}",
-                dist_area_at_percentile(&scripts[*script].results, 0.5)
+                "- 50th percentile: {:.4} [{:.4}, {:.4}]",
+                dist_area_at_percentile(&scripts[*script].results, 0.5),
+                dist_area_at_percentile(&scripts[*script].results, 0.05),
+                dist_area_at_percentile(&scripts[*script].results, 0.95)
             );
-            println!("- Runs: {}", &scripts[*script].runcount);
+            println!("- Runs: {}", scripts[*script].runcount);
+            print_peak_memory(&scripts[*script]);
             println!(
                 "- Observed percent {:.5}%",
-                scripts[*script].results.interesting as f64 / scripts[*script].runcount as f64
-                    * 100.
+                scripts[*script].results.interesting / scripts[*script].runcount as f64 * 100.
             )
         }
     } else {
-        ranking.iter().for_each(|script| {
-            println!("{}", scripts[*script].name);
-        });
+        print_names(scripts, &shown, display.sort);
     }
+    print_under_sampled(scripts, &under_sampled, display.min_runs);
+
+    ranking
 }
 
-pub fn print_ranking(scripts: &[Script], verbose: bool) {
-    let items = scripts
+pub fn print_ranking(
+    scripts: &[Script],
+    display: &DisplayOptions,
+    rng: &mut impl Rng,
+) -> Vec<usize> {
+    let active = active_scripts(scripts);
+    let items = active
         .iter()
-        .filter(|x| x.limit.is_none() || x.limit.unwrap() < x.results.interesting)
-        .map(|x| &x.results)
+        .map(|x| &scripts[*x].results)
         .collect::<Vec<_>>();
     let entries: &[&ThompsonInfo] = items.as_slice();
-    let ranking = thompson_ranking(entries);
+    let priors = script_priors(scripts, &active);
+    let priors = priors.iter().collect::<Vec<_>>();
+    let ranking = thompson_ranking(entries, &priors, rng)
+        .into_iter()
+        .map(|x| active[x])
+        .collect::<Vec<_>>();
+
+    let (shown, under_sampled) = split_under_sampled(scripts, &ranking, display);
 
-    if verbose {
+    if display.verbose {
         println!("Ranking (raw):");
 
-        for (i, script) in ranking.iter().enumerate() {
+        for (i, (rank, script)) in shown.iter().enumerate() {
             println!("{}: {}", i + 1, scripts[*script].name,);
+            if display.sort != SortOrder::Sample {
+                println!("- Sampled rank: {}", rank);
+            }
             println!(
-                "- 50th percentile: {:.4}",
-                dist_area_at_percentile(&scripts[*script].results, 0.5)
+                "- 50th percentile: {:.4} [{:.4}, {:.4}]",
+                dist_area_at_percentile(&scripts[*script].results, 0.5),
+                dist_area_at_percentile(&scripts[*script].results, 0.05),
+                dist_area_at_percentile(&scripts[*script].results, 0.95)
             );
-            println!("- Runs: {}", &scripts[*script].runcount);
+            println!("- Runs: {}", scripts[*script].runcount);
+            print_peak_memory(&scripts[*script]);
         }
     } else {
-        ranking.iter().for_each(|script| {
-            println!("{}", scripts[*script].name);
-        });
+        print_names(scripts, &shown, display.sort);
+    }
+    print_under_sampled(scripts, &under_sampled, display.min_runs);
+
+    ranking
+}
+
+/// Rank by empirical interesting rate, the order epsilon-greedy exploits scripts in.
+pub fn print_ranking_greedy(scripts: &[Script], display: &DisplayOptions) -> Vec<usize> {
+    let active = active_scripts(scripts);
+    let items = active
+        .iter()
+        .map(|x| &scripts[*x].results)
+        .collect::<Vec<_>>();
+    let entries: &[&ThompsonInfo] = items.as_slice();
+    let ranking = greedy_ranking(entries)
+        .into_iter()
+        .map(|x| active[x])
+        .collect::<Vec<_>>();
+
+    print_fixed_ranking(
+        scripts,
+        &ranking,
+        "Greedy",
+        display,
+        |script| match empirical_rate(&script.results) {
+            Some(rate) => println!("- Interesting rate: {:.4}", rate),
+            None => println!("- Interesting rate: unknown"),
+        },
+    );
+
+    ranking
+}
+
+/// Rank by UCB1 score, the order `--strategy ucb1` would run scripts in if nothing changed.
+pub fn print_ranking_ucb1(scripts: &[Script], display: &DisplayOptions) -> Vec<usize> {
+    let active = active_scripts(scripts);
+    let items = active
+        .iter()
+        .map(|x| &scripts[*x].results)
+        .collect::<Vec<_>>();
+    let entries: &[&ThompsonInfo] = items.as_slice();
+    let runcounts = active
+        .iter()
+        .map(|x| scripts[*x].runcount)
+        .collect::<Vec<_>>();
+    let total_runs = runcounts.iter().sum();
+    let ranking = ucb1_ranking(entries, &runcounts, total_runs)
+        .into_iter()
+        .map(|x| active[x])
+        .collect::<Vec<_>>();
+
+    print_fixed_ranking(scripts, &ranking, "UCB1", display, |script| {
+        println!(
+            "- UCB1 score: {:.4}",
+            ucb1_score(&script.results, script.runcount, total_runs)
+        );
+    });
+
+    ranking
+}
+
+/// Print a ranking that doesn't sample, such as greedy or UCB1, best first. `print_score` prints how the
+/// `strategy` scored a script, under its name in the verbose listing.
+fn print_fixed_ranking(
+    scripts: &[Script],
+    ranking: &[usize],
+    strategy: &str,
+    display: &DisplayOptions,
+    print_score: impl Fn(&Script),
+) {
+    let (shown, under_sampled) = split_under_sampled(scripts, ranking, display);
+
+    if display.verbose {
+        println!("Ranking ({strategy}):");
+
+        for (i, (rank, script)) in shown.iter().enumerate() {
+            println!("{}: {}", i + 1, scripts[*script].name,);
+            if display.sort != SortOrder::Sample {
+                println!("- {strategy} rank: {}", rank);
+            }
+            print_score(&scripts[*script]);
+            println!("- Runs: {}", scripts[*script].runcount);
+            print_peak_memory(&scripts[*script]);
+        }
+    } else {
+        print_names(scripts, &shown, display.sort);
+    }
+    print_under_sampled(scripts, &under_sampled, display.min_runs);
+}
+
+/// One script's place in a `rank --json` or `summarize --format markdown` ranking.
+#[derive(Debug, Serialize)]
+struct RankedScript<'a> {
+    rank: usize,
+    name: &'a str,
+    #[serde(serialize_with = "serialize_count")]
+    interesting: f64,
+    #[serde(serialize_with = "serialize_count")]
+    uninteresting: f64,
+    avgruntime_ms: Option<NotNan<f64>>,
+    bias: NotNan<f64>,
+    /// The sampled, skewed percentile the ranking is ordered by.
+    score: NotNan<f64>,
+}
+
+/// Print the thompson ranking of the active scripts as a JSON array, best first.
+///
+/// Every active script is included in sampled order, so display options don't apply.
+pub fn print_ranking_json(
+    scripts: &[Script],
+    ignore_runtime: bool,
+    runtime_metric: RuntimeMetric,
+    runtime_reference_ms: NotNan<f64>,
+    min_samples: usize,
+    rng: &mut impl Rng,
+) -> Vec<usize> {
+    let ranked = ranked_scripts(
+        scripts,
+        ignore_runtime,
+        runtime_metric,
+        runtime_reference_ms,
+        min_samples,
+        rng,
+    );
+    let rows = ranked.iter().map(|(_, row)| row).collect::<Vec<_>>();
+    println!("{}", serde_json::to_string_pretty(&rows).unwrap());
+
+    ranked.into_iter().map(|(index, _)| index).collect()
+}
+
+/// Print the thompson ranking of the active scripts as a GitHub flavored markdown table, best first, for
+/// pasting into issues and pull requests.
+pub fn print_ranking_markdown(
+    scripts: &[Script],
+    ignore_runtime: bool,
+    runtime_metric: RuntimeMetric,
+    runtime_reference_ms: NotNan<f64>,
+    min_samples: usize,
+    rng: &mut impl Rng,
+) {
+    println!("| rank | name | interesting | uninteresting | avg runtime (ms) | bias | score |");
+    println!("| ---: | --- | ---: | ---: | ---: | ---: | ---: |");
+    let ranked = ranked_scripts(
+        scripts,
+        ignore_runtime,
+        runtime_metric,
+        runtime_reference_ms,
+        min_samples,
+        rng,
+    );
+    for (_, row) in ranked {
+        let runtime = row
+            .avgruntime_ms
+            .map_or("unknown".to_string(), |runtime| format!("{runtime:.1}"));
+        // Scripts without a runtime are scored the maximum, so they run first.
+        let score = if *row.score == f64::MAX {
+            "needs sampling".to_string()
+        } else {
+            format!("{:.4}", row.score)
+        };
+        println!(
+            "| {} | {} | {} | {} | {} | {} | {} |",
+            row.rank,
+            row.name.replace('|', "\\|"),
+            row.interesting,
+            row.uninteresting,
+            runtime,
+            row.bias,
+            score
+        );
+    }
+}
+
+/// Sample the thompson ranking of the active scripts, best first, pairing each with its script index.
+fn ranked_scripts<'a>(
+    scripts: &'a [Script],
+    ignore_runtime: bool,
+    runtime_metric: RuntimeMetric,
+    runtime_reference_ms: NotNan<f64>,
+    min_samples: usize,
+    rng: &mut impl Rng,
+) -> Vec<(usize, RankedScript<'a>)> {
+    let active = active_scripts(scripts);
+    let items = active
+        .iter()
+        .map(|x| &scripts[*x].results)
+        .collect::<Vec<_>>();
+    let entries: &[&ThompsonInfo] = items.as_slice();
+    let priors = script_priors(scripts, &active);
+    let priors = priors.iter().collect::<Vec<_>>();
+    let scores = if ignore_runtime {
+        thompson_scores(entries, &priors, rng)
+    } else {
+        let runtimes = active
+            .iter()
+            .map(|x| scripts[*x].runtime_cost(runtime_metric))
+            .collect::<Vec<_>>();
+        let runtimes = runtimes.iter().collect::<Vec<_>>();
+        let runcounts = active
+            .iter()
+            .map(|x| scripts[*x].runcount)
+            .collect::<Vec<_>>();
+        let user_biases = active.iter().map(|x| &scripts[*x].bias).collect::<Vec<_>>();
+        thompson_scores_bias_runtime(
+            entries,
+            &RuntimeScoring {
+                priors: &priors,
+                runtimes: &runtimes,
+                runcounts: &runcounts,
+                user_biases: &user_biases,
+                runtime_reference_ms,
+                min_samples,
+            },
+            rng,
+        )
+    };
+
+    scores
+        .iter()
+        .enumerate()
+        .map(|(i, (index, score))| {
+            let script = &scripts[active[*index]];
+            let row = RankedScript {
+                rank: i + 1,
+                name: &script.name,
+                interesting: script.results.interesting,
+                uninteresting: script.results.uninteresting,
+                avgruntime_ms: script.avgruntime_ms,
+                bias: script.bias,
+                score: *score,
+            };
+            (active[*index], row)
+        })
+        .collect()
+}
+
+/// Expected interesting cases found per second of compute.
+///
+/// Ordered so that scripts which still need sampling sort above any measured throughput.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Throughput {
+    PerSecond(NotNan<f64>),
+    /// No runtime has been recorded yet, so the script needs to be sampled first.
+    NeedsSampling,
+}
+
+pub fn throughput(script: &Script) -> Throughput {
+    match script.avgruntime_ms {
+        Some(runtime) => {
+            // Runtimes are recorded in whole milliseconds, so very fast scripts can average 0ms.
+            // Count them as 1ms rather than dividing by zero.
+            let runtime_s = runtime.max(NotNan::new(1.0).unwrap()) / 1000.;
+            Throughput::PerSecond(NotNan::new(posterior_mean(&script.results)).unwrap() / runtime_s)
+        }
+        None => Throughput::NeedsSampling,
+    }
+}
+
+/// Deterministically rank scripts by posterior mean interesting rate / average runtime in seconds.
+pub fn print_ranking_throughput(scripts: &[Script]) {
+    let mut ranking = scripts
+        .iter()
+        .map(|script| (script, throughput(script)))
+        .collect::<Vec<_>>();
+
+    ranking.sort_by(|(_, a), (_, b)| b.cmp(a));
+
+    println!("Ranking (interesting per second):");
+
+    for (i, (script, throughput)) in ranking.iter().enumerate() {
+        println!("{}: {}", i + 1, script.name);
+        match throughput {
+            Throughput::PerSecond(rate) => println!("- Throughput: {:.4}/s", rate),
+            Throughput::NeedsSampling => println!("- Throughput: unknown (needs sampling)"),
+        }
+        println!("- Posterior mean: {:.4}", posterior_mean(&script.results));
+        match script.avgruntime_ms {
+            Some(runtime) => println!("- Avg runtime: {:.2}ms", runtime),
+            None => println!("- Avg runtime: unknown"),
+        }
+    }
+}
+
+/// Pearson correlation coefficient, or `None` if either variable is constant.
+fn correlation(xs: &[f64], ys: &[f64]) -> Option<f64> {
+    let n = xs.len() as f64;
+    let mean_x = xs.iter().sum::<f64>() / n;
+    let mean_y = ys.iter().sum::<f64>() / n;
+
+    let mut covariance = 0.0;
+    let mut variance_x = 0.0;
+    let mut variance_y = 0.0;
+    for (x, y) in xs.iter().zip(ys) {
+        covariance += (x - mean_x) * (y - mean_y);
+        variance_x += (x - mean_x).powi(2);
+        variance_y += (y - mean_y).powi(2);
+    }
+
+    if variance_x == 0.0 || variance_y == 0.0 {
+        return None;
+    }
+    Some(covariance / (variance_x * variance_y).sqrt())
+}
+
+/// Report whether faster scripts tend to be more interesting, across scripts with a known runtime.
+pub fn print_runtime_correlation(scripts: &[Script]) {
+    let (runtimes, means): (Vec<f64>, Vec<f64>) = scripts
+        .iter()
+        .filter_map(|script| {
+            script
+                .avgruntime_ms
+                .map(|runtime| (runtime.into_inner(), posterior_mean(&script.results)))
+        })
+        .unzip();
+
+    if runtimes.len() < 2 {
+        println!(
+            "Need at least two scripts with a known runtime to compare, found {}.",
+            runtimes.len()
+        );
+        return;
+    }
+
+    let Some(coefficient) = correlation(&runtimes, &means) else {
+        println!("Every script has the same runtime or posterior mean, so there is no relationship to measure.");
+        return;
+    };
+
+    println!(
+        "Correlation between average runtime and posterior mean across {} scripts: {:.4}",
+        runtimes.len(),
+        coefficient
+    );
+    if coefficient <= -0.3 {
+        println!("Faster scripts tend to be more interesting.");
+    } else if coefficient >= 0.3 {
+        println!("Slower scripts tend to be more interesting.");
+    } else {
+        println!("No relationship between runtime and interestingness.");
+    }
+}
+
+/// Describe in plain English why the top ranked script leads the ranking.
+///
+/// Scores are computed at the median of each posterior so the explanation is stable between calls, and are
+/// labelled as medians because the ranking itself orders scripts by one random draw from each posterior.
+pub fn print_explain_top(
+    scripts: &[Script],
+    ranking: &[usize],
+    ignore_runtime: bool,
+    runtime_reference_ms: NotNan<f64>,
+) {
+    let Some(top) = ranking.first().map(|index| &scripts[*index]) else {
+        println!("No scripts to explain.");
+        return;
+    };
+
+    println!(
+        "{} is ranked first. It has found {} interesting and {} uninteresting cases, an expected interesting rate of {:.4} (90% credible interval [{:.4}, {:.4}]).",
+        top.name,
+        top.results.interesting,
+        top.results.uninteresting,
+        posterior_mean(&top.results),
+        dist_area_at_percentile(&top.results, 0.05),
+        dist_area_at_percentile(&top.results, 0.95),
+    );
+
+    match top.avgruntime_ms {
+        Some(runtime) => println!("It takes {:.2}ms on average.", runtime),
+        None if !ignore_runtime => {
+            println!("Its runtime is unknown, so it is ranked first until it has been run once.");
+            return;
+        }
+        None => println!("Its runtime is unknown."),
+    }
+
+    let Some(second) = ranking.get(1).map(|index| &scripts[*index]) else {
+        println!("There are no other scripts to compare it to.");
+        return;
+    };
+
+    let explain_score = |script: &Script| {
+        let median = NotNan::new(dist_area_at_percentile(&script.results, 0.5)).unwrap();
+        if ignore_runtime {
+            format!("{:.4} (bias {})", median * script.bias, script.bias)
+        } else {
+            match script.avgruntime_ms {
+                Some(runtime) => format!(
+                    "{:.4} (runtime scaler {:.4}, bias {})",
+                    skew_percentile(
+                        median,
+                        &script.avgruntime_ms,
+                        &script.bias,
+                        runtime_reference_ms
+                    ),
+                    time_scaler(&runtime, runtime_reference_ms),
+                    script.bias
+                ),
+                None => "the maximum score (unknown runtime)".to_string(),
+            }
+        }
+    };
+
+    println!(
+        "Its median score is {}, compared to a median score of {} for {}.",
+        explain_score(top),
+        explain_score(second),
+        second.name
+    );
+    println!(
+        "The ranking orders scripts by one random draw from each posterior, not by these medians. Use --explain for the sampled scores."
+    );
+}
+
+/// Print how every candidate of a thompson selection or ranking scored, best first, marking the selected
+/// script with `*`. `scores` pairs each candidate's script index with its score.
+pub fn print_score_breakdowns(
+    scripts: &[Script],
+    scores: &[(usize, ScoreBreakdown)],
+    selected: usize,
+) {
+    let mut scores = scores.to_vec();
+    scores.sort_by_key(|(_, breakdown)| std::cmp::Reverse(breakdown.score));
+    for (index, breakdown) in scores {
+        let marker = if index == selected { '*' } else { ' ' };
+        let mut parts = format!("sampled {:.4}", breakdown.percentile);
+        if let Some(runtime_scaler) = breakdown.runtime_scaler {
+            parts.push_str(&format!(", runtime scaler {runtime_scaler:.4}"));
+        }
+        if let Some(bias) = breakdown.bias {
+            parts.push_str(&format!(", bias {bias}"));
+        }
+        // Scripts without a runtime are scored the maximum, so they run first.
+        let score = if *breakdown.score == f64::MAX {
+            "maximum (unknown runtime)".to_string()
+        } else {
+            format!("{:.4}", breakdown.score)
+        };
+        println!("{marker} {}: {parts}, score {score}", scripts[index].name);
+    }
+}
+
+/// Sample the active scripts the way `rank` does and print how each scored, best first.
+///
+/// Given a copy of the rng the ranking was sampled with, the scores are the ones it was ordered by.
+pub fn print_ranking_explanation(
+    scripts: &[Script],
+    ignore_runtime: bool,
+    runtime_metric: RuntimeMetric,
+    runtime_reference_ms: NotNan<f64>,
+    min_samples: usize,
+    rng: &mut impl Rng,
+) {
+    let active = active_scripts(scripts);
+    let items = active
+        .iter()
+        .map(|x| &scripts[*x].results)
+        .collect::<Vec<_>>();
+    let priors = script_priors(scripts, &active);
+    let priors = priors.iter().collect::<Vec<_>>();
+    let breakdowns = if ignore_runtime {
+        sample_breakdowns(&items, &priors, None, rng)
+    } else {
+        let runtimes = active
+            .iter()
+            .map(|x| scripts[*x].runtime_cost(runtime_metric))
+            .collect::<Vec<_>>();
+        let runtimes = runtimes.iter().collect::<Vec<_>>();
+        let runcounts = active
+            .iter()
+            .map(|x| scripts[*x].runcount)
+            .collect::<Vec<_>>();
+        let user_biases = active.iter().map(|x| &scripts[*x].bias).collect::<Vec<_>>();
+        sample_breakdowns_bias_runtime(
+            &items,
+            &RuntimeScoring {
+                priors: &priors,
+                runtimes: &runtimes,
+                runcounts: &runcounts,
+                user_biases: &user_biases,
+                runtime_reference_ms,
+                min_samples,
+            },
+            rng,
+        )
+    };
+    let Some(best) = best_score(&breakdowns) else {
+        println!("No scripts to explain.");
+        return;
+    };
+
+    println!("Sampled scores:");
+    let scores = active.iter().copied().zip(breakdowns).collect::<Vec<_>>();
+    print_score_breakdowns(scripts, &scores, active[best]);
+}
+
+/// Score of a script at the median of its posterior, used where a stable score is needed.
+fn median_score(script: &Script, runtime_reference_ms: NotNan<f64>) -> NotNan<f64> {
+    let median = NotNan::new(dist_area_at_percentile(&script.results, 0.5)).unwrap();
+    skew_percentile(
+        median,
+        &script.avgruntime_ms,
+        &script.bias,
+        runtime_reference_ms,
+    )
+}
+
+/// Median score of a script with a bias of 1, or `None` if its runtime is still unknown.
+fn unbiased_median_score(
+    script: &Script,
+    ignore_runtime: bool,
+    runtime_reference_ms: NotNan<f64>,
+) -> Option<NotNan<f64>> {
+    let median = NotNan::new(dist_area_at_percentile(&script.results, 0.5)).unwrap();
+    if ignore_runtime {
+        return Some(median);
+    }
+
+    // Like throughput, treat a 0ms runtime as 1ms.
+    let runtime = script.avgruntime_ms?.max(NotNan::new(1.0).unwrap());
+    Some(skew_percentile(
+        median,
+        &Some(runtime),
+        &NotNan::new(1.0).unwrap(),
+        runtime_reference_ms,
+    ))
+}
+
+/// How many times `suggest_biases` adjusts the biases toward equal chances of selection.
+const CALIBRATION_ROUNDS: usize = 100;
+
+/// Share of `draws` each script wins once its unbiased scores are weighted by `biases`. A tie goes to the
+/// script listed first, as when selecting.
+fn win_rates(draws: &[Vec<f64>], biases: &[f64]) -> Vec<f64> {
+    let mut wins = vec![0; biases.len()];
+    for scores in draws {
+        let mut best = None;
+        let mut best_score = -1.0;
+        for (index, score) in scores.iter().enumerate() {
+            let score = score * biases[index];
+            if score > best_score {
+                best = Some(index);
+                best_score = score;
+            }
+        }
+        if let Some(index) = best {
+            wins[index] += 1;
+        }
+    }
+    wins.into_iter()
+        .map(|win| win as f64 / draws.len() as f64)
+        .collect()
+}
+
+/// The bias for each script that gives every script with a known runtime the same chance of being selected
+/// next, or `None` for a script whose runtime is still unknown, which is selected before the rest whatever
+/// its bias.
+///
+/// The chances are estimated from `SELECTION_DRAWS` thompson sampling steps, drawn once so every round of
+/// calibration is measured against the same draws. The script with the highest median score keeps its bias,
+/// and the rest are scaled to match.
+fn suggest_biases(
+    scripts: &[Script],
+    ignore_runtime: bool,
+    runtime_reference_ms: NotNan<f64>,
+    rng: &mut impl Rng,
+) -> Vec<Option<NotNan<f64>>> {
+    let scores = scripts
+        .iter()
+        .map(|script| unbiased_median_score(script, ignore_runtime, runtime_reference_ms))
+        .collect::<Vec<_>>();
+    let known = (0..scripts.len())
+        .filter(|index| scores[*index].is_some())
+        .collect::<Vec<_>>();
+    // Searched from the back, so the first of several equally high scripts is kept.
+    let Some(anchor) = (0..known.len())
+        .rev()
+        .max_by_key(|position| scores[known[*position]].unwrap() * scripts[known[*position]].bias)
+    else {
+        return vec![None; scripts.len()];
+    };
+
+    let entries = known
+        .iter()
+        .map(|index| &scripts[*index].results)
+        .collect::<Vec<_>>();
+    let priors = known
+        .iter()
+        .map(|index| scripts[*index].prior())
+        .collect::<Vec<_>>();
+    let priors = priors.iter().collect::<Vec<_>>();
+    let scalers = known
+        .iter()
+        .map(|index| match scripts[*index].avgruntime_ms {
+            // Like throughput, treat a 0ms runtime as 1ms.
+            Some(runtime) if !ignore_runtime => time_scaler(
+                &runtime.max(NotNan::new(1.0).unwrap()),
+                runtime_reference_ms,
+            ),
+            _ => NotNan::new(1.0).unwrap(),
+        })
+        .collect::<Vec<_>>();
+    let draws = (0..SELECTION_DRAWS)
+        .map(|_| {
+            sample_breakdowns(&entries, &priors, None, rng)
+                .into_iter()
+                .zip(&scalers)
+                .map(|(breakdown, scaler)| (breakdown.percentile * scaler).into_inner())
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    let target = 1.0 / known.len() as f64;
+    let mut biases = vec![1.0; known.len()];
+    for round in 0..CALIBRATION_ROUNDS {
+        let rates = win_rates(&draws, &biases);
+        for (bias, rate) in biases.iter_mut().zip(rates) {
+            // Each round moves less than the last, so the biases settle instead of overshooting back
+            // and forth around equal chances.
+            let step = 0.5 * 0.95_f64.powi(round as i32);
+            *bias *= (target / rate.max(target / 4.0)).powf(step);
+        }
+    }
+
+    let scale = scripts[known[anchor]].bias.into_inner() / biases[anchor];
+    let mut suggestions = vec![None; scripts.len()];
+    for (index, bias) in known.into_iter().zip(biases) {
+        suggestions[index] = Some(NotNan::new(bias * scale).unwrap());
+    }
+    suggestions
+}
+
+/// Print the bias for each script that would give every script the same chance of being selected next.
+pub fn print_bias_suggestions(
+    scripts: &[Script],
+    ignore_runtime: bool,
+    runtime_reference_ms: NotNan<f64>,
+    rng: &mut impl Rng,
+) {
+    let suggestions = suggest_biases(scripts, ignore_runtime, runtime_reference_ms, rng);
+
+    println!("Suggested biases:");
+    println!("Each suggestion gives every script an equal chance of being selected next, estimated from {SELECTION_DRAWS} sampling steps. The current highest scoring script keeps its bias.");
+    for (script, suggestion) in scripts.iter().zip(&suggestions) {
+        match suggestion {
+            Some(suggestion) => println!(
+                "{}: current {:.4}, suggested {:.4}",
+                script.name, script.bias, suggestion
+            ),
+            None => println!(
+                "{}: current {:.4}, needs a run before a bias can be suggested",
+                script.name, script.bias
+            ),
+        }
+    }
+}
+
+/// One grep-friendly line summarizing a config, e.g. `12 scripts, 345 runs, 28 interesting, best=foo(0.42)`.
+///
+/// The best script is the top of a thompson ranking, ignoring runtime and bias, with the percentile it was
+/// ranked by.
+pub fn status_line(scripts: &[Script], rng: &mut impl Rng) -> String {
+    let runs = scripts.iter().map(|script| script.runcount).sum::<u64>();
+    let interesting = scripts
+        .iter()
+        .map(|script| script.results.interesting)
+        .sum::<f64>();
+    let entries = scripts
+        .iter()
+        .map(|script| &script.results)
+        .collect::<Vec<_>>();
+    let priors = scripts.iter().map(Script::prior).collect::<Vec<_>>();
+    let priors = priors.iter().collect::<Vec<_>>();
+    let best = match thompson_scores(&entries, &priors, rng).first() {
+        Some((index, score)) => format!("{}({:.2})", scripts[*index].name, score),
+        None => "none".to_string(),
+    };
+    format!(
+        "{} scripts, {runs} runs, {interesting} interesting, best={best}",
+        scripts.len()
+    )
+}
+
+/// Print `points` points of a script's posterior as `x,density` CSV, for plotting with external tools.
+pub fn print_posterior_curve(script: &Script, points: usize) {
+    let mut writer = csv::Writer::from_writer(std::io::stdout());
+    writer.write_record(["x", "density"]).unwrap();
+    for (x, density) in sample_posterior_curve(&script.results, points) {
+        writer
+            .write_record([x.to_string(), density.to_string()])
+            .unwrap();
+    }
+    writer.flush().unwrap();
+}
+
+/// How many sampling steps `print_selection_probabilities` estimates the probabilities from.
+pub const SELECTION_DRAWS: usize = 10_000;
+
+/// Print how likely each script that can still run is to be selected next, most likely first, by tallying the
+/// winner of `SELECTION_DRAWS` thompson sampling steps. Without `runtimes` the steps ignore runtime, like
+/// `run --ignore-runtime`.
+pub fn print_selection_probabilities(
+    scripts: &[Script],
+    runtimes: Option<&[&Option<NotNan<f64>>]>,
+    runtime_reference_ms: NotNan<f64>,
+    min_samples: usize,
+    rng: &mut impl Rng,
+) {
+    let mut active = active_scripts(scripts);
+    active.retain(|x| scripts[*x].dependencies_met(scripts));
+    let entries = active
+        .iter()
+        .map(|x| &scripts[*x].results)
+        .collect::<Vec<_>>();
+    let user_biases = active.iter().map(|x| &scripts[*x].bias).collect::<Vec<_>>();
+    let priors = script_priors(scripts, &active);
+    let priors = priors.iter().collect::<Vec<_>>();
+    let runcounts = active
+        .iter()
+        .map(|x| scripts[*x].runcount)
+        .collect::<Vec<_>>();
+
+    let probabilities = match runtimes {
+        Some(runtimes) => {
+            let runtimes = active.iter().map(|x| runtimes[*x]).collect::<Vec<_>>();
+            selection_probabilities(active.len(), SELECTION_DRAWS, || {
+                thompson_sampling_bias_runtime(
+                    &entries,
+                    &RuntimeScoring {
+                        priors: &priors,
+                        runtimes: &runtimes,
+                        runcounts: &runcounts,
+                        user_biases: &user_biases,
+                        runtime_reference_ms,
+                        min_samples,
+                    },
+                    rng,
+                )
+            })
+        }
+        None => selection_probabilities(active.len(), SELECTION_DRAWS, || {
+            thompson_sampling(&entries, &priors, &user_biases, rng)
+        }),
+    };
+
+    let mut order = (0..active.len()).collect::<Vec<_>>();
+    order.sort_by(|a, b| probabilities[*b].total_cmp(&probabilities[*a]));
+
+    println!("Chance of being selected next:");
+    for position in order {
+        println!(
+            "{}: {:.1}%",
+            scripts[active[position]].name,
+            probabilities[position] * 100.
+        );
+    }
+}
+
+/// Print the step of the run in which each script first found something interesting, a "time to first
+/// bug" for comparing strategies.
+pub fn print_first_interesting_steps(scripts: &[Script]) {
+    println!("First interesting step:");
+    for script in scripts {
+        match script.first_interesting_step {
+            Some(step) => println!("{}: step {}", script.name, step),
+            None => println!("{}: nothing interesting yet", script.name),
+        }
+    }
+}
+
+/// Draw the top 3 plots as an SVG image: the posteriors of the 3 most run scripts and, with a runtime
+/// reference, their runtime and bias scaled scores below. Fewer scripts are plotted if there are fewer.
+pub fn write_top_3_svg(
+    scripts: &[Script],
+    runtime_reference_ms: Option<NotNan<f64>>,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    let mut scripts = scripts.iter().collect::<Vec<_>>();
+    scripts.sort_by(|a, b| b.runcount.partial_cmp(&a.runcount).unwrap());
+    scripts.truncate(3);
+
+    let (width, panel_height, margin) = (640., 400., 40.);
+    let points = 100;
+    let xs = (0..=points)
+        .map(|i| i as f64 / points as f64)
+        .collect::<Vec<_>>();
+
+    let mut panels: Vec<(&str, Vec<Vec<f64>>)> = vec![(
+        "Top 3 run scripts. Interesting cases (area under curve).",
+        scripts
+            .iter()
+            .map(|script| {
+                xs.iter()
+                    .map(|x| {
+                        puruspe::betai(
+                            script.results.uninteresting + 1.0,
+                            script.results.interesting + 1.0,
+                            *x,
+                        )
+                    })
+                    .collect()
+            })
+            .collect(),
+    )];
+    if let Some(runtime_reference_ms) = runtime_reference_ms {
+        panels.push((
+            "Inverse 3 run scripts. Minimizing time per interesting case (area under curve).",
+            scripts
+                .iter()
+                .map(|script| {
+                    xs.iter()
+                        .map(|x| {
+                            *skew_percentile(
+                                NotNan::new(puruspe::invbetai(
+                                    *x,
+                                    script.results.interesting + 1.0,
+                                    script.results.uninteresting + 1.0,
+                                ))
+                                .unwrap(),
+                                &script.avgruntime_ms,
+                                &script.bias,
+                                runtime_reference_ms,
+                            )
+                        })
+                        .collect()
+                })
+                .collect(),
+        ));
+    }
+
+    writeln!(
+        out,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{}\">",
+        panel_height * panels.len() as f64
+    )?;
+    for (panel, (title, curves)) in panels.iter().enumerate() {
+        let top = panel as f64 * panel_height;
+        let max_y = curves
+            .iter()
+            .flatten()
+            .fold(f64::MIN_POSITIVE, |max, y| max.max(*y));
+        let x = |x: f64| margin + x * (width - 2. * margin);
+        let y = |y: f64| top + panel_height - margin - y / max_y * (panel_height - 2. * margin);
+
+        writeln!(
+            out,
+            "<text x=\"{margin}\" y=\"{}\" font-size=\"14\">{title}</text>",
+            top + margin / 2.
+        )?;
+        writeln!(
+            out,
+            "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"black\"/>",
+            x(0.),
+            y(0.),
+            x(1.),
+            y(0.)
+        )?;
+        writeln!(
+            out,
+            "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"black\"/>",
+            x(0.),
+            y(0.),
+            x(0.),
+            y(max_y)
+        )?;
+        for (rank, ((curve, color), script)) in
+            curves.iter().zip(TOP_3_COLORS).zip(&scripts).enumerate()
+        {
+            let line = xs
+                .iter()
+                .zip(curve)
+                .map(|(px, py)| format!("{:.2},{:.2}", x(*px), y(*py)))
+                .collect::<Vec<_>>()
+                .join(" ");
+            let stroke = format!("rgb({},{},{})", color.r, color.g, color.b);
+            writeln!(
+                out,
+                "<polyline points=\"{line}\" fill=\"none\" stroke=\"{stroke}\"/>"
+            )?;
+            writeln!(
+                out,
+                "<text x=\"{}\" y=\"{}\" fill=\"{stroke}\">{}: {} {}ms</text>",
+                width - 4. * margin,
+                top + margin + 16. * (rank + 1) as f64,
+                rank + 1,
+                escape_xml(&script.name),
+                script.avgruntime_ms.unwrap_or(NotNan::new(-1.0).unwrap())
+            )?;
+        }
+    }
+    writeln!(out, "</svg>")
+}
+
+/// Escape text for use in SVG markup.
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Plot how a script's score changes with its bias, holding its posterior and runtime fixed.
+///
+/// The strongest other script (by median score) is drawn as a flat line, and the bias at which the
+/// script overtakes it is reported. Optionally writes the same curves to an SVG image.
+pub fn plot_bias_tradeoff(
+    scripts: &[Script],
+    index: usize,
+    max_bias: Option<f64>,
+    runtime_reference_ms: NotNan<f64>,
+    image: Option<&PathBuf>,
+) {
+    let script = &scripts[index];
+    let Some(runtime) = script.avgruntime_ms else {
+        println!(
+            "{} has no recorded runtime, so it is always run next regardless of bias.",
+            script.name
+        );
+        return;
+    };
+
+    let median = NotNan::new(dist_area_at_percentile(&script.results, 0.5)).unwrap();
+    // The score is linear in bias, this is the score at a bias of 1.
+    let score_per_bias = f64::from(median * time_scaler(&runtime, runtime_reference_ms));
+
+    let competitor = scripts
+        .iter()
+        .enumerate()
+        .filter(|(other, _)| *other != index)
+        .map(|(_, other)| (other, median_score(other, runtime_reference_ms)))
+        .max_by_key(|(_, score)| *score);
+
+    let crossover = competitor
+        .as_ref()
+        .map(|(_, score)| **score / score_per_bias);
+    let max_bias = max_bias.unwrap_or_else(|| {
+        crossover
+            .filter(|crossover| crossover.is_finite())
+            .map_or(10., |crossover| (crossover * 2.).max(10.))
+    });
+    let competitor_score = competitor.as_ref().map_or(0., |(_, score)| **score);
+
+    use textplots::{Chart, Shape};
+
+    println!(
+        "Score of {} as its bias varies from 0 to {:.2} (posterior and runtime held fixed).",
+        script.name, max_bias
+    );
+
+    let script_color = RGB8 {
+        r: 100,
+        g: 250,
+        b: 200,
+    };
+    let competitor_color = RGB8 {
+        r: 200,
+        g: 100,
+        b: 250,
+    };
+
+    Chart::new(120, 60, 0.0, max_bias as f32)
+        .linecolorplot(
+            &Shape::Continuous(Box::new(|_| competitor_score as f32)),
+            competitor_color,
+        )
+        .linecolorplot(
+            &Shape::Continuous(Box::new(|bias| bias * score_per_bias as f32)),
+            script_color,
+        )
+        .display();
+
+    println!(
+        "{} scores {:.4} at its current bias of {}.",
+        script
+            .name
+            .truecolor(script_color.r, script_color.g, script_color.b),
+        score_per_bias * *script.bias,
+        script.bias
+    );
+
+    match (&competitor, crossover) {
+        (Some((other, score)), Some(crossover)) => {
+            println!(
+                "Top competitor {} scores {:.4} at its bias of {}.",
+                other
+                    .name
+                    .truecolor(competitor_color.r, competitor_color.g, competitor_color.b),
+                score,
+                other.bias
+            );
+            if crossover.is_finite() {
+                println!(
+                    "{} overtakes {} at a bias of {:.4}.",
+                    script.name, other.name, crossover
+                );
+            } else {
+                println!(
+                    "{} never overtakes {} by bias alone.",
+                    script.name, other.name
+                );
+            }
+        }
+        _ => println!("There are no other scripts to compare against."),
+    }
+
+    if let Some(image) = image {
+        write_bias_tradeoff_svg(
+            image,
+            max_bias,
+            score_per_bias,
+            competitor_score,
+            crossover.filter(|crossover| crossover.is_finite() && *crossover <= max_bias),
+        );
+        println!("Wrote plot to {}", image.display());
+    }
+}
+
+fn write_bias_tradeoff_svg(
+    path: &PathBuf,
+    max_bias: f64,
+    score_per_bias: f64,
+    competitor_score: f64,
+    crossover: Option<f64>,
+) {
+    let (width, height, margin) = (640., 400., 40.);
+    let max_score = (max_bias * score_per_bias)
+        .max(competitor_score)
+        .max(f64::MIN_POSITIVE);
+    let x = |bias: f64| margin + bias / max_bias * (width - 2. * margin);
+    let y = |score: f64| height - margin - score / max_score * (height - 2. * margin);
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\">\n"
+    );
+    svg.push_str(&format!(
+        "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"black\"/>\n",
+        x(0.),
+        y(0.),
+        x(max_bias),
+        y(0.)
+    ));
+    svg.push_str(&format!(
+        "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"black\"/>\n",
+        x(0.),
+        y(0.),
+        x(0.),
+        y(max_score)
+    ));
+    svg.push_str(&format!(
+        "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"rgb(200,100,250)\"/>\n",
+        x(0.),
+        y(competitor_score),
+        x(max_bias),
+        y(competitor_score)
+    ));
+    svg.push_str(&format!(
+        "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"rgb(100,250,200)\"/>\n",
+        x(0.),
+        y(0.),
+        x(max_bias),
+        y(max_bias * score_per_bias)
+    ));
+    if let Some(crossover) = crossover {
+        svg.push_str(&format!(
+            "<line x1=\"{0}\" y1=\"{1}\" x2=\"{0}\" y2=\"{2}\" stroke=\"gray\" stroke-dasharray=\"4\"/>\n",
+            x(crossover),
+            y(0.),
+            y(max_score)
+        ));
+        svg.push_str(&format!(
+            "<text x=\"{}\" y=\"{}\" font-size=\"12\">bias {:.4}</text>\n",
+            x(crossover) + 4.,
+            margin,
+            crossover
+        ));
+    }
+    svg.push_str("</svg>\n");
+
+    let mut file = File::create(path).unwrap();
+    file.write_all(svg.as_bytes()).unwrap();
+}
+
+#[test]
+fn test_suggest_biases_equalize_selection() {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    let script = |name: &str, interesting: u64, uninteresting: u64, runtime: f64| -> Script {
+        let mut script = crate::test_script(name, interesting, uninteresting);
+        script["avgruntime_ms"] = runtime.into();
+        serde_json::from_value(script).unwrap()
+    };
+    let mut scripts = vec![
+        script("strong", 8, 2, 100.0),
+        script("weak", 1, 9, 100.0),
+        script("slow", 5, 5, 1000.0),
+        script("new", 0, 0, 0.0),
+    ];
+    scripts[3].avgruntime_ms = None;
+    let reference = NotNan::new(1000.0).unwrap();
+
+    let suggestions = suggest_biases(&scripts, false, reference, &mut StdRng::seed_from_u64(1));
+    assert_eq!(suggestions[0], Some(NotNan::new(1.0).unwrap()));
+    assert_eq!(suggestions[3], None);
+
+    // Measured against fresh draws, each calibrated script wins about a third of the time.
+    for (script, suggestion) in scripts.iter_mut().zip(&suggestions) {
+        script.bias = suggestion.unwrap_or(script.bias);
+    }
+    let calibrated = &scripts[..3];
+    let entries = calibrated
+        .iter()
+        .map(|script| &script.results)
+        .collect::<Vec<_>>();
+    let priors = calibrated.iter().map(Script::prior).collect::<Vec<_>>();
+    let priors = priors.iter().collect::<Vec<_>>();
+    let runtimes = calibrated
+        .iter()
+        .map(|script| script.avgruntime_ms)
+        .collect::<Vec<_>>();
+    let runtimes = runtimes.iter().collect::<Vec<_>>();
+    let runcounts = calibrated
+        .iter()
+        .map(|script| script.runcount)
+        .collect::<Vec<_>>();
+    let biases = calibrated
+        .iter()
+        .map(|script| &script.bias)
+        .collect::<Vec<_>>();
+    let scoring = RuntimeScoring {
+        priors: &priors,
+        runtimes: &runtimes,
+        runcounts: &runcounts,
+        user_biases: &biases,
+        runtime_reference_ms: reference,
+        min_samples: 1,
+    };
+    let mut rng = StdRng::seed_from_u64(2);
+    let probabilities = selection_probabilities(3, SELECTION_DRAWS, || {
+        thompson_sampling_bias_runtime(&entries, &scoring, &mut rng)
+    });
+    for probability in probabilities {
+        assert!((probability - 1.0 / 3.0).abs() < 0.03, "{probability}");
     }
 }
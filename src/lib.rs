@@ -0,0 +1,2189 @@
+//! Biased Thompson sampling over a config of scripts, the engine behind the `bandits` command.
+//!
+//! A run is a loop of [`step`]s, each of which picks scripts with [`choose_script`], runs them with
+//! [`run_script_with_output`] and folds the results back into the config with [`update_state`]:
+//!
+//! ```
+//! use bandits::{config::Config, step, ExecutionOptions, SelectionOptions, SelectionState};
+//!
+//! let mut config: Config = serde_json::from_str(
+//!     r#"{
+//!         "scripts": [
+//!             {
+//!                 "name": "list",
+//!                 "command": "ls",
+//!                 "results": { "interesting": 0, "uninteresting": 0 },
+//!                 "runcount": 0,
+//!                 "avgruntime_ms": null,
+//!                 "bias": 1.0
+//!             }
+//!         ]
+//!     }"#,
+//! )
+//! .unwrap();
+//! let options = SelectionOptions::default();
+//! let execution = ExecutionOptions::default();
+//! let mut state = SelectionState::new(Some(1));
+//!
+//! for _ in 0..3 {
+//!     assert_eq!(step(&mut config, &options, &mut state, &execution, 1), 1);
+//! }
+//!
+//! // ls exits 0, which is uninteresting.
+//! assert_eq!(config.scripts[0].runcount, 3);
+//! assert_eq!(config.scripts[0].results.uninteresting, 3.0);
+//! ```
+#![feature(test)]
+pub mod config;
+pub mod history;
+pub mod ibeta;
+pub mod insights;
+pub mod lint;
+pub mod median;
+pub mod process;
+pub mod schedule;
+pub mod template;
+pub mod thompson;
+
+use config::{Config, Script, RECENT_RUNTIMES};
+use history::{append_history, now_ms, HistoryRecord, Regret};
+use log::{debug, trace, warn};
+use median::StreamingMedian;
+use ordered_float::NotNan;
+use process::{
+    apply_resource_limits, program_command, script_command, terminating_signal, wait_for_output,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use regex::Regex;
+use schedule::{local_minute_of_day, Schedule};
+use std::{
+    collections::{HashMap, VecDeque},
+    fs::File,
+    io,
+    path::{Path, PathBuf},
+    process::{ExitStatus, Output},
+    sync::Mutex,
+    thread,
+    time::{Duration, Instant},
+};
+use template::expand_env;
+use thompson::{
+    best_score, epsilon_greedy, interleave, round_robin, sample_breakdowns,
+    sample_breakdowns_bias_runtime, ucb1, uniform_random, widest_interval, Prior, RuntimeScoring,
+    ScoreBreakdown, Strategy, ThompsonInfo, DEFAULT_RUNTIME_REFERENCE_MS,
+};
+
+/// Settings that control how `step` picks the next script.
+pub struct SelectionOptions {
+    pub priors: HashMap<String, Prior>,
+    pub ignore_runtime: bool,
+    pub runtime_reference_ms: NotNan<f64>,
+    /// Nonzero biases below this are raised to it.
+    pub min_effective_bias: Option<f64>,
+    /// Run the script with the widest credible interval until every interval is narrower than this.
+    pub warmup_until_width: Option<f64>,
+    /// Cycle through scripts in order, skipping those with a posterior mean below this fraction of the best.
+    pub interleave_skip_below: Option<f64>,
+    pub strategy: Strategy,
+    /// Runs a script needs before its average runtime biases selection.
+    pub min_samples: usize,
+    /// A script isn't selected again until this many other selections have been made, unless every
+    /// selectable script is cooling down.
+    pub cooldown: usize,
+    /// Only scripts carrying one of these tags are selected. Empty selects from every script.
+    pub tags: Vec<String>,
+}
+
+impl Default for SelectionOptions {
+    /// Plain thompson sampling biased by runtime in milliseconds, with no priors, floor, warmup or
+    /// interleaving.
+    fn default() -> Self {
+        SelectionOptions {
+            priors: HashMap::new(),
+            ignore_runtime: false,
+            runtime_reference_ms: NotNan::new(DEFAULT_RUNTIME_REFERENCE_MS).unwrap(),
+            min_effective_bias: None,
+            warmup_until_width: None,
+            interleave_skip_below: None,
+            strategy: Strategy::default(),
+            min_samples: 1,
+            cooldown: 0,
+            tags: vec![],
+        }
+    }
+}
+
+/// Settings that control how `step` runs the chosen script.
+#[derive(Default)]
+pub struct ExecutionOptions {
+    pub tail_output: Option<usize>,
+    /// Overrides every script's `timeout_ms`.
+    pub timeout_ms: Option<u64>,
+    /// Append a `HistoryRecord` here after every step.
+    pub history: Option<File>,
+    /// Save the tail of every interesting run's output to a file in this directory.
+    pub capture_dir: Option<PathBuf>,
+    /// Print how every candidate scored each time a script is chosen.
+    pub explain: bool,
+}
+
+/// Selection history carried between steps of a run.
+#[derive(Debug)]
+pub struct SelectionState {
+    pub last_selected: Option<usize>,
+    pub steps_taken: usize,
+    /// Scripts whose command could not be started or whose schedule is invalid. They are not selected again.
+    pub failed: Vec<usize>,
+    /// Scripts already reported as over their `max_runtime_ms`.
+    pub too_slow: Vec<usize>,
+    /// How many scripts have been chosen.
+    pub selections: u64,
+    /// The script the round-robin strategy picked last, by name, so its turn order survives scripts
+    /// leaving or rejoining the selection.
+    pub round_robin_last: Option<String>,
+    /// Interesting results found by `step` so far, across all scripts.
+    pub interesting_found: u64,
+    /// Steps `step` has run, for estimating the run's regret.
+    pub regret: Regret,
+    /// How every candidate of the last thompson selection scored, by script index. Empty if the last
+    /// selection didn't sample, e.g. during warmup or under another strategy.
+    pub last_scores: Vec<(usize, ScoreBreakdown)>,
+    /// Scripts the last selection left out and why, by script index. Each is reported on stderr when it
+    /// is first left out.
+    pub filtered: Vec<(usize, FilterReason)>,
+    /// The most recent selections, newest last, at most `cooldown` of them.
+    recent: VecDeque<usize>,
+    /// Every random choice of the run is drawn from this, so a seeded run is reproducible.
+    rng: StdRng,
+}
+
+impl SelectionState {
+    pub fn new(seed: Option<u64>) -> Self {
+        SelectionState {
+            last_selected: None,
+            steps_taken: 0,
+            failed: vec![],
+            too_slow: vec![],
+            selections: 0,
+            round_robin_last: None,
+            interesting_found: 0,
+            regret: Regret::default(),
+            last_scores: vec![],
+            filtered: vec![],
+            recent: VecDeque::new(),
+            rng: seeded_rng(seed),
+        }
+    }
+
+    /// Forget everything remembered by script index, for when the config's scripts may have changed.
+    pub fn scripts_changed(&mut self) {
+        self.last_selected = None;
+        self.failed.clear();
+        self.too_slow.clear();
+        self.filtered.clear();
+        self.recent.clear();
+    }
+}
+
+/// Why `choose_script` left a script out.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FilterReason {
+    Limit,
+    Dependencies,
+    TooSlow,
+    OutsideSchedule,
+    Failed,
+}
+
+impl std::fmt::Display for FilterReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(match self {
+            FilterReason::Limit => "it reached its limit",
+            FilterReason::Dependencies => "its dependencies haven't found enough interesting cases",
+            FilterReason::TooSlow => "it averages over its max_runtime_ms",
+            FilterReason::OutsideSchedule => "it is outside its schedule",
+            FilterReason::Failed => "it failed to start or has an invalid schedule",
+        })
+    }
+}
+
+/// Why `choose_script` would leave out the script at `index`, or `None` if it can be selected.
+fn filter_reason(
+    config: &Config,
+    index: usize,
+    state: &SelectionState,
+    now_minute: u32,
+) -> Option<FilterReason> {
+    let script = &config.scripts[index];
+    if state.failed.contains(&index) {
+        Some(FilterReason::Failed)
+    } else if !script.is_active() {
+        Some(FilterReason::Limit)
+    } else if script.is_too_slow() {
+        Some(FilterReason::TooSlow)
+    } else if !script.dependencies_met(&config.scripts) {
+        Some(FilterReason::Dependencies)
+    } else if !script.in_schedule(now_minute) {
+        Some(FilterReason::OutsideSchedule)
+    } else {
+        None
+    }
+}
+
+/// An RNG seeded with `seed`, or from entropy when there is none.
+pub fn seeded_rng(seed: Option<u64>) -> StdRng {
+    match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    }
+}
+
+/// Raise a nonzero bias to `floor`. A zero bias is left alone since it has its own meaning.
+pub fn effective_bias(bias: NotNan<f64>, floor: Option<f64>) -> NotNan<f64> {
+    match floor {
+        Some(floor) if bias.into_inner() > 0.0 && bias.into_inner() < floor => {
+            NotNan::new(floor).unwrap()
+        }
+        _ => bias,
+    }
+}
+
+/// Pick the next script to run, or `None` if every script is past its limit, over its max runtime, outside
+/// its schedule or failed to start earlier in the run.
+///
+/// `now_minute` is the local time in minutes since midnight, checked against script schedules.
+pub fn choose_script(
+    config: &Config,
+    options: &SelectionOptions,
+    state: &mut SelectionState,
+    now_minute: u32,
+) -> Option<usize> {
+    for (index, script) in config.scripts.iter().enumerate() {
+        if script.is_too_slow() && !state.too_slow.contains(&index) {
+            warn!(
+                "{} averages {:.0}ms, over its max_runtime_ms of {}. It will no longer be selected.",
+                script.name,
+                script.avgruntime_ms.unwrap(),
+                script.max_runtime_ms.unwrap()
+            );
+            state.too_slow.push(index);
+        }
+        if let Some(Err(err)) = script.schedule.as_ref().map(|s| s.parse::<Schedule>()) {
+            if !state.failed.contains(&index) {
+                warn!(
+                    "{} has an invalid schedule. It will no longer be selected. {err}",
+                    script.name
+                );
+                state.failed.push(index);
+            }
+        }
+    }
+
+    // Scripts without any of the tags are out of scope rather than filtered, so they aren't reported.
+    let tagged = (0..config.scripts.len())
+        .filter(|index| config.scripts[*index].has_any_tag(&options.tags))
+        .collect::<Vec<_>>();
+    let filtered = tagged
+        .iter()
+        .filter_map(|index| {
+            filter_reason(config, *index, state, now_minute).map(|reason| (*index, reason))
+        })
+        .collect::<Vec<_>>();
+    for (index, reason) in &filtered {
+        // Slow and failed scripts are already reported, with more detail, when it happens.
+        if !matches!(reason, FilterReason::TooSlow | FilterReason::Failed)
+            && !state.filtered.contains(&(*index, *reason))
+        {
+            eprintln!(
+                "{} is left out of selection: {reason}.",
+                config.scripts[*index].name
+            );
+        }
+    }
+    let eligible = tagged
+        .into_iter()
+        .filter(|index| !filtered.iter().any(|(filtered, _)| filtered == index))
+        .collect::<Vec<_>>();
+    state.filtered = filtered;
+    if eligible.is_empty() {
+        return None;
+    }
+    let cooled_down = eligible
+        .iter()
+        .copied()
+        .filter(|index| !state.recent.contains(index))
+        .collect::<Vec<_>>();
+    // If every script is still cooling down, ignore the cooldown for this selection.
+    let eligible = if cooled_down.is_empty() {
+        eligible
+    } else {
+        cooled_down
+    };
+
+    let items = eligible
+        .iter()
+        .map(|x| &config.scripts[*x].results)
+        .collect::<Vec<_>>();
+    let entries: &[&ThompsonInfo] = items.as_slice();
+    let own_priors = eligible
+        .iter()
+        .map(|x| config.scripts[*x].prior())
+        .collect::<Vec<_>>();
+    let prior = eligible
+        .iter()
+        .zip(&own_priors)
+        .map(|(x, own_prior)| {
+            options
+                .priors
+                .get(&config.scripts[*x].name)
+                .unwrap_or(own_prior)
+        })
+        .collect::<Vec<_>>();
+    let priors: &[&Prior] = prior.as_slice();
+    let runtime = eligible
+        .iter()
+        .map(|x| config.scripts[*x].runtime_cost(config.runtime_metric))
+        .collect::<Vec<_>>();
+    let runtime = runtime.iter().collect::<Vec<_>>();
+    let runtimes: &[&Option<NotNan<f64>>] = runtime.as_slice();
+    let runcounts = eligible
+        .iter()
+        .map(|x| config.scripts[*x].runcount)
+        .collect::<Vec<_>>();
+
+    let user_biases = eligible
+        .iter()
+        .map(|x| effective_bias(config.scripts[*x].bias, options.min_effective_bias))
+        .collect::<Vec<_>>();
+    let user_biases = user_biases.iter().collect::<Vec<_>>();
+    let user_biases: &[&NotNan<f64>] = user_biases.as_slice();
+
+    let warmup = options
+        .warmup_until_width
+        .and_then(|target_width| widest_interval(entries, priors, &runcounts, target_width));
+
+    state.last_scores.clear();
+    let selected = if let Some(index) = warmup {
+        debug!(
+            "Warming up entry {} with the widest credible interval",
+            index
+        );
+        index
+    } else if let Some(skip_below) = options.interleave_skip_below {
+        // Continue the cycle from the script after the last one run.
+        let start = state
+            .last_selected
+            .and_then(|last| eligible.iter().position(|index| *index > last))
+            .unwrap_or(0);
+        interleave(entries, priors, start, skip_below).unwrap()
+    } else if let Strategy::EpsilonGreedy(epsilon) = options.strategy {
+        epsilon_greedy(entries, epsilon, &mut state.rng).unwrap()
+    } else if options.strategy == Strategy::Ucb1 {
+        ucb1(entries, &runcounts, runcounts.iter().sum()).unwrap()
+    } else if options.strategy == Strategy::RoundRobin {
+        let last = state.round_robin_last.as_ref().and_then(|name| {
+            config
+                .scripts
+                .iter()
+                .position(|script| &script.name == name)
+        });
+        let selected = round_robin(&eligible, last).unwrap();
+        state.round_robin_last = Some(config.scripts[eligible[selected]].name.clone());
+        selected
+    } else if options.strategy == Strategy::Uniform {
+        uniform_random(entries, &mut state.rng).unwrap()
+    } else {
+        let breakdowns = if options.ignore_runtime {
+            sample_breakdowns(entries, priors, Some(user_biases), &mut state.rng)
+        } else {
+            sample_breakdowns_bias_runtime(
+                entries,
+                &RuntimeScoring {
+                    priors,
+                    runtimes,
+                    runcounts: &runcounts,
+                    user_biases,
+                    runtime_reference_ms: options.runtime_reference_ms,
+                    min_samples: options.min_samples,
+                },
+                &mut state.rng,
+            )
+        };
+        let selected = best_score(&breakdowns).unwrap();
+        debug!("Selected entry: {:?}", selected);
+        state.last_scores = eligible.iter().copied().zip(breakdowns).collect();
+        selected
+    };
+    state.selections += 1;
+    state.recent.push_back(eligible[selected]);
+    while state.recent.len() > options.cooldown {
+        state.recent.pop_front();
+    }
+
+    Some(eligible[selected])
+}
+
+pub fn run_script(script: &Script) -> io::Result<ScriptResult> {
+    Ok(run_script_with_output(script, None)?.0)
+}
+
+/// Runs a script, also returning its captured output.
+///
+/// `timeout_ms` overrides the script's own timeout. Fails if the command can't be started, e.g. it is
+/// blank, references an unset variable or names a missing program, or if its `interesting_regex` is invalid.
+pub fn run_script_with_output(
+    script: &Script,
+    timeout_ms: Option<u64>,
+) -> io::Result<(ScriptResult, Output)> {
+    let command = expand_env(&script.command, script.empty_unset_variables)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err.to_string()))?;
+
+    let interesting_regex = script
+        .interesting_regex
+        .as_deref()
+        .map(Regex::new)
+        .transpose()
+        .map_err(|err| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid interesting_regex: {err}"),
+            )
+        })?;
+
+    let mut command = match &script.args {
+        Some(args) => program_command(&command, args)?,
+        None => script_command(&command, script.shell)?,
+    };
+    apply_resource_limits(&mut command, script.resource_limits());
+
+    let timeout = timeout_ms.or(script.timeout_ms).map(Duration::from_millis);
+
+    // Execute the command, retrying transient failures. The runtime covers every attempt.
+    let start = Instant::now();
+    let mut child = wait_for_output(&mut command, timeout)?;
+    for attempt in 1..=script.retries {
+        if child.timed_out
+            || !child
+                .output
+                .status
+                .code()
+                .is_some_and(|code| script.retry_on_codes.contains(&code))
+        {
+            break;
+        }
+        warn!(
+            "{} exited with status {}, retrying ({}/{}).",
+            script.name, child.output.status, attempt, script.retries
+        );
+        child = wait_for_output(&mut command, timeout)?;
+    }
+    let duration = start.elapsed();
+    let (output, peak_mem_kb) = (child.output, child.peak_mem_kb);
+
+    if child.timed_out {
+        eprintln!(
+            "{} timed out after {}ms. Adding to avg iteration runtime but not logging as interesting/uninteresting",
+            script.name,
+            duration.as_millis()
+        );
+        let result = ScriptResult {
+            interesting: 0,
+            uninteresting: 0,
+            penalty: 0,
+            runtime_ms: duration.as_millis(),
+            peak_mem_kb,
+            timed_out: true,
+        };
+        return Ok((result, output));
+    }
+
+    let weight = reported_interesting(&output.stdout).or_else(|| {
+        if script.weighted {
+            trailing_weight(&output.stdout)
+        } else {
+            None
+        }
+    });
+    if let Some(weight) = weight {
+        debug!("Command reported weight {}", weight);
+        // A weight of 0 is a run that found nothing.
+        let result = ScriptResult {
+            interesting: weight,
+            uninteresting: (weight == 0) as u64,
+            penalty: 0,
+            runtime_ms: duration.as_millis(),
+            peak_mem_kb,
+            timed_out: false,
+        };
+        return Ok((result, output));
+    }
+
+    if let Some(regex) = interesting_regex.filter(|regex| {
+        regex.is_match(&String::from_utf8_lossy(&output.stdout))
+            || regex.is_match(&String::from_utf8_lossy(&output.stderr))
+    }) {
+        debug!("Output matched {}, logging as interesting.", regex);
+        let result = ScriptResult {
+            interesting: 1,
+            uninteresting: 0,
+            penalty: 0,
+            runtime_ms: duration.as_millis(),
+            peak_mem_kb,
+            timed_out: false,
+        };
+        return Ok((result, output));
+    }
+
+    if let Some(threshold) = script.stderr_line_threshold {
+        let lines = count_stderr_lines(&output.stderr, script.count_blank_stderr_lines);
+        let interesting = lines > threshold;
+        debug!(
+            "Command wrote {} stderr lines (threshold {}), interesting: {}",
+            lines, threshold, interesting
+        );
+        let result = ScriptResult {
+            interesting: interesting as u64,
+            uninteresting: !interesting as u64,
+            penalty: 0,
+            runtime_ms: duration.as_millis(),
+            peak_mem_kb,
+            timed_out: false,
+        };
+        return Ok((result, output));
+    }
+
+    // Print the output
+    let code = output.status.code();
+    let result = if code.is_some_and(|code| script.penalty_codes.contains(&code)) {
+        warn!(
+            "Command exited with status {}, logging as uninteresting and taking back an interesting case.",
+            output.status
+        );
+        ScriptResult {
+            interesting: 0,
+            uninteresting: 1,
+            penalty: 1,
+            runtime_ms: duration.as_millis(),
+            peak_mem_kb,
+            timed_out: false,
+        }
+    } else if code.is_some_and(|code| script.is_uninteresting_code(code)) {
+        trace!(
+            "Command exited with status {}, logging as uninteresting.",
+            output.status
+        );
+        trace!("Output: {}", String::from_utf8_lossy(&output.stdout));
+        ScriptResult {
+            interesting: 0,
+            uninteresting: 1,
+            penalty: 0,
+            runtime_ms: duration.as_millis(),
+            peak_mem_kb,
+            timed_out: false,
+        }
+    } else if code.is_some_and(|code| script.is_interesting_code(code)) {
+        warn!(
+            "Command exited with status {}, logging as interesting.",
+            output.status
+        );
+        warn!("Error: {}", String::from_utf8_lossy(&output.stderr));
+        ScriptResult {
+            interesting: 1,
+            uninteresting: 0,
+            penalty: 0,
+            runtime_ms: duration.as_millis(),
+            peak_mem_kb,
+            timed_out: false,
+        }
+    } else if let Some(signal) = terminating_signal(&output.status) {
+        // Warned about either way, since a crash nobody asked to count is easy to miss otherwise.
+        if script.crash_is_interesting {
+            warn!(
+                "Command was terminated by signal {}, logging as interesting.",
+                signal
+            );
+        } else {
+            warn!(
+                "Command was terminated by signal {}. Adding to avg iteration runtime but not logging as interesting/uninteresting. Set crash_is_interesting to count crashes.",
+                signal
+            );
+        }
+        warn!("Error: {}", String::from_utf8_lossy(&output.stderr));
+        ScriptResult {
+            interesting: script.crash_is_interesting.into(),
+            uninteresting: 0,
+            penalty: 0,
+            runtime_ms: duration.as_millis(),
+            peak_mem_kb,
+            timed_out: false,
+        }
+    } else {
+        eprintln!("Command failed with unknown exit status: {}", output.status);
+        eprintln!("Error: {}", String::from_utf8_lossy(&output.stderr));
+        eprintln!("Unrecognized exit status. Adding to avg iteration runtime but not logging as interesting/uninteresting");
+        ScriptResult {
+            interesting: 0,
+            uninteresting: 0,
+            penalty: 0,
+            runtime_ms: duration.as_millis(),
+            peak_mem_kb,
+            timed_out: false,
+        }
+    };
+
+    Ok((result, output))
+}
+
+fn count_stderr_lines(stderr: &[u8], count_blank: bool) -> u64 {
+    String::from_utf8_lossy(stderr)
+        .lines()
+        .filter(|line| count_blank || !line.trim().is_empty())
+        .count() as u64
+}
+
+/// Prefix of a stdout line reporting how many interesting cases a run found, e.g. `BANDITS_INTERESTING=3`.
+pub const INTERESTING_MARKER: &str = "BANDITS_INTERESTING=";
+
+/// The count from the last `INTERESTING_MARKER` line of stdout, if there is one and it is well formed.
+fn reported_interesting(stdout: &[u8]) -> Option<u64> {
+    let stdout = String::from_utf8_lossy(stdout);
+    let value = stdout
+        .lines()
+        .rev()
+        .find_map(|line| line.trim().strip_prefix(INTERESTING_MARKER))?;
+    match value.trim().parse() {
+        Ok(count) => Some(count),
+        Err(_) => {
+            warn!(
+                "Ignoring malformed {}{}, classifying the run as usual",
+                INTERESTING_MARKER, value
+            );
+            None
+        }
+    }
+}
+
+/// The integer a weighted script ends its output with, read from the last non-blank line of stdout.
+///
+/// The integer must be the whole line or follow whitespace, `:` or `=`, so a version like `v1.2` isn't
+/// read as a weight of 2. A negative weight is ignored with a warning.
+fn trailing_weight(stdout: &[u8]) -> Option<u64> {
+    let stdout = String::from_utf8_lossy(stdout);
+    let line = stdout
+        .lines()
+        .rev()
+        .find(|line| !line.trim().is_empty())?
+        .trim_end();
+    let token = line
+        .rsplit(|c: char| c.is_whitespace() || c == ':' || c == '=')
+        .next()?;
+    if token
+        .strip_prefix('-')
+        .is_some_and(|digits| !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit()))
+    {
+        warn!(
+            "Ignoring negative weight {}, classifying the run as usual",
+            token
+        );
+        return None;
+    }
+    if token.is_empty() || !token.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    token.parse().ok()
+}
+
+/// Fold a run's result into the script's state. `step` is the run's step, recorded as the script's
+/// `first_interesting_step` if this is its first interesting result.
+pub fn update_state(existing_results: &mut Script, result: ScriptResult, step: u64) {
+    if result.interesting > 0 && existing_results.first_interesting_step.is_none() {
+        existing_results.first_interesting_step = Some(step);
+    }
+
+    // Runs without a result, such as timeouts, don't age the existing results.
+    let decay = match existing_results.decay {
+        Some(decay) if result.interesting + result.uninteresting > 0 => decay,
+        _ => 1.0,
+    };
+    // A penalty can take the count down to zero but not below, where the prior alone sets the posterior.
+    let results = ThompsonInfo {
+        interesting: (existing_results.results.interesting * decay + result.interesting as f64
+            - result.penalty as f64)
+            .max(0.0),
+        uninteresting: existing_results.results.uninteresting * decay + result.uninteresting as f64,
+    };
+
+    existing_results
+        .runtime_median
+        .get_or_insert_with(StreamingMedian::default)
+        .record(result.runtime_ms as f64);
+    existing_results
+        .recent_runtimes_ms
+        .push(result.runtime_ms.try_into().unwrap_or(u64::MAX));
+    let excess = existing_results
+        .recent_runtimes_ms
+        .len()
+        .saturating_sub(RECENT_RUNTIMES);
+    existing_results.recent_runtimes_ms.drain(..excess);
+    // Counts saturate rather than wrap, so a run lasting days can't reset a script's history to zero.
+    let total_runtime_ms = existing_results
+        .recorded_runtime_ms()
+        .saturating_add(result.runtime_ms.try_into().unwrap_or(u64::MAX));
+    existing_results.runcount = existing_results.runcount.saturating_add(1);
+    existing_results.set_total_runtime_ms(total_runtime_ms);
+    if let Some(peak_mem_kb) = result.peak_mem_kb {
+        existing_results.avgpeakmem_kb = Some(match existing_results.avgpeakmem_kb {
+            Some(avg) => {
+                (avg * (existing_results.runcount - 1) as f64 + peak_mem_kb as f64)
+                    / existing_results.runcount as f64
+            }
+            None => NotNan::new(peak_mem_kb as f64).unwrap(),
+        });
+    }
+    existing_results.results = results;
+}
+
+pub fn reset_state(config: &mut Config, script_name: Option<String>) -> Result<(), String> {
+    if let Some(script_name) = script_name {
+        if !config
+            .scripts
+            .iter()
+            .any(|script| script.name == script_name)
+        {
+            return Err(format!(
+                "Could not find specified script {script_name} to reset"
+            ));
+        }
+
+        // Reset only the specified script
+        config.scripts = config
+            .scripts
+            .clone()
+            .into_iter()
+            .map(|mut script| {
+                if script.name == script_name {
+                    let results = ThompsonInfo {
+                        interesting: 0.0,
+                        uninteresting: 0.0,
+                    };
+
+                    script.runcount = 0;
+                    script.results = results;
+                    script.avgruntime_ms = None;
+                    script.total_runtime_ms = 0;
+                    script.runtime_median = None;
+                    script.recent_runtimes_ms.clear();
+                    script.avgpeakmem_kb = None;
+                    script.first_interesting_step = None;
+                    script
+                } else {
+                    // Leave untouched
+                    script
+                }
+            })
+            .collect();
+    } else {
+        // Reset all scripts
+        config.scripts = config
+            .scripts
+            .clone()
+            .into_iter()
+            .map(|mut script| {
+                let results = ThompsonInfo {
+                    interesting: 0.0,
+                    uninteresting: 0.0,
+                };
+
+                script.runcount = 0;
+                script.results = results;
+                script.avgruntime_ms = None;
+                script.total_runtime_ms = 0;
+                script.runtime_median = None;
+                script.recent_runtimes_ms.clear();
+                script.avgpeakmem_kb = None;
+                script.first_interesting_step = None;
+                script
+            })
+            .collect();
+    }
+    Ok(())
+}
+
+#[derive(Debug)]
+pub struct ScriptResult {
+    pub interesting: u64,
+    pub uninteresting: u64,
+    /// Earlier interesting cases taken back by a run exiting with one of the script's `penalty_codes`.
+    pub penalty: u64,
+    pub runtime_ms: u128,
+    /// Peak resident set size of the child, where the platform reports it.
+    pub peak_mem_kb: Option<u64>,
+    /// The child was killed for running past its timeout.
+    pub timed_out: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Interesting,
+    Uninteresting,
+    Unrecognized,
+    TimedOut,
+}
+
+impl ScriptResult {
+    pub fn outcome(&self) -> Outcome {
+        if self.timed_out {
+            Outcome::TimedOut
+        } else if self.interesting > 0 {
+            Outcome::Interesting
+        } else if self.uninteresting > 0 {
+            Outcome::Uninteresting
+        } else {
+            Outcome::Unrecognized
+        }
+    }
+}
+
+/// Longest line printed by `--tail-output`, in characters.
+const MAX_TAIL_LINE_CHARS: usize = 200;
+
+/// The last `count` lines of `text`, with overly long lines cut short.
+fn tail_lines(text: &str, count: usize) -> Vec<String> {
+    let lines = text.lines().collect::<Vec<_>>();
+    lines[lines.len().saturating_sub(count)..]
+        .iter()
+        .map(|line| {
+            let length = line.chars().count();
+            if length > MAX_TAIL_LINE_CHARS {
+                let kept = line.chars().take(MAX_TAIL_LINE_CHARS).collect::<String>();
+                format!(
+                    "{kept}... ({} more characters)",
+                    length - MAX_TAIL_LINE_CHARS
+                )
+            } else {
+                line.to_string()
+            }
+        })
+        .collect()
+}
+
+fn print_output_tail(script: &Script, step: usize, output: &Output, count: usize) {
+    println!(
+        "Step {}: {} was interesting. Last {} lines of output:",
+        step, script.name, count
+    );
+    for (label, stream) in [("stdout", &output.stdout), ("stderr", &output.stderr)] {
+        let lines = tail_lines(&String::from_utf8_lossy(stream), count);
+        if lines.is_empty() {
+            continue;
+        }
+        println!("  {label}:");
+        for line in lines {
+            println!("    {line}");
+        }
+    }
+}
+
+/// How many lines of each stream `capture_output` keeps.
+pub const CAPTURE_LINES: usize = 50;
+
+/// Save the last `CAPTURE_LINES` lines of a run's stdout and stderr to `{script}-step-{step}.log` in `dir`,
+/// creating it if needed. Characters other than letters, digits, `-` and `_` in the name become `_`.
+pub fn capture_output(
+    dir: &Path,
+    script: &Script,
+    step: usize,
+    output: &Output,
+) -> io::Result<PathBuf> {
+    std::fs::create_dir_all(dir)?;
+    let name = script
+        .name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+    let path = dir.join(format!("{name}-step-{step}.log"));
+
+    let mut contents = String::new();
+    for (label, stream) in [("stdout", &output.stdout), ("stderr", &output.stderr)] {
+        contents.push_str(&format!("{label}:\n"));
+        for line in tail_lines(&String::from_utf8_lossy(stream), CAPTURE_LINES) {
+            contents.push_str(&line);
+            contents.push('\n');
+        }
+    }
+    std::fs::write(&path, contents)?;
+    Ok(path)
+}
+
+/// Print how every candidate scored in the selection `state` last made, which picked `selected`.
+pub fn explain_selection(config: &Config, state: &SelectionState, selected: usize) {
+    println!("Selection {}:", state.selections);
+    if state.last_scores.is_empty() {
+        println!(
+            "* {} was chosen without sampling scores, by warmup, interleaving or the strategy.",
+            config.scripts[selected].name
+        );
+    } else {
+        insights::print_score_breakdowns(&config.scripts, &state.last_scores, selected);
+    }
+}
+
+/// Pick up to `jobs` scripts, run them concurrently and record their results.
+///
+/// Every script in a batch is sampled from the same posterior, before any of the batch's results are in,
+/// and a script may be picked more than once. Limits are checked when picking, so a batch can overshoot
+/// a limit by up to `jobs - 1` interesting cases. Returns how many scripts were picked, 0 if none can run.
+pub fn step(
+    config: &mut Config,
+    options: &SelectionOptions,
+    state: &mut SelectionState,
+    execution: &ExecutionOptions,
+    jobs: usize,
+) -> usize {
+    step_with(
+        config,
+        options,
+        state,
+        execution,
+        jobs,
+        &|_, script, timeout_ms| run_script_with_output(script, timeout_ms),
+    )
+}
+
+/// Runs the script at an index for `step_with`, given the timeout that overrides the script's own.
+pub type Runner<'a> =
+    dyn Fn(usize, &Script, Option<u64>) -> io::Result<(ScriptResult, Output)> + Sync + 'a;
+
+/// `step`, with each picked script run by `runner` instead of executed, e.g. to simulate it.
+pub fn step_with(
+    config: &mut Config,
+    options: &SelectionOptions,
+    state: &mut SelectionState,
+    execution: &ExecutionOptions,
+    jobs: usize,
+    runner: &Runner,
+) -> usize {
+    if config.scripts.is_empty() {
+        debug!("ERROR: No scripts to execute. Exiting...");
+        return 0;
+    }
+
+    let now_minute = local_minute_of_day();
+    let mut picks = vec![];
+    while picks.len() < jobs {
+        let Some(script_index) = choose_script(config, options, state, now_minute) else {
+            break;
+        };
+        state.last_selected = Some(script_index);
+        picks.push(script_index);
+        if execution.explain {
+            explain_selection(config, state, script_index);
+        }
+    }
+
+    debug!("Running scripts {:?}...", picks);
+
+    let scripts = &config.scripts;
+    let outcomes = if let [script_index] = picks[..] {
+        vec![runner(
+            script_index,
+            &scripts[script_index],
+            execution.timeout_ms,
+        )]
+    } else {
+        thread::scope(|scope| {
+            let handles = picks
+                .iter()
+                .map(|&script_index| {
+                    scope.spawn(move || {
+                        runner(script_index, &scripts[script_index], execution.timeout_ms)
+                    })
+                })
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .collect::<Vec<_>>()
+        })
+    };
+
+    // Results are folded in after every run has finished, in the order the scripts were picked.
+    for (&script_index, outcome) in picks.iter().zip(outcomes) {
+        state.steps_taken += 1;
+
+        let (result, output) = match outcome {
+            Ok(ran) => ran,
+            Err(err) => {
+                eprintln!(
+                    "Could not run {}: {}. Skipping it for the rest of this run.",
+                    config.scripts[script_index].name, err
+                );
+                if !state.failed.contains(&script_index) {
+                    state.failed.push(script_index);
+                }
+                continue;
+            }
+        };
+
+        debug!("Script {} finished. Result: {:?}", script_index, result);
+        state.interesting_found += result.interesting;
+
+        if let Some(count) = execution.tail_output {
+            if result.interesting > 0 {
+                print_output_tail(
+                    &config.scripts[script_index],
+                    state.steps_taken,
+                    &output,
+                    count,
+                );
+            }
+        }
+
+        if let Some(dir) = &execution.capture_dir {
+            if result.interesting > 0 {
+                let script = &config.scripts[script_index];
+                if let Err(err) = capture_output(dir, script, state.steps_taken, &output) {
+                    eprintln!(
+                        "Warning: could not capture the output of {} at step {}: {}",
+                        script.name, state.steps_taken, err
+                    );
+                }
+            }
+        }
+
+        let mut record = HistoryRecord {
+            step: state.steps_taken,
+            script_index,
+            script_name: config.scripts[script_index].name.clone(),
+            interesting: result.interesting,
+            uninteresting: result.uninteresting,
+            runtime_ms: result.runtime_ms,
+            timestamp: now_ms(),
+            regret: 0.0,
+        };
+
+        update_state(
+            config.scripts.get_mut(script_index).unwrap(),
+            result,
+            state.steps_taken as u64,
+        );
+        state.regret.record(&config.scripts[script_index]);
+
+        if let Some(history) = &execution.history {
+            record.regret = state.regret.cumulative(&config.scripts);
+            if let Err(err) = append_history(history, &record) {
+                eprintln!(
+                    "Warning: could not append step {} to history log: {}",
+                    record.step, err
+                );
+            }
+        }
+    }
+
+    picks.len()
+}
+
+/// Outcome of `simulate`.
+#[derive(Debug)]
+pub struct Simulation {
+    /// Steps each script was selected for, by script index.
+    pub runs: Vec<u64>,
+    /// Expected interesting cases missed against always running the script with the highest truth.
+    pub regret: f64,
+    /// Steps simulated, fewer than asked for if every script reached its limit.
+    pub steps: usize,
+}
+
+/// Run up to `steps` steps of selection against a synthetic environment, where script `i` is interesting
+/// with probability `truths[i]`, without running any command.
+///
+/// Each step goes through `step_with` like a real run, with a runner that draws the result instead of
+/// running the script. A simulated run takes the script's average runtime, or the reference runtime if it
+/// has none, so runtime only biases selection through the runtimes already in the config.
+pub fn simulate(
+    config: &mut Config,
+    options: &SelectionOptions,
+    state: &mut SelectionState,
+    truths: &[f64],
+    steps: usize,
+    rng: &mut impl Rng,
+) -> Simulation {
+    let rng = Mutex::new(StdRng::from_rng(rng).unwrap());
+    let runs = Mutex::new(vec![0; config.scripts.len()]);
+    let runner = |index: usize, script: &Script, _timeout_ms| {
+        let interesting = rng.lock().unwrap().gen_bool(truths[index]);
+        runs.lock().unwrap()[index] += 1;
+        let runtime_ms = script
+            .avgruntime_ms
+            .unwrap_or(options.runtime_reference_ms)
+            .max(NotNan::new(1.0).unwrap());
+        let result = ScriptResult {
+            interesting: interesting as u64,
+            uninteresting: !interesting as u64,
+            penalty: 0,
+            runtime_ms: runtime_ms.round() as u128,
+            peak_mem_kb: None,
+            timed_out: false,
+        };
+        let output = Output {
+            status: ExitStatus::default(),
+            stdout: vec![],
+            stderr: vec![],
+        };
+        Ok((result, output))
+    };
+
+    let execution = ExecutionOptions::default();
+    let mut simulated = 0;
+    while simulated < steps && step_with(config, options, state, &execution, 1, &runner) > 0 {
+        simulated += 1;
+    }
+
+    let runs = runs.into_inner().unwrap();
+    let best_truth = truths.iter().copied().fold(0.0, f64::max);
+    let regret = runs
+        .iter()
+        .zip(truths)
+        .map(|(runs, truth)| *runs as f64 * (best_truth - truth))
+        .sum();
+    Simulation {
+        runs,
+        regret,
+        steps: simulated,
+    }
+}
+
+/// The JSON for a script named `name` that has found `interesting` of its `interesting + uninteresting`
+/// runs interesting, with no runtime recorded yet. Tests set any other fields on the returned value.
+#[cfg(test)]
+fn test_script(name: &str, interesting: u64, uninteresting: u64) -> serde_json::Value {
+    serde_json::json!({
+        "name": name,
+        "command": "ls",
+        "results": { "interesting": interesting, "uninteresting": uninteresting },
+        "runcount": interesting + uninteresting,
+        "avgruntime_ms": null,
+        "bias": 1.0
+    })
+}
+
+#[test]
+fn test_round_robin_skips_exhausted_scripts() {
+    let script = |name: &str, interesting: u64| {
+        let mut script = test_script(name, interesting, 0);
+        script["limit"] = 2.into();
+        script
+    };
+    let config: Config = serde_json::from_value(serde_json::json!({
+        "scripts": [script("a", 0), script("done", 2), script("b", 1), script("c", 0)]
+    }))
+    .unwrap();
+    let options = SelectionOptions {
+        strategy: Strategy::RoundRobin,
+        ..SelectionOptions::default()
+    };
+    let mut state = SelectionState::new(None);
+
+    let picks = (0..6)
+        .map(|_| choose_script(&config, &options, &mut state, 0).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(picks, vec![0, 2, 3, 0, 2, 3]);
+}
+
+#[test]
+fn test_round_robin_keeps_turns_when_scripts_leave() {
+    let script = |name: &str| {
+        let mut script = test_script(name, 0, 0);
+        script["limit"] = 1.into();
+        script
+    };
+    let mut config: Config = serde_json::from_value(serde_json::json!({
+        "scripts": [script("a"), script("b"), script("c")]
+    }))
+    .unwrap();
+    let options = SelectionOptions {
+        strategy: Strategy::RoundRobin,
+        ..SelectionOptions::default()
+    };
+    let mut state = SelectionState::new(None);
+
+    assert_eq!(choose_script(&config, &options, &mut state, 0), Some(0));
+    assert_eq!(choose_script(&config, &options, &mut state, 0), Some(1));
+    // "b" reaches its limit mid-cycle; it's still "c"'s turn.
+    config.scripts[1].results.interesting = 1.0;
+    assert_eq!(choose_script(&config, &options, &mut state, 0), Some(2));
+    assert_eq!(choose_script(&config, &options, &mut state, 0), Some(0));
+    // Reordering the scripts doesn't repeat "a": "c" comes next.
+    config.scripts.swap(0, 2);
+    assert_eq!(choose_script(&config, &options, &mut state, 0), Some(0));
+}
+
+#[test]
+fn test_cooldown_prevents_back_to_back_selections() {
+    let script = |name: &str, interesting: u64| test_script(name, interesting, 100 - interesting);
+    // "good" would win nearly every sample without the cooldown.
+    let config: Config = serde_json::from_value(serde_json::json!({
+        "scripts": [script("good", 90), script("bad", 1)]
+    }))
+    .unwrap();
+    let options = SelectionOptions {
+        cooldown: 1,
+        ..SelectionOptions::default()
+    };
+    let mut state = SelectionState::new(Some(1));
+
+    let picks = (0..20)
+        .map(|_| choose_script(&config, &options, &mut state, 0).unwrap())
+        .collect::<Vec<_>>();
+    assert!(picks.windows(2).all(|pair| pair[0] != pair[1]), "{picks:?}");
+
+    // With a single script the cooldown can't be honoured, so it is ignored.
+    let config: Config = serde_json::from_value(serde_json::json!({
+        "scripts": [script("good", 90)]
+    }))
+    .unwrap();
+    assert_eq!(choose_script(&config, &options, &mut state, 0), Some(0));
+    assert_eq!(choose_script(&config, &options, &mut state, 0), Some(0));
+}
+
+#[test]
+fn test_average_runtime_is_exact() {
+    let mut config: Config = serde_json::from_str(
+        r#"{
+            "scripts": [
+                {
+                    "name": "Test 1",
+                    "command": "ls",
+                    "results": { "interesting": 0, "uninteresting": 0 },
+                    "runcount": 0,
+                    "avgruntime_ms": null,
+                    "bias": 1.0
+                }
+            ]
+        }"#,
+    )
+    .unwrap();
+
+    for index in 0..100_000 {
+        let result = ScriptResult {
+            interesting: 0,
+            uninteresting: 1,
+            penalty: 0,
+            runtime_ms: index % 1000,
+            peak_mem_kb: None,
+            timed_out: false,
+        };
+        update_state(&mut config.scripts[0], result, index as u64 + 1);
+    }
+    assert_eq!(config.scripts[0].total_runtime_ms, 49_950_000);
+    assert_eq!(
+        config.scripts[0].avgruntime_ms,
+        Some(NotNan::new(499.5).unwrap())
+    );
+
+    // A config saved before the sum was stored picks it up from the average.
+    config.scripts[0].total_runtime_ms = 0;
+    assert_eq!(config.scripts[0].recorded_runtime_ms(), 49_950_000);
+}
+
+#[test]
+fn test_run_limit_caps_runs() {
+    let mut config: Config = serde_json::from_str(
+        r#"{
+            "scripts": [
+                {
+                    "name": "capped",
+                    "command": "ls",
+                    "results": { "interesting": 0, "uninteresting": 0 },
+                    "runcount": 0,
+                    "avgruntime_ms": null,
+                    "bias": 1.0,
+                    "run_limit": 3
+                },
+                {
+                    "name": "open",
+                    "command": "ls",
+                    "results": { "interesting": 0, "uninteresting": 0 },
+                    "runcount": 0,
+                    "avgruntime_ms": null,
+                    "bias": 1.0
+                }
+            ]
+        }"#,
+    )
+    .unwrap();
+    let options = SelectionOptions {
+        strategy: Strategy::Uniform,
+        ..SelectionOptions::default()
+    };
+    let mut state = SelectionState::new(Some(3));
+
+    // Uninteresting runs never reach an interesting-count limit, but the run limit still applies.
+    for step in 1..=50 {
+        let index = choose_script(&config, &options, &mut state, 0).unwrap();
+        let result = ScriptResult {
+            interesting: 0,
+            uninteresting: 1,
+            penalty: 0,
+            runtime_ms: 1,
+            peak_mem_kb: None,
+            timed_out: false,
+        };
+        update_state(&mut config.scripts[index], result, step);
+    }
+    assert_eq!(config.scripts[0].runcount, 3);
+    assert_eq!(config.scripts[1].runcount, 47);
+}
+
+#[test]
+fn test_runtime_metric_on_bimodal_runtimes() {
+    let script = |name: &str| {
+        let mut script = test_script(name, 50, 50);
+        script["runcount"] = 0.into();
+        script
+    };
+    let mut config: Config = serde_json::from_value(serde_json::json!({
+        "scripts": [script("bimodal"), script("steady")]
+    }))
+    .unwrap();
+    let result = |runtime_ms| ScriptResult {
+        interesting: 0,
+        uninteresting: 0,
+        penalty: 0,
+        runtime_ms,
+        peak_mem_kb: None,
+        timed_out: false,
+    };
+    // Usually 10ms with an occasional 1s run: a mean of 109ms but a median of 10ms, against a steady 40ms.
+    for index in 0..200 {
+        let runtime_ms = if index % 10 == 9 { 1000 } else { 10 };
+        update_state(&mut config.scripts[0], result(runtime_ms), index + 1);
+        update_state(&mut config.scripts[1], result(40), index + 1);
+    }
+
+    let options = SelectionOptions::default();
+    let bimodal_picks = |config: &Config| {
+        let mut state = SelectionState::new(Some(5));
+        (0..200)
+            .filter(|_| choose_script(config, &options, &mut state, 0) == Some(0))
+            .count()
+    };
+    let by_mean = bimodal_picks(&config);
+    config.runtime_metric = config::RuntimeMetric::Median;
+    let by_median = bimodal_picks(&config);
+
+    assert!(by_mean < 20, "bimodal picked {by_mean} times by mean");
+    assert!(
+        by_median > 180,
+        "bimodal picked {by_median} times by median"
+    );
+}
+
+#[test]
+fn test_runtime_percentile_penalizes_heavy_tail() {
+    let script = |name: &str| {
+        let mut script = test_script(name, 50, 50);
+        script["runcount"] = 0.into();
+        script
+    };
+    let mut config: Config = serde_json::from_value(serde_json::json!({
+        "scripts": [script("tail"), script("steady")]
+    }))
+    .unwrap();
+    let result = |runtime_ms| ScriptResult {
+        interesting: 0,
+        uninteresting: 0,
+        penalty: 0,
+        runtime_ms,
+        peak_mem_kb: None,
+        timed_out: false,
+    };
+    // Usually 10ms with a 1s run one time in five: a mean of 208ms but a 90th percentile of 1s, against a
+    // steady 400ms.
+    for index in 0..200 {
+        let runtime_ms = if index % 5 == 4 { 1000 } else { 10 };
+        update_state(&mut config.scripts[0], result(runtime_ms), index + 1);
+        update_state(&mut config.scripts[1], result(400), index + 1);
+    }
+    assert_eq!(config.scripts[0].recent_runtimes_ms.len(), RECENT_RUNTIMES);
+
+    let options = SelectionOptions::default();
+    let tail_picks = |config: &Config| {
+        let mut state = SelectionState::new(Some(5));
+        (0..200)
+            .filter(|_| choose_script(config, &options, &mut state, 0) == Some(0))
+            .count()
+    };
+    let by_mean = tail_picks(&config);
+    config.runtime_metric = config::RuntimeMetric::Percentile(90.);
+    let by_p90 = tail_picks(&config);
+
+    assert!(by_mean > 120, "tail picked {by_mean} times by mean");
+    assert!(by_p90 < 80, "tail picked {by_p90} times by p90");
+}
+
+#[test]
+fn test_cost_deprioritizes_fast_expensive_script() {
+    let script = |name: &str, avgruntime_ms: f64, cost_per_ms: Option<f64>| {
+        let mut script = test_script(name, 50, 50);
+        script["avgruntime_ms"] = avgruntime_ms.into();
+        script["cost_per_ms"] = cost_per_ms.into();
+        script
+    };
+    // Twice as fast, but ten times the price per millisecond: five times the cost of a run.
+    let config: Config = serde_json::from_value(serde_json::json!({
+        "scripts": [script("expensive", 10.0, Some(10.0)), script("cheap", 20.0, None)]
+    }))
+    .unwrap();
+    assert_eq!(
+        config.scripts[0].runtime_cost(config.runtime_metric),
+        Some(NotNan::new(100.0).unwrap())
+    );
+
+    let options = SelectionOptions::default();
+    let mut state = SelectionState::new(Some(5));
+    let expensive_picks = (0..200)
+        .filter(|_| choose_script(&config, &options, &mut state, 0) == Some(0))
+        .count();
+    assert!(
+        expensive_picks < 20,
+        "expensive picked {expensive_picks} times"
+    );
+}
+
+#[test]
+fn test_dependency_gates_selection() {
+    let mut config: Config = serde_json::from_value(serde_json::json!({
+        "scripts": [
+            {
+                "name": "producer",
+                "command": "ls",
+                "results": { "interesting": 0, "uninteresting": 0 },
+                "runcount": 0,
+                "avgruntime_ms": null,
+                "bias": 1.0
+            },
+            {
+                "name": "consumer",
+                "command": "ls",
+                "results": { "interesting": 0, "uninteresting": 0 },
+                "runcount": 0,
+                "avgruntime_ms": null,
+                "bias": 1000.0,
+                "depends_on": ["producer"]
+            }
+        ]
+    }))
+    .unwrap();
+    let options = SelectionOptions::default();
+    let mut state = SelectionState::new(Some(2));
+    let result = |interesting| ScriptResult {
+        interesting,
+        uninteresting: 1 - interesting,
+        penalty: 0,
+        runtime_ms: 10,
+        peak_mem_kb: None,
+        timed_out: false,
+    };
+
+    // Until the producer finds something, the consumer is never a candidate despite its bias.
+    for step in 1..=5 {
+        assert_eq!(choose_script(&config, &options, &mut state, 0), Some(0));
+        update_state(&mut config.scripts[0], result(0), step);
+    }
+    update_state(&mut config.scripts[0], result(1), 6);
+    assert_eq!(choose_script(&config, &options, &mut state, 0), Some(1));
+}
+
+#[test]
+fn test_strong_prior_wins_early() {
+    let script = |name: &str, prior_interesting: f64| {
+        let mut script = test_script(name, 0, 0);
+        script["prior_interesting"] = prior_interesting.into();
+        script
+    };
+    let config: Config = serde_json::from_value(serde_json::json!({
+        "scripts": [script("unknown", 1.0), script("productive", 20.0)]
+    }))
+    .unwrap();
+    let options = SelectionOptions {
+        ignore_runtime: true,
+        ..SelectionOptions::default()
+    };
+    let mut state = SelectionState::new(Some(4));
+
+    // A Beta(20, 1) sample beats a uniform Beta(1, 1) one 20 times in 21, before either has run.
+    let productive_picks = (0..100)
+        .filter(|_| choose_script(&config, &options, &mut state, 0) == Some(1))
+        .count();
+    assert!(
+        productive_picks > 75,
+        "productive picked {productive_picks} times"
+    );
+}
+
+#[test]
+fn test_simulation_converges_on_best_truth() {
+    let script = |name: &str| test_script(name, 0, 0);
+    let mut config: Config = serde_json::from_value(serde_json::json!({
+        "scripts": [script("rare"), script("common"), script("sometimes")]
+    }))
+    .unwrap();
+    let options = SelectionOptions::default();
+    let mut state = SelectionState::new(Some(6));
+    let truths = [0.05, 0.5, 0.2];
+
+    let early = simulate(
+        &mut config,
+        &options,
+        &mut state,
+        &truths,
+        100,
+        &mut seeded_rng(Some(6)),
+    );
+    let late = simulate(
+        &mut config,
+        &options,
+        &mut state,
+        &truths,
+        500,
+        &mut seeded_rng(Some(7)),
+    );
+
+    assert_eq!(late.steps, 500);
+    assert!(late.runs[1] > 450, "late runs {:?}", late.runs);
+    // Regret per step shrinks as selection settles on the best script.
+    assert!(late.regret / 500. < early.regret / 100.);
+}
+
+#[test]
+fn test_penalties_reduce_selection_chance() {
+    use thompson::{selection_probabilities, thompson_sampling};
+
+    let script = |name: &str| test_script(name, 5, 5);
+    let mut config: Config = serde_json::from_value(serde_json::json!({
+        "scripts": [script("penalized"), script("untouched")]
+    }))
+    .unwrap();
+    for step in 1..=3 {
+        let penalty = ScriptResult {
+            interesting: 0,
+            uninteresting: 1,
+            penalty: 1,
+            runtime_ms: 10,
+            peak_mem_kb: None,
+            timed_out: false,
+        };
+        update_state(&mut config.scripts[0], penalty, step);
+    }
+    assert_eq!(config.scripts[0].results.interesting, 2.0);
+    assert_eq!(config.scripts[0].results.uninteresting, 8.0);
+
+    let entries = [&config.scripts[0].results, &config.scripts[1].results];
+    let prior = Prior::default();
+    let bias = NotNan::new(1.0).unwrap();
+    let mut rng = StdRng::seed_from_u64(5);
+    let probabilities = selection_probabilities(2, 10_000, || {
+        thompson_sampling(&entries, &[&prior, &prior], &[&bias, &bias], &mut rng)
+    });
+    assert!(probabilities[0] < 0.2, "{probabilities:?}");
+}
+
+#[test]
+fn test_watched_bias_change_is_picked_up() {
+    use config::{carry_over_results, save_config, try_parse_config, ConfigWatcher};
+
+    let script = |name: &str, interesting: u64| test_script(name, interesting, 20 - interesting);
+    let mut config: Config = serde_json::from_value(serde_json::json!({
+        "scripts": [script("strong", 18), script("weak", 2)]
+    }))
+    .unwrap();
+    let path = std::env::temp_dir().join(format!("bandits-watch-{}.json", std::process::id()));
+    save_config(&config, &path);
+    let mut watcher = ConfigWatcher::new(&path).unwrap();
+    assert!(!watcher.changed());
+
+    let options = SelectionOptions {
+        ignore_runtime: true,
+        ..SelectionOptions::default()
+    };
+    let mut state = SelectionState::new(Some(4));
+    let index = choose_script(&config, &options, &mut state, 0).unwrap();
+    update_state(
+        &mut config.scripts[index],
+        ScriptResult {
+            interesting: 1,
+            uninteresting: 0,
+            penalty: 0,
+            runtime_ms: 10,
+            peak_mem_kb: None,
+            timed_out: false,
+        },
+        1,
+    );
+    assert_eq!(index, 0);
+
+    // Saving the same config again, as a checkpoint does, isn't an edit.
+    let unchanged = try_parse_config(&path).unwrap();
+    save_config(&unchanged, &path);
+    thread::sleep(Duration::from_millis(200));
+    assert!(!watcher.changed());
+
+    // Edit the bias in the file within the same second, and wait for the file event to arrive.
+    let mut edited = unchanged;
+    edited.scripts[0].bias = NotNan::new(0.0).unwrap();
+    save_config(&edited, &path);
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !watcher.changed() {
+        assert!(Instant::now() < deadline, "no event for the edit");
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    let mut fresh = try_parse_config(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    carry_over_results(&config, &mut fresh);
+    config = fresh;
+    state.scripts_changed();
+
+    assert_eq!(config.scripts[0].bias, 0.0);
+    assert_eq!(config.scripts[0].runcount, 21);
+    assert_eq!(config.scripts[0].results.interesting, 19.0);
+    for _ in 0..20 {
+        assert_eq!(choose_script(&config, &options, &mut state, 0), Some(1));
+    }
+}
+
+#[test]
+fn test_first_interesting_step_set_once() {
+    let mut config: Config = serde_json::from_str(
+        r#"{
+            "scripts": [
+                {
+                    "name": "Test 1",
+                    "command": "ls",
+                    "results": { "interesting": 0, "uninteresting": 0 },
+                    "runcount": 0,
+                    "avgruntime_ms": null,
+                    "bias": 1.0
+                }
+            ]
+        }"#,
+    )
+    .unwrap();
+    let result = |interesting| ScriptResult {
+        interesting,
+        uninteresting: 1 - interesting,
+        penalty: 0,
+        runtime_ms: 10,
+        peak_mem_kb: None,
+        timed_out: false,
+    };
+
+    update_state(&mut config.scripts[0], result(0), 1);
+    update_state(&mut config.scripts[0], result(0), 2);
+    assert_eq!(config.scripts[0].first_interesting_step, None);
+
+    update_state(&mut config.scripts[0], result(1), 3);
+    assert_eq!(config.scripts[0].first_interesting_step, Some(3));
+
+    update_state(&mut config.scripts[0], result(1), 4);
+    update_state(&mut config.scripts[0], result(0), 5);
+    assert_eq!(config.scripts[0].first_interesting_step, Some(3));
+
+    reset_state(&mut config, None).unwrap();
+    assert_eq!(config.scripts[0].first_interesting_step, None);
+}
+
+#[test]
+fn test_update_state_saturates_counts() {
+    let mut config: Config = serde_json::from_str(
+        r#"{
+            "scripts": [
+                {
+                    "name": "Test 1",
+                    "command": "ls",
+                    "results": { "interesting": 0, "uninteresting": 0 },
+                    "runcount": 18446744073709551615,
+                    "avgruntime_ms": 1.0,
+                    "total_runtime_ms": 18446744073709551615,
+                    "bias": 1.0
+                }
+            ]
+        }"#,
+    )
+    .unwrap();
+    let result = ScriptResult {
+        interesting: 1,
+        uninteresting: 0,
+        penalty: 0,
+        runtime_ms: 10,
+        peak_mem_kb: None,
+        timed_out: false,
+    };
+
+    update_state(&mut config.scripts[0], result, 1);
+    assert_eq!(config.scripts[0].runcount, u64::MAX);
+    assert_eq!(config.scripts[0].total_runtime_ms, u64::MAX);
+    assert_eq!(config.scripts[0].results.interesting, 1.0);
+}
+
+#[test]
+fn test_count_stderr_lines() {
+    let stderr = b"warning: one\n\n  \nwarning: two\nwarning: three";
+    assert_eq!(count_stderr_lines(stderr, false), 3);
+    assert_eq!(count_stderr_lines(stderr, true), 5);
+    assert_eq!(count_stderr_lines(b"", true), 0);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_stderr_line_threshold() {
+    let script = |lines: usize| -> Script {
+        let mut script = test_script("warnings", 0, 0);
+        script["command"] = format!("yes warning | head -n {lines} >&2").into();
+        script["shell"] = true.into();
+        script["stderr_line_threshold"] = 3.into();
+        serde_json::from_value(script).unwrap()
+    };
+
+    // Interesting only once the count exceeds the threshold.
+    let result = run_script(&script(2)).unwrap();
+    assert_eq!((result.interesting, result.uninteresting), (0, 1));
+    let result = run_script(&script(3)).unwrap();
+    assert_eq!((result.interesting, result.uninteresting), (0, 1));
+    let result = run_script(&script(4)).unwrap();
+    assert_eq!((result.interesting, result.uninteresting), (1, 0));
+}
+
+#[test]
+fn test_reported_interesting() {
+    assert_eq!(reported_interesting(b"BANDITS_INTERESTING=3\n"), Some(3));
+    // The last marker wins, wherever it is in the output.
+    assert_eq!(
+        reported_interesting(b"BANDITS_INTERESTING=1\n  BANDITS_INTERESTING=4 \ndone\n"),
+        Some(4)
+    );
+    assert_eq!(reported_interesting(b"BANDITS_INTERESTING=0"), Some(0));
+    assert_eq!(reported_interesting(b"found 3 crashes\n"), None);
+    assert_eq!(reported_interesting(b""), None);
+}
+
+#[test]
+fn test_reported_interesting_malformed() {
+    assert_eq!(reported_interesting(b"BANDITS_INTERESTING=three\n"), None);
+    assert_eq!(reported_interesting(b"BANDITS_INTERESTING=-1\n"), None);
+    assert_eq!(reported_interesting(b"BANDITS_INTERESTING=\n"), None);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_run_script_reports_interesting_count() {
+    let script = |command: &str| -> Script {
+        serde_json::from_value(serde_json::json!({
+            "name": "fuzzer",
+            "command": command,
+            "shell": true,
+            "results": { "interesting": 0, "uninteresting": 0 },
+            "runcount": 0,
+            "avgruntime_ms": null,
+            "bias": 1.0
+        }))
+        .unwrap()
+    };
+
+    // The marker overrides the exit code.
+    let result = run_script(&script("echo BANDITS_INTERESTING=3")).unwrap();
+    assert_eq!((result.interesting, result.uninteresting), (3, 0));
+
+    // Without a well formed marker the exit code decides.
+    let result = run_script(&script("echo BANDITS_INTERESTING=lots; exit 1")).unwrap();
+    assert_eq!((result.interesting, result.uninteresting), (1, 0));
+    let result = run_script(&script("echo no marker")).unwrap();
+    assert_eq!((result.interesting, result.uninteresting), (0, 1));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_run_script_retries_transient_failures() {
+    let marker = std::env::temp_dir().join(format!("bandits-retry-{}", std::process::id()));
+    let _ = std::fs::remove_file(&marker);
+    // Fails with 75 on the first attempt only, then finds something.
+    let command = format!(
+        "if [ -e {0} ]; then exit 1; else touch {0}; exit 75; fi",
+        marker.display()
+    );
+    let mut script: Script = serde_json::from_value(serde_json::json!({
+        "name": "server",
+        "command": command,
+        "shell": true,
+        "results": { "interesting": 0, "uninteresting": 0 },
+        "runcount": 0,
+        "avgruntime_ms": null,
+        "bias": 1.0,
+        "retries": 2,
+        "retry_on_codes": [75]
+    }))
+    .unwrap();
+
+    let result = run_script(&script).unwrap();
+    std::fs::remove_file(&marker).unwrap();
+    assert_eq!((result.interesting, result.uninteresting), (1, 0));
+
+    // Both attempts make up a single run.
+    update_state(&mut script, result, 1);
+    assert_eq!(script.runcount, 1);
+    assert_eq!(script.results.interesting, 1.0);
+    assert_eq!(script.results.uninteresting, 0.0);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_interesting_regex() {
+    let script = |command: &str, regex: &str| -> Script {
+        serde_json::from_value(serde_json::json!({
+            "name": "grep",
+            "command": command,
+            "shell": true,
+            "results": { "interesting": 0, "uninteresting": 0 },
+            "runcount": 0,
+            "avgruntime_ms": null,
+            "bias": 1.0,
+            "interesting_regex": regex
+        }))
+        .unwrap()
+    };
+
+    // A match on stdout or stderr is interesting even though the command exits 0.
+    let result = run_script(&script("echo 'src/lib.rs:3: TODO fix'", r"TODO\b")).unwrap();
+    assert_eq!((result.interesting, result.uninteresting), (1, 0));
+    let result = run_script(&script("echo 'panicked at' >&2", "panicked")).unwrap();
+    assert_eq!((result.interesting, result.uninteresting), (1, 0));
+
+    // Without a match the exit code decides.
+    let result = run_script(&script("echo all clear", r"TODO\b")).unwrap();
+    assert_eq!((result.interesting, result.uninteresting), (0, 1));
+    let result = run_script(&script("echo all clear; exit 1", r"TODO\b")).unwrap();
+    assert_eq!((result.interesting, result.uninteresting), (1, 0));
+
+    let err = run_script(&script("echo TODO", "TODO(")).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn test_trailing_weight() {
+    assert_eq!(trailing_weight(b"5\n"), Some(5));
+    assert_eq!(trailing_weight(b"checking...\nseverity: 7\n\n"), Some(7));
+    assert_eq!(trailing_weight(b"0"), Some(0));
+    assert_eq!(trailing_weight(b"7 findings\n"), None);
+    assert_eq!(trailing_weight(b""), None);
+    assert_eq!(trailing_weight(b"severity=4"), Some(4));
+    assert_eq!(trailing_weight(b"exit -3\n"), None);
+    assert_eq!(trailing_weight(b"built v1.2\n"), None);
+    assert_eq!(trailing_weight(b"found12\n"), None);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_weighted_script_adds_weight() {
+    let mut config: Config = serde_json::from_str(
+        r#"{
+            "scripts": [
+                {
+                    "name": "severity",
+                    "command": "echo 5",
+                    "results": { "interesting": 2, "uninteresting": 3 },
+                    "runcount": 5,
+                    "avgruntime_ms": 10.0,
+                    "bias": 1.0,
+                    "weighted": true
+                }
+            ]
+        }"#,
+    )
+    .unwrap();
+
+    let result = run_script(&config.scripts[0]).unwrap();
+    assert_eq!(result.interesting, 5);
+    assert_eq!(result.uninteresting, 0);
+
+    update_state(&mut config.scripts[0], result, 1);
+    assert_eq!(config.scripts[0].results.interesting, 7.0);
+    assert_eq!(config.scripts[0].results.uninteresting, 3.0);
+    assert_eq!(config.scripts[0].runcount, 6);
+
+    // Without `weighted` the same script is classified by its exit code.
+    config.scripts[0].weighted = false;
+    let result = run_script(&config.scripts[0]).unwrap();
+    assert_eq!((result.interesting, result.uninteresting), (0, 1));
+}
+
+#[test]
+fn test_tail_lines() {
+    assert_eq!(tail_lines("one\ntwo\nthree\n", 2), vec!["two", "three"]);
+    assert_eq!(tail_lines("one\n", 5), vec!["one"]);
+    assert!(tail_lines("", 5).is_empty());
+
+    let long = "x".repeat(MAX_TAIL_LINE_CHARS + 5);
+    assert_eq!(
+        tail_lines(&long, 1),
+        vec![format!(
+            "{}... (5 more characters)",
+            "x".repeat(MAX_TAIL_LINE_CHARS)
+        )]
+    );
+}
+
+#[test]
+fn test_effective_bias_floor() {
+    let floor = Some(0.1);
+    assert_eq!(
+        effective_bias(NotNan::new(0.0001).unwrap(), floor),
+        NotNan::new(0.1).unwrap()
+    );
+    assert_eq!(
+        effective_bias(NotNan::new(0.0).unwrap(), floor),
+        NotNan::new(0.0).unwrap()
+    );
+    assert_eq!(
+        effective_bias(NotNan::new(2.0).unwrap(), floor),
+        NotNan::new(2.0).unwrap()
+    );
+    assert_eq!(
+        effective_bias(NotNan::new(0.0001).unwrap(), None),
+        NotNan::new(0.0001).unwrap()
+    );
+}
+
+#[test]
+fn test_choose_script_respects_schedule() {
+    let config: Config = serde_json::from_str(
+        r#"{
+            "scripts": [
+                {
+                    "name": "day",
+                    "command": "ls",
+                    "results": { "interesting": 0, "uninteresting": 0 },
+                    "runcount": 0,
+                    "avgruntime_ms": null,
+                    "bias": 1.0,
+                    "schedule": "09:00-17:00"
+                },
+                {
+                    "name": "night",
+                    "command": "ls",
+                    "results": { "interesting": 0, "uninteresting": 0 },
+                    "runcount": 0,
+                    "avgruntime_ms": null,
+                    "bias": 1.0,
+                    "schedule": "22:00-06:00"
+                }
+            ]
+        }"#,
+    )
+    .unwrap();
+    let options = SelectionOptions::default();
+    let mut state = SelectionState::new(None);
+
+    assert_eq!(
+        choose_script(&config, &options, &mut state, 12 * 60),
+        Some(0)
+    );
+    assert_eq!(
+        choose_script(&config, &options, &mut state, 23 * 60),
+        Some(1)
+    );
+    assert_eq!(choose_script(&config, &options, &mut state, 7 * 60), None);
+    assert_eq!(
+        state.filtered,
+        vec![
+            (0, FilterReason::OutsideSchedule),
+            (1, FilterReason::OutsideSchedule)
+        ]
+    );
+}
+
+#[test]
+fn test_choose_script_skips_invalid_schedule() {
+    let config: Config = serde_json::from_str(
+        r#"{
+            "scripts": [
+                {
+                    "name": "typo",
+                    "command": "ls",
+                    "results": { "interesting": 100, "uninteresting": 0 },
+                    "runcount": 100,
+                    "avgruntime_ms": 10.0,
+                    "bias": 1.0,
+                    "schedule": "9:00 to 17:00"
+                },
+                {
+                    "name": "anytime",
+                    "command": "ls",
+                    "results": { "interesting": 0, "uninteresting": 100 },
+                    "runcount": 100,
+                    "avgruntime_ms": 10.0,
+                    "bias": 1.0
+                }
+            ]
+        }"#,
+    )
+    .unwrap();
+    let options = SelectionOptions::default();
+    let mut state = SelectionState::new(Some(1));
+
+    // Skipped instead of panicking mid-run, however interesting it is.
+    for _ in 0..3 {
+        assert_eq!(choose_script(&config, &options, &mut state, 600), Some(1));
+    }
+    assert_eq!(state.failed, vec![0]);
+}
+
+#[test]
+fn test_choose_script_drops_scripts_over_max_runtime() {
+    let mut config: Config = serde_json::from_str(
+        r#"{
+            "scripts": [
+                {
+                    "name": "fast",
+                    "command": "ls",
+                    "results": { "interesting": 0, "uninteresting": 100 },
+                    "runcount": 100,
+                    "avgruntime_ms": 10.0,
+                    "bias": 1.0
+                },
+                {
+                    "name": "degrading",
+                    "command": "ls",
+                    "results": { "interesting": 100, "uninteresting": 0 },
+                    "runcount": 100,
+                    "avgruntime_ms": 50.0,
+                    "bias": 1.0,
+                    "max_runtime_ms": 100
+                }
+            ]
+        }"#,
+    )
+    .unwrap();
+    let options = SelectionOptions {
+        ignore_runtime: true,
+        ..SelectionOptions::default()
+    };
+    let mut state = SelectionState::new(Some(1));
+
+    // Far more interesting, so it wins while it is under the cap.
+    assert_eq!(choose_script(&config, &options, &mut state, 0), Some(1));
+
+    // One 6s run pulls the average to about 109ms, over the cap.
+    update_state(
+        &mut config.scripts[1],
+        ScriptResult {
+            interesting: 1,
+            uninteresting: 0,
+            penalty: 0,
+            runtime_ms: 6_000,
+            peak_mem_kb: None,
+            timed_out: false,
+        },
+        1,
+    );
+    for _ in 0..10 {
+        assert_eq!(choose_script(&config, &options, &mut state, 0), Some(0));
+    }
+    assert_eq!(state.too_slow, vec![1]);
+}
+
+#[test]
+fn test_decay_lets_newly_interesting_script_win() {
+    let config = |decay: &str| -> Config {
+        serde_json::from_str(&format!(
+            r#"{{
+                "scripts": [
+                    {{
+                        "name": "fixed",
+                        "command": "ls",
+                        "results": {{ "interesting": 200, "uninteresting": 10 }},
+                        "runcount": 210,
+                        "avgruntime_ms": 10.0,
+                        "bias": 1.0,
+                        "decay": {decay}
+                    }},
+                    {{
+                        "name": "new",
+                        "command": "ls",
+                        "results": {{ "interesting": 10, "uninteresting": 30 }},
+                        "runcount": 40,
+                        "avgruntime_ms": 10.0,
+                        "bias": 1.0,
+                        "decay": {decay}
+                    }}
+                ]
+            }}"#
+        ))
+        .unwrap()
+    };
+    let uninteresting = || ScriptResult {
+        interesting: 0,
+        uninteresting: 1,
+        penalty: 0,
+        runtime_ms: 10,
+        peak_mem_kb: None,
+        timed_out: false,
+    };
+    let options = SelectionOptions {
+        ignore_runtime: true,
+        strategy: Strategy::EpsilonGreedy(0.0),
+        ..SelectionOptions::default()
+    };
+    let mut state = SelectionState::new(None);
+
+    // The old findings keep the fixed script ahead without decay.
+    let mut stationary = config("null");
+    for _ in 0..200 {
+        update_state(&mut stationary.scripts[0], uninteresting(), 1);
+    }
+    assert_eq!(stationary.scripts[0].results.interesting, 200.0);
+    assert_eq!(choose_script(&stationary, &options, &mut state, 0), Some(0));
+
+    let mut decayed = config("0.95");
+    for _ in 0..200 {
+        update_state(&mut decayed.scripts[0], uninteresting(), 1);
+    }
+    assert!(decayed.scripts[0].results.interesting < 0.01);
+    assert_eq!(choose_script(&decayed, &options, &mut state, 0), Some(1));
+
+    // Thompson sampling agrees once the decayed script's posterior has collapsed.
+    let thompson = SelectionOptions {
+        strategy: Strategy::Thompson,
+        ..options
+    };
+    let picks = (0..100)
+        .filter(|_| choose_script(&decayed, &thompson, &mut state, 0) == Some(1))
+        .count();
+    assert!(picks > 90, "newly interesting script picked {picks} times");
+}
+
+#[test]
+fn test_seeded_selection_is_reproducible() {
+    let config: Config =
+        serde_json::from_str(&std::fs::read_to_string("./tests/rank/sort.json").unwrap()).unwrap();
+    let options = SelectionOptions {
+        ignore_runtime: true,
+        strategy: Strategy::Thompson,
+        ..SelectionOptions::default()
+    };
+    let selections = |seed| {
+        let mut state = SelectionState::new(Some(seed));
+        (0..50)
+            .map(|_| choose_script(&config, &options, &mut state, 0).unwrap())
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(selections(1), selections(1));
+    assert_ne!(selections(1), selections(2));
+}
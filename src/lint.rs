@@ -0,0 +1,220 @@
+use std::{collections::HashSet, fmt::Display};
+
+use ordered_float::NotNan;
+use regex::Regex;
+
+use crate::{config::Config, schedule::Schedule};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "Warning"),
+            Severity::Error => write!(f, "ERROR"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LintFinding {
+    pub script: String,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl Display for LintFinding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let prefix = format!("{} {}: ", self.script, self.severity);
+        let mut lines = self.message.lines();
+        write!(f, "{}{}", prefix, lines.next().unwrap_or_default())?;
+        // Align continuation lines with the start of the message.
+        for line in lines {
+            write!(f, "\n{:1$}{line}", "", prefix.len())?;
+        }
+        Ok(())
+    }
+}
+
+/// Interesting and uninteresting counts are floats, which stop counting single results past this.
+const MAX_EXACT_COUNT: f64 = 9_007_199_254_740_992.;
+
+/// Biases above this are flagged by default. Larger ones swamp every other script's score.
+pub const DEFAULT_MAX_BIAS: f64 = 1000.;
+
+/// Check a config for values that are valid but probably not what the user wants. Biases above `max_bias` are
+/// flagged.
+pub fn lint_config(config: &Config, max_bias: f64) -> Vec<LintFinding> {
+    let mut findings = vec![];
+    let mut names = HashSet::new();
+
+    for script in &config.scripts {
+        let mut finding = |severity, message: &str| {
+            findings.push(LintFinding {
+                script: script.name.clone(),
+                severity,
+                message: message.to_string(),
+            })
+        };
+
+        if !names.insert(&script.name) {
+            finding(
+                Severity::Error,
+                &format!("Duplicate script name {}. reset --script, import-state and promote can't tell these scripts apart.", script.name),
+            );
+        }
+
+        if script.bias == 0. {
+            finding(
+                Severity::Warning,
+                "A bias of 0 will only run after all other scripts reach their limit.",
+            );
+        }
+
+        if script.bias < NotNan::new(0.).unwrap() {
+            finding(
+                Severity::Error,
+                "A negative bias rewards tests that take more time to find an interesting case.",
+            );
+        }
+
+        if *script.bias > max_bias {
+            finding(
+                Severity::Warning,
+                &format!("A bias of {} is above {max_bias}. This script will be picked over every other almost regardless of results.", script.bias),
+            );
+        }
+
+        if script.runcount > u64::MAX / 2
+            || script.total_runtime_ms > u64::MAX / 2
+            || script.results.interesting.max(script.results.uninteresting) > MAX_EXACT_COUNT / 2.
+        {
+            finding(
+                Severity::Warning,
+                "Counts are close to the largest value they can hold. Further runs will stop being counted. Reset the script or set a decay.",
+            );
+        }
+
+        if script.limit == Some(0) {
+            finding(Severity::Warning, "Limit of 0. This will stop this script from ever running. Leave undefined to have no limit.");
+        }
+
+        if script.retries > 0 && script.retry_on_codes.is_empty() {
+            finding(
+                Severity::Warning,
+                "Retries without retry_on_codes. No exit code triggers a retry, so the script always runs once.",
+            );
+        }
+
+        if script.run_limit == Some(0) {
+            finding(Severity::Warning, "Run limit of 0. This will stop this script from ever running. Leave undefined to have no run limit.");
+        }
+
+        if let Some(code) = script
+            .interesting_codes
+            .iter()
+            .copied()
+            .chain([0, 1])
+            .find(|code| script.is_interesting_code(*code) && script.is_uninteresting_code(*code))
+        {
+            finding(
+                Severity::Error,
+                &format!("Exit code {code} is both interesting and uninteresting. It will be treated as uninteresting."),
+            );
+        }
+
+        for dependency in &script.depends_on {
+            if !config.scripts.iter().any(|other| &other.name == dependency) {
+                finding(
+                    Severity::Error,
+                    &format!("Depends on {dependency}, which is not in the config. This script will never be selected."),
+                );
+            }
+        }
+
+        if let Some(cycle) = dependency_cycle(config, &script.name) {
+            finding(
+                Severity::Error,
+                &format!("Dependency cycle {}. None of these scripts will be selected until one of them finds something.", cycle.join(" -> ")),
+            );
+        }
+
+        if let Some(champion) = &script.challenger_of {
+            if !config.scripts.iter().any(|other| &other.name == champion) {
+                finding(
+                    Severity::Error,
+                    &format!("Challenger of {champion}, which is not in the config."),
+                );
+            }
+        }
+
+        if let Some(decay) = script.decay {
+            if !(decay > 0. && decay <= 1.) {
+                finding(
+                    Severity::Error,
+                    "Decay must be above 0 and at most 1. 1 keeps every result, smaller values forget faster.",
+                );
+            } else if script.limit.is_some() {
+                finding(Severity::Warning, "Limit is compared against the decayed interesting count, so it may never be reached.");
+            }
+        }
+
+        if let Some(Err(err)) = script.schedule.as_ref().map(|s| s.parse::<Schedule>()) {
+            finding(Severity::Error, &format!("Invalid schedule. {err}"));
+        }
+
+        if script.args.is_some() && script.shell {
+            finding(
+                Severity::Warning,
+                "Both args and shell are set. With args the program is run directly and shell is ignored.",
+            );
+        }
+
+        if let Some(Err(err)) = script.interesting_regex.as_deref().map(Regex::new) {
+            finding(
+                Severity::Error,
+                &format!(
+                    "Invalid interesting_regex. The script will be skipped when selected.\n{err}"
+                ),
+            );
+        }
+    }
+
+    findings
+}
+
+/// A chain of `depends_on` names from `start` back to itself, if there is one.
+fn dependency_cycle<'a>(config: &'a Config, start: &'a str) -> Option<Vec<&'a str>> {
+    // Depth first search, keeping the path to the current script.
+    let mut path = vec![start];
+    let mut pending = vec![config
+        .scripts
+        .iter()
+        .find(|script| script.name == start)?
+        .depends_on
+        .iter()];
+    let mut visited = HashSet::new();
+    while let Some(dependencies) = pending.last_mut() {
+        let Some(name) = dependencies.next() else {
+            pending.pop();
+            path.pop();
+            continue;
+        };
+        if name == start {
+            path.push(start);
+            return Some(path);
+        }
+        if !visited.insert(name.as_str()) {
+            continue;
+        }
+        if let Some(script) = config.scripts.iter().find(|script| &script.name == name) {
+            path.push(name);
+            pending.push(script.depends_on.iter());
+        }
+    }
+    None
+}
@@ -0,0 +1,190 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Desired marker positions as a fraction of the observations, for the median.
+const INCREMENTS: [f64; 5] = [0.0, 0.25, 0.5, 0.75, 1.0];
+
+/// Streaming median estimate using the P² algorithm (Jain and Chlamtac, 1985).
+///
+/// Five markers track the minimum, the quartiles, the median and the maximum, so the estimate takes
+/// constant space however many values are recorded. Until five values are recorded the markers are just
+/// the sorted values.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize, JsonSchema)]
+#[serde(try_from = "UncheckedMedian")]
+pub struct StreamingMedian {
+    /// Marker heights, in ascending order.
+    heights: Vec<f64>,
+    /// 1-based marker positions among the values recorded so far. Empty until five values are recorded.
+    positions: Vec<f64>,
+    count: u64,
+}
+
+/// A `StreamingMedian` as stored, before its markers are checked to match its count.
+#[derive(Deserialize)]
+struct UncheckedMedian {
+    heights: Vec<f64>,
+    positions: Vec<f64>,
+    count: u64,
+}
+
+impl TryFrom<UncheckedMedian> for StreamingMedian {
+    type Error = String;
+
+    fn try_from(median: UncheckedMedian) -> Result<Self, Self::Error> {
+        let (heights, positions) = if median.count < 5 {
+            (median.count as usize, 0)
+        } else {
+            (5, 5)
+        };
+        if median.heights.len() != heights || median.positions.len() != positions {
+            return Err(format!(
+                "a median of {} values needs {} heights and {} positions, got {} and {}",
+                median.count,
+                heights,
+                positions,
+                median.heights.len(),
+                median.positions.len()
+            ));
+        }
+        Ok(StreamingMedian {
+            heights: median.heights,
+            positions: median.positions,
+            count: median.count,
+        })
+    }
+}
+
+impl StreamingMedian {
+    pub fn record(&mut self, value: f64) {
+        self.count += 1;
+        if self.count <= 5 {
+            let index = self.heights.partition_point(|height| *height <= value);
+            self.heights.insert(index, value);
+            if self.count == 5 {
+                self.positions = vec![1.0, 2.0, 3.0, 4.0, 5.0];
+            }
+            return;
+        }
+
+        let heights = &mut self.heights;
+        let positions = &mut self.positions;
+
+        // Find the cell the value falls in, stretching the outer markers if it is a new extreme.
+        let cell = if value < heights[0] {
+            heights[0] = value;
+            0
+        } else if value >= heights[4] {
+            heights[4] = value;
+            3
+        } else {
+            (0..4)
+                .find(|&i| heights[i] <= value && value < heights[i + 1])
+                .unwrap()
+        };
+        for position in &mut positions[cell + 1..] {
+            *position += 1.0;
+        }
+
+        // Move the middle markers towards their desired positions, at most one step each.
+        for i in 1..4 {
+            let desired = 1.0 + (self.count - 1) as f64 * INCREMENTS[i];
+            let offset = desired - positions[i];
+            if (offset >= 1.0 && positions[i + 1] - positions[i] > 1.0)
+                || (offset <= -1.0 && positions[i - 1] - positions[i] < -1.0)
+            {
+                let step = offset.signum();
+                let parabolic = heights[i]
+                    + step / (positions[i + 1] - positions[i - 1])
+                        * ((positions[i] - positions[i - 1] + step)
+                            * (heights[i + 1] - heights[i])
+                            / (positions[i + 1] - positions[i])
+                            + (positions[i + 1] - positions[i] - step)
+                                * (heights[i] - heights[i - 1])
+                                / (positions[i] - positions[i - 1]));
+                heights[i] = if heights[i - 1] < parabolic && parabolic < heights[i + 1] {
+                    parabolic
+                } else {
+                    // Fall back to linear interpolation towards the neighbour in the direction of travel.
+                    let neighbour = (i as isize + step as isize) as usize;
+                    heights[i]
+                        + step * (heights[neighbour] - heights[i])
+                            / (positions[neighbour] - positions[i])
+                };
+                positions[i] += step;
+            }
+        }
+    }
+
+    /// How many values have been recorded.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// The estimated median, or `None` if nothing has been recorded.
+    pub fn estimate(&self) -> Option<f64> {
+        match self.heights.len() {
+            0 => None,
+            _ if self.count >= 5 => Some(self.heights[2]),
+            len if len % 2 == 1 => Some(self.heights[len / 2]),
+            len => Some((self.heights[len / 2 - 1] + self.heights[len / 2]) / 2.0),
+        }
+    }
+}
+
+#[test]
+fn test_median_of_few_values() {
+    let mut median = StreamingMedian::default();
+    assert_eq!(median.estimate(), None);
+    median.record(30.0);
+    assert_eq!(median.estimate(), Some(30.0));
+    median.record(10.0);
+    assert_eq!(median.estimate(), Some(20.0));
+    median.record(20.0);
+    assert_eq!(median.estimate(), Some(20.0));
+}
+
+#[test]
+fn test_median_of_many_values() {
+    // A shuffled permutation of 1..=1001, whose median is 501.
+    let mut median = StreamingMedian::default();
+    for index in 0..1001u64 {
+        median.record(((index * 367) % 1001 + 1) as f64);
+    }
+    let estimate = median.estimate().unwrap();
+    assert!((estimate - 501.0).abs() < 20.0, "estimate {estimate}");
+}
+
+#[test]
+fn test_median_of_bimodal_values() {
+    // Mostly fast runs with an occasional slow one. The mean is pulled up to about 109, the median isn't.
+    let mut median = StreamingMedian::default();
+    for index in 0..1000 {
+        median.record(if index % 10 == 9 { 1000.0 } else { 10.0 });
+    }
+    let estimate = median.estimate().unwrap();
+    assert!((estimate - 10.0).abs() < 1.0, "estimate {estimate}");
+}
+
+#[test]
+fn test_median_rejects_mismatched_markers() {
+    let mut median = StreamingMedian::default();
+    for value in [3.0, 1.0, 4.0, 1.0, 5.0, 9.0] {
+        median.record(value);
+    }
+    let json = serde_json::to_value(&median).unwrap();
+    assert_eq!(
+        serde_json::from_value::<StreamingMedian>(json).unwrap(),
+        median
+    );
+
+    let truncated =
+        serde_json::json!({ "heights": [1.0, 2.0], "positions": [1.0, 2.0], "count": 6 });
+    let err = serde_json::from_value::<StreamingMedian>(truncated).unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("needs 5 heights and 5 positions, got 2 and 2"),
+        "{err}"
+    );
+    let early = serde_json::json!({ "heights": [1.0], "positions": [], "count": 2 });
+    assert!(serde_json::from_value::<StreamingMedian>(early).is_err());
+}
@@ -0,0 +1,377 @@
+use std::{
+    io::{self, Read},
+    process::{Command, ExitStatus, Output, Stdio},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+/// Build the command to run for a script's command line.
+///
+/// By default the command line is split on whitespace and the program is spawned directly, with no
+/// quoting or shell syntax. With `shell` the whole line is handed to the platform shell instead, `sh -c`
+/// on Unix and `cmd /C` on Windows, so builtins, pipes and `.bat`/`.cmd` scripts work.
+pub fn script_command(command_line: &str, shell: bool) -> io::Result<Command> {
+    let mut parts = command_line.split_whitespace();
+    let Some(program) = parts.next() else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "No command provided",
+        ));
+    };
+    if shell {
+        return Ok(shell_command(command_line));
+    }
+
+    let mut command = Command::new(program);
+    command.args(parts);
+    Ok(command)
+}
+
+/// Build the command to run `program` with an explicit argument list, passed as is without splitting or
+/// quoting, so arguments may contain spaces.
+pub fn program_command(program: &str, args: &[String]) -> io::Result<Command> {
+    let program = program.trim();
+    if program.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "No command provided",
+        ));
+    }
+
+    let mut command = Command::new(program);
+    command.args(args);
+    Ok(command)
+}
+
+#[cfg(unix)]
+fn shell_command(command_line: &str) -> Command {
+    let mut command = Command::new("sh");
+    command.arg("-c").arg(command_line);
+    command
+}
+
+#[cfg(windows)]
+fn shell_command(command_line: &str) -> Command {
+    use std::os::windows::process::CommandExt;
+
+    // cmd does its own parsing of the rest of the line, so pass it through unquoted. The default
+    // argument quoting follows the MSVC runtime conventions, which cmd does not understand.
+    let mut command = Command::new("cmd");
+    command.arg("/C").raw_arg(command_line);
+    command
+}
+
+/// Optional resource limits for a script's process, applied with `setrlimit` on Unix.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct ResourceLimits {
+    pub address_space_mb: Option<u64>,
+    pub cpu_s: Option<u64>,
+    pub file_size_mb: Option<u64>,
+}
+
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+type Resource = libc::__rlimit_resource_t;
+#[cfg(all(unix, not(all(target_os = "linux", target_env = "gnu"))))]
+type Resource = libc::c_int;
+
+#[cfg(unix)]
+fn set_limit(resource: Resource, value: Option<u64>) -> io::Result<()> {
+    let Some(value) = value else {
+        return Ok(());
+    };
+    let limit = libc::rlimit {
+        rlim_cur: value as libc::rlim_t,
+        rlim_max: value as libc::rlim_t,
+    };
+    // SAFETY: limit is a valid rlimit for the duration of the call.
+    if unsafe { libc::setrlimit(resource, &limit) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Apply `limits` to the child process before it executes.
+///
+/// A process that runs over its CPU time is killed by a signal. Running out of address space or file
+/// size makes allocations and writes fail, which most programs turn into a nonzero exit.
+#[cfg(unix)]
+pub fn apply_resource_limits(command: &mut Command, limits: ResourceLimits) {
+    use std::os::unix::process::CommandExt;
+
+    if limits == ResourceLimits::default() {
+        return;
+    }
+
+    const MB: u64 = 1024 * 1024;
+    // Converted before forking, where nothing may panic. Sizes too large for bytes saturate to no limit.
+    let address_space = limits.address_space_mb.map(|mb| mb.saturating_mul(MB));
+    let file_size = limits.file_size_mb.map(|mb| mb.saturating_mul(MB));
+    // SAFETY: the hook runs between fork and exec, and only calls setrlimit, which is async-signal-safe.
+    unsafe {
+        command.pre_exec(move || {
+            set_limit(libc::RLIMIT_AS, address_space)?;
+            set_limit(libc::RLIMIT_CPU, limits.cpu_s)?;
+            set_limit(libc::RLIMIT_FSIZE, file_size)?;
+            Ok(())
+        });
+    }
+}
+
+/// Resource limits are not supported on this platform and are ignored.
+#[cfg(not(unix))]
+pub fn apply_resource_limits(_command: &mut Command, _limits: ResourceLimits) {}
+
+/// How often a child with a timeout is checked on.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// A finished child process.
+pub struct ChildOutput {
+    pub output: Output,
+    /// Peak resident set size in kilobytes. Only known on Unix.
+    pub peak_mem_kb: Option<u64>,
+    /// Whether the child was killed for running past its timeout.
+    pub timed_out: bool,
+}
+
+/// Run a command to completion like `Command::output`, killing it if it runs longer than `timeout`.
+///
+/// Peak memory is read from `wait4` on Unix. With a timeout the child is started in its own process
+/// group and the whole group is killed, so processes it started can't keep its output open.
+#[cfg(unix)]
+pub fn wait_for_output(
+    command: &mut Command,
+    timeout: Option<Duration>,
+) -> io::Result<ChildOutput> {
+    use std::os::unix::process::{CommandExt, ExitStatusExt};
+
+    if timeout.is_some() {
+        command.process_group(0);
+    }
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let start = Instant::now();
+
+    // Drain both pipes while waiting so a chatty child can't block on a full pipe.
+    let stdout = read_to_end_in_background(child.stdout.take());
+    let stderr = read_to_end_in_background(child.stderr.take());
+
+    let pid = child.id() as libc::pid_t;
+    let mut status = 0;
+    let mut timed_out = false;
+    // SAFETY: rusage is plain old data, so all zeros is a valid value.
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    loop {
+        // Poll while the timeout can still fire, otherwise block until the child exits.
+        let options = if timeout.is_some() && !timed_out {
+            libc::WNOHANG
+        } else {
+            0
+        };
+        // SAFETY: status and usage are valid for writes, and pid is our unreaped child.
+        let reaped = unsafe { libc::wait4(pid, &mut status, options, &mut usage) };
+        if reaped == pid {
+            break;
+        }
+        if reaped == 0 {
+            if timeout.is_some_and(|timeout| start.elapsed() >= timeout) {
+                // SAFETY: the child is unreaped and leads its own process group, so -pid is our group.
+                if unsafe { libc::kill(-pid, libc::SIGKILL) } != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                timed_out = true;
+            } else {
+                thread::sleep(POLL_INTERVAL);
+            }
+            continue;
+        }
+        let error = io::Error::last_os_error();
+        if error.kind() != io::ErrorKind::Interrupted {
+            return Err(error);
+        }
+    }
+
+    let output = Output {
+        status: std::process::ExitStatus::from_raw(status),
+        stdout: stdout.join().unwrap()?,
+        stderr: stderr.join().unwrap()?,
+    };
+
+    // ru_maxrss is reported in bytes on macOS and kilobytes elsewhere.
+    let max_rss = usage.ru_maxrss as u64;
+    let peak_mem_kb = if cfg!(target_os = "macos") {
+        max_rss / 1024
+    } else {
+        max_rss
+    };
+
+    Ok(ChildOutput {
+        output,
+        peak_mem_kb: Some(peak_mem_kb),
+        timed_out,
+    })
+}
+
+#[cfg(not(unix))]
+pub fn wait_for_output(
+    command: &mut Command,
+    timeout: Option<Duration>,
+) -> io::Result<ChildOutput> {
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let start = Instant::now();
+
+    let stdout = read_to_end_in_background(child.stdout.take());
+    let stderr = read_to_end_in_background(child.stderr.take());
+
+    let mut timed_out = false;
+    let status = match timeout {
+        Some(timeout) => loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+            if !timed_out && start.elapsed() >= timeout {
+                child.kill()?;
+                timed_out = true;
+            }
+            thread::sleep(POLL_INTERVAL);
+        },
+        None => child.wait()?,
+    };
+
+    Ok(ChildOutput {
+        output: Output {
+            status,
+            stdout: stdout.join().unwrap()?,
+            stderr: stderr.join().unwrap()?,
+        },
+        peak_mem_kb: None,
+        timed_out,
+    })
+}
+
+/// The signal that terminated a process, if it was killed by one.
+#[cfg(unix)]
+pub fn terminating_signal(status: &ExitStatus) -> Option<i32> {
+    use std::os::unix::process::ExitStatusExt;
+
+    status.signal()
+}
+
+/// Processes aren't terminated by signals on this platform.
+#[cfg(not(unix))]
+pub fn terminating_signal(_status: &ExitStatus) -> Option<i32> {
+    None
+}
+
+fn read_to_end_in_background<R: Read + Send + 'static>(
+    pipe: Option<R>,
+) -> JoinHandle<io::Result<Vec<u8>>> {
+    thread::spawn(move || {
+        let mut data = vec![];
+        if let Some(mut pipe) = pipe {
+            pipe.read_to_end(&mut data)?;
+        }
+        Ok(data)
+    })
+}
+
+#[test]
+fn test_script_command_blank() {
+    let err = script_command("  ", false).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    assert!(script_command("", true).is_err());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_script_command_shell() {
+    let output = script_command("echo one && echo two", true)
+        .unwrap()
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "one\ntwo\n");
+
+    // Without a shell, && is passed to echo as a plain argument.
+    let output = script_command("echo one && echo two", false)
+        .unwrap()
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "one && echo two\n");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_script_command_shell_pipe_and_quotes() {
+    let output = script_command("printf 'b\\na\\n' | sort | tr a-z A-Z", true)
+        .unwrap()
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "A\nB\n");
+
+    let output = script_command(r#"printf '%s\n' "it's \"quoted\"""#, true)
+        .unwrap()
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "it's \"quoted\"\n");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_program_command_args_with_spaces() {
+    let args = [
+        "%s|".to_string(),
+        "my file.txt".to_string(),
+        r#"{"key": "a value"}"#.to_string(),
+    ];
+    let output = program_command("printf", &args).unwrap().output().unwrap();
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        r#"my file.txt|{"key": "a value"}|"#
+    );
+
+    assert!(program_command(" ", &args).is_err());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_huge_resource_limits_saturate() {
+    let mut command = Command::new("true");
+    apply_resource_limits(
+        &mut command,
+        ResourceLimits {
+            address_space_mb: Some(u64::MAX),
+            cpu_s: None,
+            file_size_mb: Some(u64::MAX / 2),
+        },
+    );
+    assert!(command.status().unwrap().success());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_wait_for_output_timeout() {
+    // The shell's sleep is a separate process holding stdout, so the whole group must be killed.
+    let start = Instant::now();
+    let child = wait_for_output(
+        &mut script_command("sleep 5; echo done", true).unwrap(),
+        Some(Duration::from_millis(100)),
+    )
+    .unwrap();
+    assert!(child.timed_out);
+    assert!(start.elapsed() < Duration::from_secs(5));
+    assert!(child.output.stdout.is_empty());
+
+    let child = wait_for_output(
+        &mut script_command("echo done", true).unwrap(),
+        Some(Duration::from_secs(5)),
+    )
+    .unwrap();
+    assert!(!child.timed_out);
+    assert_eq!(String::from_utf8_lossy(&child.output.stdout), "done\n");
+}
@@ -0,0 +1,127 @@
+use std::str::FromStr;
+
+const MINUTES_PER_DAY: u32 = 24 * 60;
+
+/// Daily time windows a script may run in, written as `HH:MM-HH:MM` ranges separated by commas.
+///
+/// Each window includes its start and excludes its end. A window whose end is before its start wraps
+/// past midnight, so `22:00-06:00` covers the night.
+#[derive(Debug, PartialEq)]
+pub struct Schedule {
+    windows: Vec<(u32, u32)>,
+}
+
+impl Schedule {
+    /// Whether `minute` (minutes since local midnight) falls inside one of the windows.
+    pub fn contains(&self, minute: u32) -> bool {
+        self.windows.iter().any(|(start, end)| {
+            if start < end {
+                *start <= minute && minute < *end
+            } else {
+                *start <= minute || minute < *end
+            }
+        })
+    }
+}
+
+fn parse_time(s: &str) -> Result<u32, String> {
+    let (hours, minutes) = s
+        .split_once(':')
+        .ok_or_else(|| format!("Invalid time {s}. Expected HH:MM"))?;
+    let hours: u32 = hours.parse().map_err(|_| format!("Invalid hour in {s}"))?;
+    let minutes: u32 = minutes
+        .parse()
+        .map_err(|_| format!("Invalid minute in {s}"))?;
+    if hours > 23 || minutes > 59 {
+        return Err(format!("Time {s} is out of range. Expected 00:00 to 23:59"));
+    }
+    Ok(hours * 60 + minutes)
+}
+
+impl FromStr for Schedule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let windows = s
+            .split(',')
+            .map(|window| {
+                let (start, end) = window
+                    .trim()
+                    .split_once('-')
+                    .ok_or_else(|| format!("Invalid window {window}. Expected HH:MM-HH:MM"))?;
+                let start = parse_time(start.trim())?;
+                let end = parse_time(end.trim())?;
+                if start == end {
+                    return Err(format!("Window {window} is empty"));
+                }
+                Ok((start, end))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(Schedule { windows })
+    }
+}
+
+/// Minutes since midnight in local time.
+#[cfg(unix)]
+pub fn local_minute_of_day() -> u32 {
+    // SAFETY: time with a null pointer only returns the current time. tm is plain old data, so all
+    // zeros is a valid value for localtime_r to overwrite.
+    unsafe {
+        let now = libc::time(std::ptr::null_mut());
+        let mut tm: libc::tm = std::mem::zeroed();
+        if libc::localtime_r(&now, &mut tm).is_null() {
+            return utc_minute_of_day();
+        }
+        (tm.tm_hour * 60 + tm.tm_min) as u32
+    }
+}
+
+/// Minutes since midnight. Time zones are not looked up on this platform, so this is UTC.
+#[cfg(not(unix))]
+pub fn local_minute_of_day() -> u32 {
+    utc_minute_of_day()
+}
+
+fn utc_minute_of_day() -> u32 {
+    let seconds = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    ((seconds / 60) % MINUTES_PER_DAY as u64) as u32
+}
+
+#[test]
+fn test_schedule_parse() {
+    assert_eq!(
+        "09:00-17:30".parse::<Schedule>(),
+        Ok(Schedule {
+            windows: vec![(540, 1050)]
+        })
+    );
+    assert_eq!(
+        "22:00-06:00, 12:00-13:00".parse::<Schedule>(),
+        Ok(Schedule {
+            windows: vec![(1320, 360), (720, 780)]
+        })
+    );
+    assert!("09:00".parse::<Schedule>().is_err());
+    assert!("9-17".parse::<Schedule>().is_err());
+    assert!("24:00-01:00".parse::<Schedule>().is_err());
+    assert!("10:00-10:00".parse::<Schedule>().is_err());
+}
+
+#[test]
+fn test_schedule_contains() {
+    let office: Schedule = "09:00-17:00".parse().unwrap();
+    assert!(!office.contains(8 * 60 + 59));
+    assert!(office.contains(9 * 60));
+    assert!(office.contains(16 * 60 + 59));
+    assert!(!office.contains(17 * 60));
+
+    let night: Schedule = "22:00-06:00".parse().unwrap();
+    assert!(night.contains(23 * 60));
+    assert!(night.contains(0));
+    assert!(night.contains(5 * 60 + 59));
+    assert!(!night.contains(6 * 60));
+    assert!(!night.contains(12 * 60));
+}
@@ -0,0 +1,147 @@
+use std::fmt;
+
+/// A `$VAR` or `${VAR}` reference to an environment variable that is not set and has no default.
+#[derive(Debug, PartialEq)]
+pub struct UnsetVariable(pub String);
+
+impl fmt::Display for UnsetVariable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "environment variable {} is not set (use ${{{}:-default}} to provide a fallback)",
+            self.0, self.0
+        )
+    }
+}
+
+/// Expands `$VAR`, `${VAR}` and `${VAR:-default}` references using the process environment, and `$$` to
+/// `$`. Unset variables without a default are an error, or expand to nothing with `empty_unset`.
+pub fn expand_env(input: &str, empty_unset: bool) -> Result<String, UnsetVariable> {
+    expand_with(input, |name| std::env::var(name).ok(), empty_unset)
+}
+
+/// Expands `$VAR`, `${VAR}` and `${VAR:-default}` references, looking variables up with `lookup`, and `$$`
+/// to `$`.
+///
+/// Unterminated references, and a `$` not followed by a name, such as the shell's `$1` or `$?`, are left as
+/// written.
+fn expand_with(
+    input: &str,
+    lookup: impl Fn(&str) -> Option<String>,
+    empty_unset: bool,
+) -> Result<String, UnsetVariable> {
+    let mut expanded = String::with_capacity(input.len());
+    let mut rest = input;
+
+    while let Some(start) = rest.find('$') {
+        expanded.push_str(&rest[..start]);
+        rest = &rest[start + 1..];
+
+        let (name, default, len) = if let Some(braced) = rest.strip_prefix('{') {
+            let Some(len) = braced.find('}') else {
+                expanded.push('$');
+                break;
+            };
+            let reference = &braced[..len];
+            match reference.split_once(":-") {
+                Some((name, default)) => (name, Some(default), len + 2),
+                None => (reference, None, len + 2),
+            }
+        } else if let Some(escaped) = rest.strip_prefix('$') {
+            expanded.push('$');
+            rest = escaped;
+            continue;
+        } else {
+            let len = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            if len == 0 || rest.starts_with(|c: char| c.is_ascii_digit()) {
+                expanded.push('$');
+                continue;
+            }
+            (&rest[..len], None, len)
+        };
+
+        match (lookup(name), default) {
+            (Some(value), _) => expanded.push_str(&value),
+            (None, Some(default)) => expanded.push_str(default),
+            (None, None) if empty_unset => {}
+            (None, None) => return Err(UnsetVariable(name.to_string())),
+        }
+        rest = &rest[len..];
+    }
+    expanded.push_str(rest);
+
+    Ok(expanded)
+}
+
+#[cfg(test)]
+fn lookup(name: &str) -> Option<String> {
+    match name {
+        "SCRIPTS" => Some("/home/user/scripts".to_string()),
+        "EMPTY" => Some(String::new()),
+        _ => None,
+    }
+}
+
+#[test]
+fn test_expand_set_variable() {
+    assert_eq!(
+        expand_with("${SCRIPTS}/exit.sh 1", lookup, false),
+        Ok("/home/user/scripts/exit.sh 1".to_string())
+    );
+    assert_eq!(
+        expand_with("a${EMPTY}b ${SCRIPTS:-/tmp}", lookup, false),
+        Ok("ab /home/user/scripts".to_string())
+    );
+    assert_eq!(
+        expand_with("$SCRIPTS/exit.sh a$EMPTY-b", lookup, false),
+        Ok("/home/user/scripts/exit.sh a-b".to_string())
+    );
+}
+
+#[test]
+fn test_expand_unset_variable() {
+    assert_eq!(
+        expand_with("${MISSING}/exit.sh 1", lookup, false),
+        Err(UnsetVariable("MISSING".to_string()))
+    );
+    assert_eq!(
+        expand_with("tool --key $MISSING", lookup, false),
+        Err(UnsetVariable("MISSING".to_string()))
+    );
+
+    // Unless unset variables are allowed to expand to nothing.
+    assert_eq!(
+        expand_with("tool --key $MISSING ${MISSING}.", lookup, true),
+        Ok("tool --key  .".to_string())
+    );
+}
+
+#[test]
+fn test_expand_escaped_dollar() {
+    assert_eq!(
+        expand_with("echo $$SCRIPTS $$$$ $${SCRIPTS}", lookup, false),
+        Ok("echo $SCRIPTS $$ ${SCRIPTS}".to_string())
+    );
+}
+
+#[test]
+fn test_expand_default_fallback() {
+    assert_eq!(
+        expand_with("${MISSING:-./scripts}/exit.sh 1", lookup, false),
+        Ok("./scripts/exit.sh 1".to_string())
+    );
+    assert_eq!(
+        expand_with("${MISSING:-}exit.sh", lookup, false),
+        Ok("exit.sh".to_string())
+    );
+}
+
+#[test]
+fn test_expand_without_references() {
+    assert_eq!(
+        expand_with("./scripts/exit.sh $1 $? $ ${unterminated", lookup, false),
+        Ok("./scripts/exit.sh $1 $? $ ${unterminated".to_string())
+    );
+}
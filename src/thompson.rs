@@ -1,54 +1,231 @@
+#[cfg(test)]
+extern crate test;
+use crate::ibeta::cached_invbetai;
 use log::debug;
 use ordered_float::NotNan;
 use rand::Rng;
+use rayon::prelude::*;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+#[cfg(test)]
+use test::{black_box, Bencher};
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+/// Rankings with at least this many entries sample them on the rayon thread pool.
+const PARALLEL_RANKING_THRESHOLD: usize = 1000;
+
+/// Interesting and uninteresting results of an entry.
+///
+/// Counts are whole numbers unless the script decays its results, see `Script::decay`.
+#[derive(Debug, Deserialize, Serialize, Clone, JsonSchema)]
 pub struct ThompsonInfo {
-    pub interesting: u64,
-    pub uninteresting: u64,
+    #[serde(serialize_with = "serialize_count")]
+    pub interesting: f64,
+    #[serde(serialize_with = "serialize_count")]
+    pub uninteresting: f64,
+}
+
+/// Write whole counts as integers so configs without decay keep their format.
+pub fn serialize_count<S: serde::Serializer>(
+    count: &f64,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    if count.fract() == 0.0 && *count >= 0.0 && *count <= u64::MAX as f64 {
+        serializer.serialize_u64(*count as u64)
+    } else {
+        serializer.serialize_f64(*count)
+    }
+}
+
+/// Beta distribution parameters that observed results are added to when sampling.
+///
+/// The default is the uniform Beta(1, 1) prior.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+pub struct Prior {
+    pub alpha: f64,
+    pub beta: f64,
+}
+
+impl Default for Prior {
+    fn default() -> Self {
+        Prior {
+            alpha: 1.0,
+            beta: 1.0,
+        }
+    }
+}
+
+/// The runtime, in milliseconds, that gets a runtime scaler of 1 unless a config or `--runtime-reference`
+/// sets another.
+///
+/// All scripts with a known runtime share the same reference, so it only sets the magnitude of scores.
+/// Their relative order and the bias equivalence (a bias of 5 is worth running 5x as fast) are the same
+/// whatever the reference.
+pub const DEFAULT_RUNTIME_REFERENCE_MS: f64 = 100.0;
+
+/// How `run` picks the next script and `rank` orders them.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Default)]
+pub enum Strategy {
+    /// Sample each script's beta distribution, optionally weighted by runtime and bias.
+    #[default]
+    Thompson,
+    /// Explore a uniformly random script with the given probability, otherwise exploit the script with
+    /// the highest empirical interesting rate.
+    EpsilonGreedy(f64),
+    /// Pick the script with the highest upper confidence bound on its interesting rate. Deterministic
+    /// given the config.
+    Ucb1,
+    /// Baseline that cycles through the scripts in config order, ignoring their results.
+    RoundRobin,
+    /// Baseline that picks a uniformly random script, ignoring their results.
+    Uniform,
+}
+
+impl FromStr for Strategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "thompson" => return Ok(Strategy::Thompson),
+            "ucb1" => return Ok(Strategy::Ucb1),
+            "round-robin" => return Ok(Strategy::RoundRobin),
+            "uniform" => return Ok(Strategy::Uniform),
+            _ => {}
+        }
+        if let Some(epsilon) = s.strip_prefix("epsilon-greedy:") {
+            return match epsilon.parse::<f64>() {
+                Ok(epsilon) if (0.0..=1.0).contains(&epsilon) => {
+                    Ok(Strategy::EpsilonGreedy(epsilon))
+                }
+                _ => Err(format!(
+                    "Invalid epsilon {epsilon}. Expected a number from 0 to 1"
+                )),
+            };
+        }
+        Err(format!(
+            "Unknown strategy {s}. Expected thompson, ucb1, round-robin, uniform or epsilon-greedy:<epsilon>"
+        ))
+    }
+}
+
+/// Multiplier applied to a sampled point to favor faster scripts.
+pub fn time_scaler(runtime: &NotNan<f64>, runtime_reference_ms: NotNan<f64>) -> NotNan<f64> {
+    runtime_reference_ms / runtime
+}
+
+/// Biases are multiplicative weights on a script's sampled score. Lower biases, including 0 and negative
+/// ones, are raised to this, so those scripts run after the rest but are still ranked among themselves.
+pub const MIN_BIAS: f64 = 1e-6;
+
+/// Weight a sampled score by a user bias. Applied the same way whether or not runtime is considered.
+pub fn apply_bias(score: NotNan<f64>, user_bias: &NotNan<f64>) -> NotNan<f64> {
+    score * (*user_bias).max(NotNan::new(MIN_BIAS).unwrap())
 }
 
 pub fn skew_percentile(
     sampled_point: NotNan<f64>,
     runtime: &Option<NotNan<f64>>,
     user_bias: &NotNan<f64>,
+    runtime_reference_ms: NotNan<f64>,
 ) -> NotNan<f64> {
     if let Some(runtime) = runtime {
-        let time_scaler = NotNan::new(100.0).unwrap() / runtime;
+        let time_scaler = time_scaler(runtime, runtime_reference_ms);
 
         // A script with bias of 5 is weighted to be equal to an equivalent script that runs 5x as fast.
-        sampled_point * time_scaler * user_bias
+        apply_bias(sampled_point * time_scaler, user_bias)
     } else {
         // If we don't know a runtime, return the max score so we sample the runtime at least once.
         NotNan::new(f64::MAX).unwrap()
     }
 }
 
+/// Everything besides its results that an entry's runtime-biased score is made from. Each slice holds
+/// one element per entry, in the same order as the entries.
+pub struct RuntimeScoring<'a> {
+    pub priors: &'a [&'a Prior],
+    pub runtimes: &'a [&'a Option<NotNan<f64>>],
+    pub runcounts: &'a [u64],
+    pub user_biases: &'a [&'a NotNan<f64>],
+    pub runtime_reference_ms: NotNan<f64>,
+    /// Runs before an entry's average runtime is trusted.
+    pub min_samples: usize,
+}
+
 /// Prefer entries with low runtime.
 /// Entries without a specified runtime will always be run first.
 pub fn thompson_sampling_bias_runtime(
     entries: &[&ThompsonInfo],
-    runtimes: &[&Option<NotNan<f64>>],
-    user_biases: &[&NotNan<f64>],
+    scoring: &RuntimeScoring,
+    rng: &mut impl Rng,
 ) -> Option<usize> {
-    let mut selected_entry_index: Option<usize> = None;
-    let mut selected_entry_percentile: NotNan<f64> = NotNan::new(-1.0).unwrap();
-    for (index, entry) in entries.iter().enumerate() {
-        let skewed_percentile = thompson_step_bias_runtime(
-            entry.interesting,
-            entry.uninteresting,
-            runtimes[index],
-            user_biases[index],
-        );
+    let selected_entry_index = best_score(&sample_breakdowns_bias_runtime(entries, scoring, rng));
+    debug!("Selected entry: {:?}", selected_entry_index);
+
+    selected_entry_index
+}
+
+/// How a sampled score was made up, for explaining a selection.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScoreBreakdown {
+    /// The point sampled from the entry's posterior.
+    pub percentile: NotNan<f64>,
+    /// Multiplier favoring faster entries. `None` if runtime is ignored or unknown, and an unknown runtime
+    /// scores the maximum.
+    pub runtime_scaler: Option<NotNan<f64>>,
+    /// The bias the score was weighted by, or `None` if biases were left out.
+    pub bias: Option<NotNan<f64>>,
+    pub score: NotNan<f64>,
+}
 
-        if skewed_percentile > selected_entry_percentile {
+/// Sample every entry the way `thompson_sampling_bias_runtime` does, keeping how each score was made up.
+pub fn sample_breakdowns_bias_runtime(
+    entries: &[&ThompsonInfo],
+    scoring: &RuntimeScoring,
+    rng: &mut impl Rng,
+) -> Vec<ScoreBreakdown> {
+    entries
+        .iter()
+        .enumerate()
+        .map(|(index, entry)| {
+            breakdown_bias_runtime(entry, scoring, index, rng.gen_range(0.0..1.0))
+        })
+        .collect()
+}
+
+/// Sample every entry ignoring runtime, the way `thompson_sampling` does with `user_biases` and
+/// `thompson_ranking` without, keeping how each score was made up.
+pub fn sample_breakdowns(
+    entries: &[&ThompsonInfo],
+    priors: &[&Prior],
+    user_biases: Option<&[&NotNan<f64>]>,
+    rng: &mut impl Rng,
+) -> Vec<ScoreBreakdown> {
+    entries
+        .iter()
+        .enumerate()
+        .map(|(index, entry)| {
+            let percentile = thompson_step(entry, priors[index], rng.gen_range(0.0..1.0));
+            let bias = user_biases.map(|user_biases| *user_biases[index]);
+            ScoreBreakdown {
+                percentile,
+                runtime_scaler: None,
+                bias,
+                score: bias.map_or(percentile, |bias| apply_bias(percentile, &bias)),
+            }
+        })
+        .collect()
+}
+
+/// The first entry with the highest score, the one selection picks.
+pub fn best_score(breakdowns: &[ScoreBreakdown]) -> Option<usize> {
+    let mut selected_entry_index: Option<usize> = None;
+    let mut selected_entry_score: NotNan<f64> = NotNan::new(-1.0).unwrap();
+    for (index, breakdown) in breakdowns.iter().enumerate() {
+        if breakdown.score > selected_entry_score {
             selected_entry_index = Some(index);
-            selected_entry_percentile = skewed_percentile;
+            selected_entry_score = breakdown.score;
         }
     }
-    debug!("Selected entry: {:?}", selected_entry_index);
-
     selected_entry_index
 }
 
@@ -57,136 +234,461 @@ pub fn thompson_sampling_bias_runtime(
 /// Ex. [0, 2, 1]: The first element was ranked first, the third second, and second third.
 pub fn thompson_ranking_bias_runtime(
     entries: &[&ThompsonInfo],
-    runtimes: &[&Option<NotNan<f64>>],
-    user_biases: &[&NotNan<f64>],
+    scoring: &RuntimeScoring,
+    rng: &mut impl Rng,
 ) -> Vec<usize> {
-    let mut percentiles_index_mapping = entries
-        .iter()
-        .enumerate()
-        .map(|(idx, entry)| {
-            (
-                idx,
-                thompson_step_bias_runtime(
-                    entry.interesting,
-                    entry.uninteresting,
-                    runtimes[idx],
-                    user_biases[idx],
-                ),
-            )
-        })
-        .collect::<Vec<_>>();
+    thompson_scores_bias_runtime(entries, scoring, rng)
+        .into_iter()
+        .map(|(index, _score)| index)
+        .collect()
+}
 
-    percentiles_index_mapping.sort_by_key(|&(_, percentile)| percentile);
+/// Like `thompson_ranking_bias_runtime`, but keeps the skewed percentile each entry was ranked by.
+pub fn thompson_scores_bias_runtime(
+    entries: &[&ThompsonInfo],
+    scoring: &RuntimeScoring,
+    rng: &mut impl Rng,
+) -> Vec<(usize, NotNan<f64>)> {
+    let mut percentiles_index_mapping = score_entries(entries.len(), rng, |idx, random_float| {
+        thompson_step_bias_runtime(entries[idx], scoring, idx, random_float)
+    });
 
+    sort_by_score(&mut percentiles_index_mapping);
     percentiles_index_mapping
-        .iter()
-        .rev()
-        .map(|(index, _percentile)| *index)
-        .collect()
 }
 
-/// Map a single entry into a score comparable to other entries.
+/// Order scored entries best first. Equal scores, such as every script with an unknown runtime, keep their
+/// input order, the entry listed first in the config ranking first, as it would win a tie when selecting.
+fn sort_by_score(scores: &mut [(usize, NotNan<f64>)]) {
+    scores.sort_by(|(a_index, a_score), (b_index, b_score)| {
+        b_score.cmp(a_score).then(a_index.cmp(b_index))
+    });
+}
+
+/// Pair each index with its score, scoring large inputs in parallel.
+///
+/// The random point each entry is sampled at is drawn from `rng` up front, in order, so a seeded ranking
+/// is the same whether or not it is scored in parallel.
+fn score_entries(
+    len: usize,
+    rng: &mut impl Rng,
+    score: impl Fn(usize, f64) -> NotNan<f64> + Sync,
+) -> Vec<(usize, NotNan<f64>)> {
+    let random_floats = (0..len)
+        .map(|_| rng.gen_range(0.0..1.0))
+        .collect::<Vec<f64>>();
+    if len >= PARALLEL_RANKING_THRESHOLD {
+        (0..len)
+            .into_par_iter()
+            .map(|idx| (idx, score(idx, random_floats[idx])))
+            .collect()
+    } else {
+        (0..len)
+            .map(|idx| (idx, score(idx, random_floats[idx])))
+            .collect()
+    }
+}
+
+/// Map a single entry, the `index`th of `scoring`'s, into a score comparable to other entries.
+///
+/// Until a script has run `min_samples` times its average runtime is not trusted, and it is scored as
+/// if it took the reference runtime, so one slow first run can't bury it.
 fn thompson_step_bias_runtime(
-    interesting: u64,
-    uninteresting: u64,
-    runtime: &Option<NotNan<f64>>,
-    user_bias: &NotNan<f64>,
+    entry: &ThompsonInfo,
+    scoring: &RuntimeScoring,
+    index: usize,
+    random_float: f64,
 ) -> NotNan<f64> {
-    let mut rng = rand::thread_rng();
-    // Random number from 0.0 to 1.0 inclusive
-    let random_float = rng.gen_range(0.0..1.0);
+    breakdown_bias_runtime(entry, scoring, index, random_float).score
+}
 
-    let percentile = puruspe::invbetai(
-        random_float,
-        (interesting + 1) as f64,
-        (uninteresting + 1) as f64,
-    );
+/// Like `thompson_step_bias_runtime`, keeping the parts of the score.
+fn breakdown_bias_runtime(
+    entry: &ThompsonInfo,
+    scoring: &RuntimeScoring,
+    index: usize,
+    random_float: f64,
+) -> ScoreBreakdown {
+    let prior = scoring.priors[index];
+    let runtime = scoring.runtimes[index];
+    let runcount = scoring.runcounts[index];
+    let user_bias = scoring.user_biases[index];
+    let runtime_reference_ms = scoring.runtime_reference_ms;
+
+    let alpha = entry.interesting + prior.alpha;
+    let beta = entry.uninteresting + prior.beta;
+    let percentile = cached_invbetai(random_float, alpha, beta);
 
-    let skewed_percentile = skew_percentile(NotNan::new(percentile).unwrap(), runtime, user_bias);
+    let runtime = match runtime {
+        Some(_) if runcount < scoring.min_samples as u64 => &Some(runtime_reference_ms),
+        _ => runtime,
+    };
+
+    let skewed_percentile = skew_percentile(
+        NotNan::new(percentile).unwrap(),
+        runtime,
+        user_bias,
+        runtime_reference_ms,
+    );
 
     debug!(
         "Total percentage of area at point {:.4}: {:.2}% B({}, {}) Skewed area: {:.2}",
         random_float * 100.0,
         percentile,
-        (uninteresting + 1) as f64,
-        (interesting + 1) as f64,
+        alpha,
+        beta,
         skewed_percentile
     );
 
-    skewed_percentile
+    ScoreBreakdown {
+        percentile: NotNan::new(percentile).unwrap(),
+        runtime_scaler: runtime
+            .as_ref()
+            .map(|runtime| time_scaler(runtime, runtime_reference_ms)),
+        bias: Some(*user_bias),
+        score: skewed_percentile,
+    }
 }
 
 /// Perform thompson sampling and pick a single entry. Ignores runtime.
-pub fn thompson_sampling(entries: &[&ThompsonInfo], user_biases: &[&NotNan<f64>]) -> Option<usize> {
-    let mut selected_entry_index: Option<usize> = None;
-    let mut selected_entry_percentile: NotNan<f64> = NotNan::new(-1.0).unwrap();
-    for (index, entry) in entries.iter().enumerate() {
-        let mut percentile = thompson_step(entry.interesting, entry.uninteresting);
-        debug!(
-            "Total percentage of area at random point {:.2}%",
-            percentile * 100.,
-        );
-        percentile *= f64::from(*user_biases[index]);
-
-        if percentile > selected_entry_percentile {
-            selected_entry_index = Some(index);
-            selected_entry_percentile = percentile
-        }
-    }
-    selected_entry_index
+pub fn thompson_sampling(
+    entries: &[&ThompsonInfo],
+    priors: &[&Prior],
+    user_biases: &[&NotNan<f64>],
+    rng: &mut impl Rng,
+) -> Option<usize> {
+    best_score(&sample_breakdowns(entries, priors, Some(user_biases), rng))
 }
 
 /// Returns a vector mapping the nth selected entry to its index.
 ///
 /// Ex. [0, 2, 1]: The first element was ranked first, the third second, and second third.
-pub fn thompson_ranking(entries: &[&ThompsonInfo]) -> Vec<usize> {
-    let mut percentiles_index_mapping = entries
-        .iter()
-        .enumerate()
-        .map(|(idx, entry)| (idx, thompson_step(entry.interesting, entry.uninteresting)))
-        .collect::<Vec<_>>();
+pub fn thompson_ranking(
+    entries: &[&ThompsonInfo],
+    priors: &[&Prior],
+    rng: &mut impl Rng,
+) -> Vec<usize> {
+    thompson_scores(entries, priors, rng)
+        .into_iter()
+        .map(|(index, _score)| index)
+        .collect()
+}
 
-    percentiles_index_mapping.sort_by_key(|&(_, percentile)| percentile);
+/// Like `thompson_ranking`, but keeps the percentile each entry was ranked by.
+pub fn thompson_scores(
+    entries: &[&ThompsonInfo],
+    priors: &[&Prior],
+    rng: &mut impl Rng,
+) -> Vec<(usize, NotNan<f64>)> {
+    let mut percentiles_index_mapping = score_entries(entries.len(), rng, |idx, random_float| {
+        thompson_step(entries[idx], priors[idx], random_float)
+    });
 
+    sort_by_score(&mut percentiles_index_mapping);
     percentiles_index_mapping
-        .iter()
-        .rev()
-        .map(|(index, _percentile)| *index)
-        .collect()
 }
 
-fn thompson_step(interesting: u64, uninteresting: u64) -> NotNan<f64> {
-    let mut rng = rand::thread_rng();
-    // Random number from 0.0 to 1.0 inclusive
-    let random_float: f64 = rng.gen_range(0.0..1.0);
+/// Sample an entry's beta distribution at `random_float`, a point from 0.0 to 1.0.
+fn thompson_step(entry: &ThompsonInfo, prior: &Prior, random_float: f64) -> NotNan<f64> {
     debug!("Percentile to sample: {}", random_float);
-    let percentile = puruspe::invbetai(
-        random_float,
-        (interesting + 1) as f64,
-        (uninteresting + 1) as f64,
-    );
+    let alpha = entry.interesting + prior.alpha;
+    let beta = entry.uninteresting + prior.beta;
+    let percentile = cached_invbetai(random_float, alpha, beta);
     debug!(
         "Total percentage of area at point {:.4}: {:.2}% B({}, {})",
         random_float * 100.0,
         percentile,
-        (interesting + 1) as f64,
-        (uninteresting + 1) as f64
+        alpha,
+        beta
     );
     NotNan::new(percentile).unwrap()
 }
 
+/// Returns the mean of the beta distribution, the expected interesting rate of an entry.
+pub fn posterior_mean(entry: &ThompsonInfo) -> f64 {
+    posterior_mean_with_prior(entry, &Prior::default())
+}
+
+pub fn posterior_mean_with_prior(entry: &ThompsonInfo, prior: &Prior) -> f64 {
+    let alpha = entry.interesting + prior.alpha;
+    let beta = entry.uninteresting + prior.beta;
+    alpha / (alpha + beta)
+}
+
+/// Round robin through entries starting at `start`, skipping entries whose posterior mean is
+/// below `skip_below` times the best posterior mean.
+///
+/// The best entry is never skipped, so this only returns `None` when there are no entries.
+pub fn interleave(
+    entries: &[&ThompsonInfo],
+    priors: &[&Prior],
+    start: usize,
+    skip_below: f64,
+) -> Option<usize> {
+    let means = entries
+        .iter()
+        .enumerate()
+        .map(|(index, entry)| posterior_mean_with_prior(entry, priors[index]))
+        .collect::<Vec<_>>();
+    let best = means.iter().copied().fold(f64::MIN, f64::max);
+
+    (0..entries.len())
+        .map(|offset| (start + offset) % entries.len())
+        .find(|index| means[*index] >= skip_below * best)
+}
+
+/// Fraction of an entry's results that were interesting, or `None` if it has no results yet.
+pub fn empirical_rate(entry: &ThompsonInfo) -> Option<f64> {
+    let total = entry.interesting + entry.uninteresting;
+    (total > 0.0).then(|| entry.interesting / total)
+}
+
+/// With probability `epsilon` pick a uniformly random entry, otherwise the entry with the highest
+/// empirical interesting rate.
+///
+/// Entries without results are picked first so every entry has a rate to compare. Ties go to the
+/// earliest entry.
+pub fn epsilon_greedy(
+    entries: &[&ThompsonInfo],
+    epsilon: f64,
+    rng: &mut impl Rng,
+) -> Option<usize> {
+    if entries.is_empty() {
+        return None;
+    }
+
+    if rng.gen_bool(epsilon) {
+        let selected = rng.gen_range(0..entries.len());
+        debug!("Exploring entry: {}", selected);
+        return Some(selected);
+    }
+
+    greedy_ranking(entries).first().copied()
+}
+
+/// Returns a vector mapping the nth entry by empirical interesting rate to its index.
+///
+/// Entries without results rank first, as `epsilon_greedy` would pick them first.
+pub fn greedy_ranking(entries: &[&ThompsonInfo]) -> Vec<usize> {
+    let mut ranking = (0..entries.len()).collect::<Vec<_>>();
+    // Stable sort keeps ties in config order.
+    ranking.sort_by(|&a, &b| {
+        let rate = |index: usize| empirical_rate(entries[index]).unwrap_or(f64::INFINITY);
+        rate(b).total_cmp(&rate(a))
+    });
+    ranking
+}
+
+/// Upper confidence bound of an entry: its interesting rate plus `sqrt(2 ln(total_runs) / runcount)`.
+///
+/// Entries that have never run are infinite so they're tried first. Runs without a result, e.g. timeouts,
+/// count towards `runcount` but not the rate.
+pub fn ucb1_score(entry: &ThompsonInfo, runcount: u64, total_runs: u64) -> f64 {
+    if runcount == 0 {
+        return f64::INFINITY;
+    }
+    let mean = empirical_rate(entry).unwrap_or(0.0);
+    mean + (2.0 * (total_runs as f64).ln() / runcount as f64).sqrt()
+}
+
+/// Pick the entry with the highest UCB1 score. Deterministic, ties go to the earliest entry.
+pub fn ucb1(entries: &[&ThompsonInfo], runcounts: &[u64], total_runs: u64) -> Option<usize> {
+    let mut selected_entry_index: Option<usize> = None;
+    let mut selected_entry_score = f64::NEG_INFINITY;
+    for (index, entry) in entries.iter().enumerate() {
+        let score = ucb1_score(entry, runcounts[index], total_runs);
+        debug!("UCB1 score of entry {}: {}", index, score);
+        if score > selected_entry_score || selected_entry_index.is_none() {
+            selected_entry_index = Some(index);
+            selected_entry_score = score;
+        }
+    }
+    selected_entry_index
+}
+
+/// Pick candidates in turn: the position in `candidates`, ascending script indices, of the first one after
+/// `last`, the script picked last time, wrapping around to the first.
+pub fn round_robin(candidates: &[usize], last: Option<usize>) -> Option<usize> {
+    if candidates.is_empty() {
+        return None;
+    }
+    Some(
+        last.and_then(|last| candidates.iter().position(|index| *index > last))
+            .unwrap_or(0),
+    )
+}
+
+/// Pick a uniformly random entry.
+pub fn uniform_random(entries: &[&ThompsonInfo], rng: &mut impl Rng) -> Option<usize> {
+    if entries.is_empty() {
+        return None;
+    }
+    Some(rng.gen_range(0..entries.len()))
+}
+
+/// Returns a vector mapping the nth entry by UCB1 score to its index.
+pub fn ucb1_ranking(entries: &[&ThompsonInfo], runcounts: &[u64], total_runs: u64) -> Vec<usize> {
+    let mut ranking = (0..entries.len()).collect::<Vec<_>>();
+    ranking.sort_by(|&a, &b| {
+        let score = |index: usize| ucb1_score(entries[index], runcounts[index], total_runs);
+        score(b).total_cmp(&score(a))
+    });
+    ranking
+}
+
+/// Returns the width of the central 90% credible interval of an entry's beta distribution.
+pub fn credible_interval_width(entry: &ThompsonInfo, prior: &Prior) -> f64 {
+    let alpha = entry.interesting + prior.alpha;
+    let beta = entry.uninteresting + prior.beta;
+    puruspe::invbetai(0.95, alpha, beta) - puruspe::invbetai(0.05, alpha, beta)
+}
+
+/// Pick the entry with the widest credible interval, if any interval is at least `target_width` wide.
+///
+/// Used to warm up uncertain entries before handing off to thompson sampling. Only classified runs narrow an
+/// interval, so an entry that has run more often than it would take a fully classified one to get below
+/// `target_width` is left out. Its runs keep timing out or ending with unknown exit statuses, and warming
+/// it up further would keep every other entry from running.
+pub fn widest_interval(
+    entries: &[&ThompsonInfo],
+    priors: &[&Prior],
+    runcounts: &[u64],
+    target_width: f64,
+) -> Option<usize> {
+    // The 90% interval of n classified runs is at most about 2 * 1.645 * sqrt(0.25 / n) wide.
+    let max_warmup_runs = (1.645 / target_width).powi(2).ceil() as u64;
+    entries
+        .iter()
+        .enumerate()
+        .filter(|(index, _)| runcounts[*index] < max_warmup_runs)
+        .map(|(index, entry)| (index, credible_interval_width(entry, priors[index])))
+        .filter(|(_, width)| *width >= target_width)
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(index, _)| index)
+}
+
 /// Returns the nth percentile of the beta distribution.
 pub fn dist_area_at_percentile(entry: &ThompsonInfo, area: f64) -> f64 {
-    puruspe::invbetai(
-        area,
-        (entry.interesting + 1) as f64,
-        (entry.uninteresting + 1) as f64,
-    )
+    puruspe::invbetai(area, entry.interesting + 1.0, entry.uninteresting + 1.0)
+}
+
+/// Returns `n` `(x, density)` points of an entry's beta distribution, at the midpoints of `n` equal
+/// slices of `[0, 1]`.
+///
+/// Each density is the probability mass of its slice, from the CDF, divided by the slice's width, so the
+/// curve stays finite where the density itself is infinite and always integrates to 1.
+pub fn sample_posterior_curve(entry: &ThompsonInfo, n: usize) -> Vec<(f64, f64)> {
+    let alpha = entry.interesting + 1.0;
+    let beta = entry.uninteresting + 1.0;
+    let width = 1.0 / n as f64;
+    (0..n)
+        .map(|index| {
+            let start = index as f64 * width;
+            let end = (index + 1) as f64 * width;
+            let mass = puruspe::betai(alpha, beta, end) - puruspe::betai(alpha, beta, start);
+            (start + width / 2.0, mass / width)
+        })
+        .collect()
+}
+
+/// Monte Carlo estimate of the probability that `challenger`'s interesting rate is above `champion`'s.
+pub fn probability_better(
+    challenger: &ThompsonInfo,
+    champion: &ThompsonInfo,
+    samples: usize,
+    rng: &mut impl Rng,
+) -> f64 {
+    let wins = (0..samples)
+        .filter(|_| {
+            let challenger_rate = dist_area_at_percentile(challenger, rng.gen_range(0.0..1.0));
+            let champion_rate = dist_area_at_percentile(champion, rng.gen_range(0.0..1.0));
+            challenger_rate > champion_rate
+        })
+        .count();
+    wins as f64 / samples as f64
+}
+
+/// Monte Carlo estimate of how likely each of `count` entries is to be selected, from `draws` calls of
+/// `select`. A draw that selects nothing counts for no entry.
+pub fn selection_probabilities(
+    count: usize,
+    draws: usize,
+    mut select: impl FnMut() -> Option<usize>,
+) -> Vec<f64> {
+    let mut wins = vec![0; count];
+    for _ in 0..draws {
+        if let Some(index) = select() {
+            wins[index] += 1;
+        }
+    }
+    wins.into_iter()
+        .map(|win| win as f64 / draws as f64)
+        .collect()
+}
+
+#[test]
+fn test_posterior_curve_integrates_to_one() {
+    for (interesting, uninteresting) in [(0.0, 0.0), (3.0, 7.0), (40.0, 2.0), (0.0, 200.0)] {
+        let entry = ThompsonInfo {
+            interesting,
+            uninteresting,
+        };
+        let curve = sample_posterior_curve(&entry, 100);
+        assert_eq!(curve.len(), 100);
+        assert!((curve[0].0 - 0.005).abs() < 1e-9);
+
+        let area = curve.iter().map(|(_, density)| density * 0.01).sum::<f64>();
+        assert!((area - 1.0).abs() < 1e-6, "area {area}");
+    }
+
+    // The density peaks near the observed rate.
+    let entry = ThompsonInfo {
+        interesting: 30.0,
+        uninteresting: 70.0,
+    };
+    let curve = sample_posterior_curve(&entry, 100);
+    let peak = curve
+        .iter()
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .unwrap();
+    assert!((peak.0 - 0.3).abs() < 0.02, "peak at {}", peak.0);
+}
+
+#[test]
+fn test_ranking_ties_keep_input_order() {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    let entry = ThompsonInfo {
+        interesting: 2.0,
+        uninteresting: 2.0,
+    };
+    let bias = NotNan::new(1.0).unwrap();
+    let reference = NotNan::new(1000.0).unwrap();
+
+    // Scripts with an unknown runtime all score the maximum, ahead of the one with a known runtime.
+    for seed in 0..20 {
+        let ranking = thompson_ranking_bias_runtime(
+            &[&entry, &entry, &entry],
+            &RuntimeScoring {
+                priors: &[&Prior::default(), &Prior::default(), &Prior::default()],
+                runtimes: &[&None, &Some(NotNan::new(10.0).unwrap()), &None],
+                runcounts: &[0, 5, 0],
+                user_biases: &[&bias, &bias, &bias],
+                runtime_reference_ms: reference,
+                min_samples: 1,
+            },
+            &mut StdRng::seed_from_u64(seed),
+        );
+        assert_eq!(ranking, vec![0, 2, 1]);
+    }
 }
 
 #[test]
 fn test_thompson_sampling_none() {
-    assert_eq!(thompson_sampling(&vec![], &vec![]), None);
+    assert_eq!(
+        thompson_sampling(&[], &[], &[], &mut rand::thread_rng()),
+        None
+    );
 }
 
 #[test]
@@ -194,10 +696,12 @@ fn test_thompson_sampling_one() {
     assert_eq!(
         thompson_sampling(
             &[&ThompsonInfo {
-                interesting: 0,
-                uninteresting: 0
+                interesting: 0.0,
+                uninteresting: 0.0
             }],
-            &[&NotNan::new(1.0).unwrap(), &NotNan::new(1.0).unwrap()]
+            &[&Prior::default()],
+            &[&NotNan::new(1.0).unwrap(), &NotNan::new(1.0).unwrap()],
+            &mut rand::thread_rng()
         ),
         Some(0)
     );
@@ -209,15 +713,17 @@ fn test_thompson_sampling_prefer_interesting() {
         thompson_sampling(
             &[
                 &ThompsonInfo {
-                    interesting: 0,
-                    uninteresting: 100,
+                    interesting: 0.0,
+                    uninteresting: 100.0,
                 },
                 &ThompsonInfo {
-                    interesting: 100,
-                    uninteresting: 0
+                    interesting: 100.0,
+                    uninteresting: 0.0
                 }
             ],
-            &[&NotNan::new(1.0).unwrap(), &NotNan::new(1.0).unwrap()]
+            &[&Prior::default(), &Prior::default()],
+            &[&NotNan::new(1.0).unwrap(), &NotNan::new(1.0).unwrap()],
+            &mut rand::thread_rng()
         ),
         Some(1)
     );
@@ -229,19 +735,26 @@ fn test_thompson_sampling_bias_prefer_fast() {
         thompson_sampling_bias_runtime(
             &[
                 &ThompsonInfo {
-                    interesting: 100,
-                    uninteresting: 100
+                    interesting: 100.0,
+                    uninteresting: 100.0
                 },
                 &ThompsonInfo {
-                    interesting: 100,
-                    uninteresting: 100
+                    interesting: 100.0,
+                    uninteresting: 100.0
                 }
             ],
-            &[
-                &Some(NotNan::new(1.0).unwrap()),
-                &Some(NotNan::new(100.0).unwrap())
-            ],
-            &[&NotNan::new(1.0).unwrap(), &NotNan::new(1.0).unwrap()]
+            &RuntimeScoring {
+                priors: &[&Prior::default(), &Prior::default()],
+                runtimes: &[
+                    &Some(NotNan::new(1.0).unwrap()),
+                    &Some(NotNan::new(100.0).unwrap())
+                ],
+                runcounts: &[200, 200],
+                user_biases: &[&NotNan::new(1.0).unwrap(), &NotNan::new(1.0).unwrap()],
+                runtime_reference_ms: NotNan::new(DEFAULT_RUNTIME_REFERENCE_MS).unwrap(),
+                min_samples: 1
+            },
+            &mut rand::thread_rng()
         ),
         Some(0)
     );
@@ -253,17 +766,657 @@ fn test_thompson_sampling_bias_prefer_unknown() {
         thompson_sampling_bias_runtime(
             &[
                 &ThompsonInfo {
-                    interesting: 100,
-                    uninteresting: 0
+                    interesting: 100.0,
+                    uninteresting: 0.0
                 },
                 &ThompsonInfo {
-                    interesting: 0,
-                    uninteresting: 0
+                    interesting: 0.0,
+                    uninteresting: 0.0
                 }
             ],
-            &[&Some(NotNan::new(1.0).unwrap()), &None],
-            &[&NotNan::new(1.0).unwrap(), &NotNan::new(1.0).unwrap()]
+            &RuntimeScoring {
+                priors: &[&Prior::default(), &Prior::default()],
+                runtimes: &[&Some(NotNan::new(1.0).unwrap()), &None],
+                runcounts: &[100, 0],
+                user_biases: &[&NotNan::new(1.0).unwrap(), &NotNan::new(1.0).unwrap()],
+                runtime_reference_ms: NotNan::new(DEFAULT_RUNTIME_REFERENCE_MS).unwrap(),
+                min_samples: 1
+            },
+            &mut rand::thread_rng()
         ),
         Some(1)
     );
 }
+
+#[test]
+fn test_min_samples_ignores_early_runtime() {
+    let entry = ThompsonInfo {
+        interesting: 1.0,
+        uninteresting: 1.0,
+    };
+    let slow = Some(NotNan::new(10_000.0).unwrap());
+    let reference = NotNan::new(DEFAULT_RUNTIME_REFERENCE_MS).unwrap();
+    let bias = NotNan::new(1.0).unwrap();
+    let score = |runtime: &Option<NotNan<f64>>, runcount, min_samples| {
+        let scoring = RuntimeScoring {
+            priors: &[&Prior::default()],
+            runtimes: &[runtime],
+            runcounts: &[runcount],
+            user_biases: &[&bias],
+            runtime_reference_ms: reference,
+            min_samples,
+        };
+        thompson_step_bias_runtime(&entry, &scoring, 0, 0.5)
+    };
+
+    // Before min_samples runs the script is scored as if it took the reference runtime.
+    let neutral = score(&Some(reference), 1, 1);
+    assert_eq!(score(&slow, 1, 3), neutral);
+    assert!(score(&slow, 1, 1) < neutral);
+    assert!(score(&slow, 3, 3) < neutral);
+}
+
+#[test]
+fn test_min_samples_keeps_slow_first_run_in_rotation() {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    let entries = [
+        &ThompsonInfo {
+            interesting: 5.0,
+            uninteresting: 5.0,
+        },
+        &ThompsonInfo {
+            interesting: 5.0,
+            uninteresting: 5.0,
+        },
+    ];
+    let priors = [&Prior::default(), &Prior::default()];
+    // The first script's only run so far was slow.
+    let slow = Some(NotNan::new(10_000.0).unwrap());
+    let fast = Some(NotNan::new(100.0).unwrap());
+    let runtimes = [&slow, &fast];
+    let runcounts = [1, 10];
+    let bias = NotNan::new(1.0).unwrap();
+    let biases = [&bias, &bias];
+    let reference = NotNan::new(DEFAULT_RUNTIME_REFERENCE_MS).unwrap();
+
+    let mut rng = StdRng::seed_from_u64(7);
+    let mut picks_of_slow = |min_samples| {
+        (0..1000)
+            .filter(|_| {
+                thompson_sampling_bias_runtime(
+                    &entries,
+                    &RuntimeScoring {
+                        priors: &priors,
+                        runtimes: &runtimes,
+                        runcounts: &runcounts,
+                        user_biases: &biases,
+                        runtime_reference_ms: reference,
+                        min_samples,
+                    },
+                    &mut rng,
+                ) == Some(0)
+            })
+            .count()
+    };
+
+    assert_eq!(picks_of_slow(1), 0);
+    assert!(picks_of_slow(3) > 300);
+}
+
+#[test]
+fn test_percentile_interval_narrows_with_samples() {
+    // The same 0.6 observed rate, sampled 10, 100 and 1000 times.
+    let widths = [(6.0, 4.0), (60.0, 40.0), (600.0, 400.0)].map(|(interesting, uninteresting)| {
+        let entry = ThompsonInfo {
+            interesting,
+            uninteresting,
+        };
+        let (lo, hi) = (
+            dist_area_at_percentile(&entry, 0.05),
+            dist_area_at_percentile(&entry, 0.95),
+        );
+        assert!(lo < 0.6 && 0.6 < hi);
+        hi - lo
+    });
+
+    assert!(widths[0] > widths[1] && widths[1] > widths[2]);
+    assert!(widths[2] < 0.06);
+}
+
+#[test]
+fn test_apply_bias() {
+    let score = NotNan::new(0.5).unwrap();
+    assert_eq!(apply_bias(score, &NotNan::new(2.0).unwrap()), 1.0);
+    assert_eq!(
+        apply_bias(score, &NotNan::new(0.0).unwrap()),
+        0.5 * MIN_BIAS
+    );
+    assert_eq!(
+        apply_bias(score, &NotNan::new(-3.0).unwrap()),
+        0.5 * MIN_BIAS
+    );
+}
+
+/// Which entry each of `draws` seeded selections picks, with and without runtime biasing.
+#[cfg(test)]
+fn picks_in_both_modes(
+    entries: &[&ThompsonInfo],
+    biases: &[&NotNan<f64>],
+    draws: usize,
+) -> (Vec<Option<usize>>, Vec<Option<usize>>) {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    let prior = Prior::default();
+    let priors = vec![&prior; entries.len()];
+    // Every script takes the reference runtime, so runtime scales every score by 1.
+    let reference = NotNan::new(DEFAULT_RUNTIME_REFERENCE_MS).unwrap();
+    let runtime = Some(reference);
+    let runtimes = vec![&runtime; entries.len()];
+    let runcounts = vec![20; entries.len()];
+
+    let mut rng = StdRng::seed_from_u64(3);
+    let ignoring_runtime = (0..draws)
+        .map(|_| thompson_sampling(entries, &priors, biases, &mut rng))
+        .collect();
+    let mut rng = StdRng::seed_from_u64(3);
+    let biasing_runtime = (0..draws)
+        .map(|_| {
+            thompson_sampling_bias_runtime(
+                entries,
+                &RuntimeScoring {
+                    priors: &priors,
+                    runtimes: &runtimes,
+                    runcounts: &runcounts,
+                    user_biases: biases,
+                    runtime_reference_ms: reference,
+                    min_samples: 1,
+                },
+                &mut rng,
+            )
+        })
+        .collect();
+
+    (ignoring_runtime, biasing_runtime)
+}
+
+#[test]
+fn test_bias_zero_in_both_modes() {
+    let good = ThompsonInfo {
+        interesting: 100.0,
+        uninteresting: 0.0,
+    };
+    let bad = ThompsonInfo {
+        interesting: 0.0,
+        uninteresting: 100.0,
+    };
+    let zero = NotNan::new(0.0).unwrap();
+    let one = NotNan::new(1.0).unwrap();
+
+    // A zero bias loses to any positive bias, however good its results.
+    let (ignoring_runtime, biasing_runtime) =
+        picks_in_both_modes(&[&good, &bad], &[&zero, &one], 200);
+    assert_eq!(ignoring_runtime, vec![Some(1); 200]);
+    assert_eq!(biasing_runtime, ignoring_runtime);
+
+    // Zero bias scripts are still ranked by their results among themselves.
+    let (ignoring_runtime, biasing_runtime) =
+        picks_in_both_modes(&[&bad, &good], &[&zero, &zero], 200);
+    assert_eq!(ignoring_runtime, vec![Some(1); 200]);
+    assert_eq!(biasing_runtime, ignoring_runtime);
+}
+
+#[test]
+fn test_bias_two_in_both_modes() {
+    let entry = ThompsonInfo {
+        interesting: 10.0,
+        uninteresting: 10.0,
+    };
+    let two = NotNan::new(2.0).unwrap();
+    let one = NotNan::new(1.0).unwrap();
+
+    let (ignoring_runtime, biasing_runtime) =
+        picks_in_both_modes(&[&entry, &entry], &[&one, &two], 1000);
+    assert_eq!(biasing_runtime, ignoring_runtime);
+    let doubled_wins = ignoring_runtime
+        .iter()
+        .filter(|pick| **pick == Some(1))
+        .count();
+    assert!(doubled_wins > 900);
+}
+
+#[test]
+fn test_posterior_mean() {
+    assert_eq!(
+        posterior_mean(&ThompsonInfo {
+            interesting: 0.0,
+            uninteresting: 0.0
+        }),
+        0.5
+    );
+    assert_eq!(
+        posterior_mean(&ThompsonInfo {
+            interesting: 2.0,
+            uninteresting: 6.0
+        }),
+        0.3
+    );
+}
+
+#[test]
+fn test_thompson_sampling_prior_overlay() {
+    let fresh = ThompsonInfo {
+        interesting: 0.0,
+        uninteresting: 0.0,
+    };
+    assert_eq!(
+        thompson_sampling(
+            &[&fresh, &fresh],
+            &[
+                &Prior {
+                    alpha: 1.0,
+                    beta: 1000.0
+                },
+                &Prior {
+                    alpha: 1000.0,
+                    beta: 1.0
+                }
+            ],
+            &[&NotNan::new(1.0).unwrap(), &NotNan::new(1.0).unwrap()],
+            &mut rand::thread_rng()
+        ),
+        Some(1)
+    );
+}
+
+#[test]
+fn test_time_scaler_references() {
+    let runtime = NotNan::new(1_000.0).unwrap();
+    // References of 100ms, 100s and 100m.
+    assert_eq!(time_scaler(&runtime, NotNan::new(100.0).unwrap()), 0.1);
+    assert_eq!(
+        time_scaler(&runtime, NotNan::new(100_000.0).unwrap()),
+        100.0
+    );
+    assert_eq!(
+        time_scaler(&runtime, NotNan::new(6_000_000.0).unwrap()),
+        6_000.0
+    );
+}
+
+#[test]
+fn test_runtime_reference_keeps_bias_equivalence() {
+    // A bias of 5 matches a script that runs 5x as fast, whatever the reference.
+    let slow = Some(NotNan::new(5_000.0).unwrap());
+    let fast = Some(NotNan::new(1_000.0).unwrap());
+    let point = NotNan::new(0.5).unwrap();
+    for reference in [100.0, 100_000.0, 6_000_000.0] {
+        let reference = NotNan::new(reference).unwrap();
+        let biased_slow = skew_percentile(point, &slow, &NotNan::new(5.0).unwrap(), reference);
+        let fast = skew_percentile(point, &fast, &NotNan::new(1.0).unwrap(), reference);
+        assert!(float_cmp::approx_eq!(f64, *biased_slow, *fast, ulps = 4));
+    }
+}
+
+#[test]
+fn test_widest_interval() {
+    let entries = [
+        &ThompsonInfo {
+            interesting: 10.0,
+            uninteresting: 10.0,
+        },
+        &ThompsonInfo {
+            interesting: 0.0,
+            uninteresting: 0.0,
+        },
+        &ThompsonInfo {
+            interesting: 100.0,
+            uninteresting: 100.0,
+        },
+    ];
+    let priors = [&Prior::default(), &Prior::default(), &Prior::default()];
+    let runcounts = [20, 0, 200];
+
+    // The fresh entry is the most uncertain.
+    assert_eq!(widest_interval(&entries, &priors, &runcounts, 0.0), Some(1));
+
+    // Once it's narrower than the target, the next widest is chosen.
+    let sampled = ThompsonInfo {
+        interesting: 50.0,
+        uninteresting: 50.0,
+    };
+    assert_eq!(
+        widest_interval(
+            &[entries[0], &sampled, entries[2]],
+            &priors,
+            &[20, 100, 200],
+            0.0
+        ),
+        Some(0)
+    );
+
+    // Everything is narrower than the target, so warmup is over.
+    assert_eq!(widest_interval(&entries, &priors, &runcounts, 0.95), None);
+}
+
+#[test]
+fn test_widest_interval_skips_entries_without_classified_runs() {
+    let unclassified = ThompsonInfo {
+        interesting: 0.0,
+        uninteresting: 0.0,
+    };
+    let entries = [
+        &unclassified,
+        &ThompsonInfo {
+            interesting: 5.0,
+            uninteresting: 5.0,
+        },
+    ];
+    let priors = [&Prior::default(), &Prior::default()];
+
+    // Fresh, so it is warmed up first.
+    assert_eq!(widest_interval(&entries, &priors, &[0, 10], 0.2), Some(0));
+
+    // 100 runs would have brought a classified entry below a width of 0.2, but every one of them timed
+    // out, so the other entry is warmed up instead.
+    assert_eq!(widest_interval(&entries, &priors, &[100, 10], 0.2), Some(1));
+}
+
+#[test]
+fn test_interleave_skip_below_zero_is_round_robin() {
+    let entries = [
+        &ThompsonInfo {
+            interesting: 0.0,
+            uninteresting: 100.0,
+        },
+        &ThompsonInfo {
+            interesting: 100.0,
+            uninteresting: 0.0,
+        },
+        &ThompsonInfo {
+            interesting: 5.0,
+            uninteresting: 5.0,
+        },
+    ];
+    let priors = [&Prior::default(), &Prior::default(), &Prior::default()];
+
+    let mut selections = vec![];
+    let mut start = 0;
+    for _ in 0..6 {
+        let selected = interleave(&entries, &priors, start, 0.0).unwrap();
+        selections.push(selected);
+        start = selected + 1;
+    }
+    assert_eq!(selections, vec![0, 1, 2, 0, 1, 2]);
+}
+
+#[test]
+fn test_interleave_skips_weak_entries() {
+    let entries = [
+        &ThompsonInfo {
+            interesting: 0.0,
+            uninteresting: 100.0,
+        },
+        &ThompsonInfo {
+            interesting: 100.0,
+            uninteresting: 0.0,
+        },
+        &ThompsonInfo {
+            interesting: 50.0,
+            uninteresting: 50.0,
+        },
+    ];
+    let priors = [&Prior::default(), &Prior::default(), &Prior::default()];
+
+    assert_eq!(interleave(&entries, &priors, 0, 0.5), Some(1));
+    assert_eq!(interleave(&entries, &priors, 2, 0.5), Some(2));
+    assert_eq!(interleave(&entries, &priors, 2, 0.9), Some(1));
+    assert_eq!(interleave(&[], &[], 0, 0.5), None);
+}
+
+#[test]
+fn test_probability_better() {
+    let strong = ThompsonInfo {
+        interesting: 40.0,
+        uninteresting: 10.0,
+    };
+    let weak = ThompsonInfo {
+        interesting: 5.0,
+        uninteresting: 45.0,
+    };
+    let mut rng = rand::thread_rng();
+
+    assert!(probability_better(&strong, &weak, 1000, &mut rng) > 0.99);
+    assert!(probability_better(&weak, &strong, 1000, &mut rng) < 0.01);
+    let even = probability_better(&strong, &strong, 1000, &mut rng);
+    assert!((0.4..0.6).contains(&even));
+}
+
+#[test]
+fn test_selection_probabilities_of_dominant_entry() {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    let strong = ThompsonInfo {
+        interesting: 90.0,
+        uninteresting: 10.0,
+    };
+    let weak = ThompsonInfo {
+        interesting: 1.0,
+        uninteresting: 99.0,
+    };
+    let entries = [&weak, &strong];
+    let prior = Prior::default();
+    let bias = NotNan::new(1.0).unwrap();
+    let mut rng = StdRng::seed_from_u64(3);
+
+    let probabilities = selection_probabilities(2, 10_000, || {
+        thompson_sampling(&entries, &[&prior, &prior], &[&bias, &bias], &mut rng)
+    });
+    assert!(probabilities[1] > 0.99, "{probabilities:?}");
+    assert!((probabilities.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+}
+
+#[test]
+fn test_round_robin_and_uniform_random() {
+    let info = ThompsonInfo {
+        interesting: 9.0,
+        uninteresting: 1.0,
+    };
+    let entries = [&info, &info, &info];
+    let mut rng = rand::thread_rng();
+
+    assert_eq!(round_robin(&[], Some(5)), None);
+    let mut last = None;
+    let picks = (0..6)
+        .map(|_| {
+            let pick = round_robin(&[0, 1, 2], last).unwrap();
+            last = Some(pick);
+            pick
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(picks, vec![0, 1, 2, 0, 1, 2]);
+    // The turn passes to the next candidate after the last pick, even if that pick is no longer one.
+    assert_eq!(round_robin(&[0, 2, 5], Some(3)), Some(2));
+    assert_eq!(round_robin(&[0, 2, 5], Some(5)), Some(0));
+
+    assert_eq!(uniform_random(&[], &mut rng), None);
+    let mut counts = [0; 3];
+    for _ in 0..300 {
+        counts[uniform_random(&entries, &mut rng).unwrap()] += 1;
+    }
+    assert!(counts.iter().all(|count| *count > 50), "{counts:?}");
+}
+
+#[test]
+fn test_epsilon_greedy_exploits_best_rate() {
+    let entries = [
+        &ThompsonInfo {
+            interesting: 2.0,
+            uninteresting: 8.0,
+        },
+        &ThompsonInfo {
+            interesting: 8.0,
+            uninteresting: 2.0,
+        },
+        &ThompsonInfo {
+            interesting: 5.0,
+            uninteresting: 5.0,
+        },
+    ];
+    let mut rng = rand::thread_rng();
+
+    assert_eq!(epsilon_greedy(&[], 0.1, &mut rng), None);
+    for _ in 0..100 {
+        assert_eq!(epsilon_greedy(&entries, 0.0, &mut rng), Some(1));
+    }
+
+    // An entry without results is tried before any rate is trusted.
+    let fresh = ThompsonInfo {
+        interesting: 0.0,
+        uninteresting: 0.0,
+    };
+    assert_eq!(
+        epsilon_greedy(&[entries[0], entries[1], &fresh], 0.0, &mut rng),
+        Some(2)
+    );
+    assert_eq!(greedy_ranking(&entries), vec![1, 2, 0]);
+}
+
+#[test]
+fn test_epsilon_greedy_converges_on_high_reward_arm() {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    let rates = [0.2, 0.8, 0.5];
+    let mut infos = rates
+        .iter()
+        .map(|_| ThompsonInfo {
+            interesting: 0.0,
+            uninteresting: 0.0,
+        })
+        .collect::<Vec<_>>();
+    // Seeded, so an unlucky run of early pulls can't fail the test.
+    let mut rng = StdRng::seed_from_u64(5);
+
+    let steps = 2000;
+    let mut best_pulls = 0;
+    for _ in 0..steps {
+        let entries = infos.iter().collect::<Vec<_>>();
+        let selected = epsilon_greedy(&entries, 0.05, &mut rng).unwrap();
+        if rng.gen_bool(rates[selected]) {
+            infos[selected].interesting += 1.0;
+        } else {
+            infos[selected].uninteresting += 1.0;
+        }
+        if selected == 1 {
+            best_pulls += 1;
+        }
+    }
+
+    assert!(
+        best_pulls > steps * 3 / 4,
+        "best arm pulled {best_pulls} times"
+    );
+}
+
+#[test]
+fn test_ucb1_exact_choice() {
+    let entries = [
+        &ThompsonInfo {
+            interesting: 6.0,
+            uninteresting: 4.0,
+        },
+        &ThompsonInfo {
+            interesting: 1.0,
+            uninteresting: 1.0,
+        },
+        &ThompsonInfo {
+            interesting: 9.0,
+            uninteresting: 9.0,
+        },
+    ];
+    let runcounts = [10, 2, 18];
+
+    // 0.6 + sqrt(2 ln 30 / 10) = 1.4248, 0.5 + sqrt(2 ln 30 / 2) = 2.3442, 0.5 + sqrt(2 ln 30 / 18) = 1.1147
+    assert!((ucb1_score(entries[0], 10, 30) - 1.4248).abs() < 1e-4);
+    assert_eq!(ucb1(&entries, &runcounts, 30), Some(1));
+    assert_eq!(ucb1_ranking(&entries, &runcounts, 30), vec![1, 0, 2]);
+
+    // With enough runs the bonus shrinks and the best rate wins.
+    assert_eq!(ucb1(&entries, &[1000, 1000, 1000], 3000), Some(0));
+
+    // Unplayed entries come first.
+    assert_eq!(ucb1(&entries, &[10, 2, 0], 12), Some(2));
+    assert_eq!(ucb1(&[], &[], 0), None);
+}
+
+#[cfg(test)]
+fn synthetic_entries(count: u64) -> Vec<ThompsonInfo> {
+    (0..count)
+        .map(|i| ThompsonInfo {
+            interesting: (i % 7) as f64,
+            uninteresting: (i % 101) as f64,
+        })
+        .collect()
+}
+
+#[test]
+fn test_parallel_ranking_is_a_permutation() {
+    let entries = synthetic_entries(PARALLEL_RANKING_THRESHOLD as u64 * 2);
+    let entries = entries.iter().collect::<Vec<_>>();
+
+    let prior = Prior::default();
+    let priors = vec![&prior; entries.len()];
+
+    let mut ranking = thompson_ranking(&entries, &priors, &mut rand::thread_rng());
+    ranking.sort();
+    assert_eq!(ranking, (0..entries.len()).collect::<Vec<_>>());
+}
+
+#[test]
+fn test_seeded_parallel_ranking_is_reproducible() {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    let entries = synthetic_entries(PARALLEL_RANKING_THRESHOLD as u64 * 2);
+    let entries = entries.iter().collect::<Vec<_>>();
+
+    let prior = Prior::default();
+    let priors = vec![&prior; entries.len()];
+
+    assert_eq!(
+        thompson_ranking(&entries, &priors, &mut StdRng::seed_from_u64(7)),
+        thompson_ranking(&entries, &priors, &mut StdRng::seed_from_u64(7))
+    );
+}
+
+#[bench]
+fn bench_ranking_bias_runtime_large(ben: &mut Bencher) {
+    let entries = synthetic_entries(10_000);
+    let entries = entries.iter().collect::<Vec<_>>();
+    let runtimes = (0..entries.len())
+        .map(|i| Some(NotNan::new(1.0 + (i % 1000) as f64).unwrap()))
+        .collect::<Vec<_>>();
+    let runtimes = runtimes.iter().collect::<Vec<_>>();
+    let runcounts = vec![1; entries.len()];
+    let bias = NotNan::new(1.0).unwrap();
+    let biases = vec![&bias; entries.len()];
+    let prior = Prior::default();
+    let priors = vec![&prior; entries.len()];
+    let reference = NotNan::new(DEFAULT_RUNTIME_REFERENCE_MS).unwrap();
+
+    let mut rng = rand::thread_rng();
+
+    ben.iter(|| {
+        black_box(thompson_ranking_bias_runtime(
+            &entries,
+            &RuntimeScoring {
+                priors: &priors,
+                runtimes: &runtimes,
+                runcounts: &runcounts,
+                user_biases: &biases,
+                runtime_reference_ms: reference,
+                min_samples: 1,
+            },
+            &mut rng,
+        ))
+    });
+}
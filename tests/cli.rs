@@ -138,6 +138,269 @@ fn lint_negative_bias() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+#[test]
+fn lint_counts_near_max() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("lint").arg("./tests/lint/counts-near-max.json");
+
+    cmd.assert().success().stdout(predicate::str::contains(
+        "Test 1 Warning: Counts are close to the largest value they can hold.",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn lint_dependency_cycle() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("lint").arg("./tests/lint/dependency-cycle.json");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "a ERROR: Dependency cycle a -> b -> a.",
+        ))
+        .stdout(predicate::str::contains(
+            "b ERROR: Dependency cycle b -> a -> b.",
+        ))
+        .stdout(predicate::str::contains(
+            "c ERROR: Depends on missing, which is not in the config.",
+        ))
+        .stdout(predicate::str::contains("c ERROR: Dependency cycle").not());
+
+    Ok(())
+}
+
+#[test]
+fn lint_huge_bias() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("lint").arg("./tests/lint/bias-huge.json");
+
+    cmd.assert().success().stdout(predicate::str::contains(
+        "Test 1 Warning: A bias of 1000000 is above 1000.",
+    ));
+
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("lint")
+        .arg("./tests/lint/bias-huge.json")
+        .arg("--max-bias")
+        .arg("1e7");
+
+    cmd.assert().success().stdout("");
+
+    Ok(())
+}
+
+#[test]
+fn lint_infinite_bias() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("lint").arg("./tests/lint/bias-infinite.json");
+
+    cmd.assert().failure().stderr(predicate::str::contains(
+        "Could not parse ./tests/lint/bias-infinite.json: number out of range",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn lint_strict() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("lint")
+        .arg("./tests/lint/bias-negative.json")
+        .arg("--strict");
+
+    cmd.assert()
+        .code(1)
+        .stdout(predicate::str::contains("Test 1 ERROR: A negative bias"));
+
+    // Warnings alone don't fail.
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("lint")
+        .arg("./tests/lint/bias-zero.json")
+        .arg("--strict");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Test 1 Warning: A bias of 0"));
+
+    Ok(())
+}
+
+#[test]
+fn lint_duplicate_name() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("lint").arg("./tests/lint/duplicate-name.json");
+
+    cmd.assert().success().stdout(predicate::str::contains(
+        "Test 1 ERROR: Duplicate script name Test 1.",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn lint_stdin() -> Result<(), Box<dyn std::error::Error>> {
+    // std Command has no stdin helper, unlike assert_cmd's.
+    let mut cmd = assert_cmd::Command::cargo_bin("bandits")?;
+
+    cmd.arg("lint")
+        .arg("-")
+        .write_stdin(std::fs::read("./tests/lint/duplicate-name.json")?);
+
+    cmd.assert().success().stdout(predicate::str::contains(
+        "Test 1 ERROR: Duplicate script name Test 1.",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn run_stdin_to_stdout() -> Result<(), Box<dyn std::error::Error>> {
+    // std Command has no stdin helper, unlike assert_cmd's.
+    let mut cmd = assert_cmd::Command::cargo_bin("bandits")?;
+
+    cmd.arg("run")
+        .arg("-")
+        .arg("--steps")
+        .arg("1")
+        .arg("-o")
+        .arg("-")
+        .write_stdin(std::fs::read("./tests/run/always-interesting.json")?);
+
+    let output = cmd.assert().success().get_output().stdout.clone();
+    let config: serde_json::Value = serde_json::from_slice(&output)?;
+    assert_eq!(config["scripts"][0]["runcount"], 3);
+
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("run")
+        .arg("./tests/run/noisy.json")
+        .arg("--tail-output")
+        .arg("2")
+        .arg("-o")
+        .arg("-");
+
+    cmd.assert().failure().stderr(predicate::str::contains(
+        "--tail-output prints to stdout, so it can't be combined with writing the config to stdout",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn lint_args_with_shell() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("lint").arg("./tests/lint/args-shell.json");
+
+    cmd.assert().success().stdout(predicate::str::contains(
+        "Test 1 Warning: Both args and shell are set.",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn lint_invalid_regex() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("lint").arg("./tests/lint/invalid-regex.json");
+
+    cmd.assert().success().stdout(predicate::str::contains(
+        "Grep ERROR: Invalid interesting_regex.",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn new_duplicate_name() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("new")
+        .arg("./tests/temp/new-duplicate.json")
+        .arg("-t")
+        .arg("fuzz=./fuzz.sh")
+        .arg("-t")
+        .arg("lint=./lint.sh")
+        .arg("-t")
+        .arg("fuzz=./fuzz.sh --slow");
+
+    cmd.assert().code(1).stderr(predicate::str::contains(
+        "Duplicate test name fuzz. Each script needs a unique name.",
+    ));
+    assert!(!std::path::Path::new("./tests/temp/new-duplicate.json").exists());
+
+    Ok(())
+}
+
+#[test]
+fn new_from_file() -> Result<(), Box<dyn std::error::Error>> {
+    std::fs::create_dir_all("./tests/temp")?;
+    let mut mappings = String::from("# Generated fuzz targets\n\n");
+    for index in 0..50 {
+        mappings.push_str(&format!("target-{index}=./fuzz.sh {index}\n"));
+    }
+    std::fs::write("./tests/temp/new-mappings.txt", mappings)?;
+
+    let mut cmd = Command::cargo_bin("bandits")?;
+    cmd.arg("new")
+        .arg("./tests/temp/new-from-file.json")
+        .arg("--from-file")
+        .arg("./tests/temp/new-mappings.txt");
+    cmd.assert().success();
+
+    let config: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string("./tests/temp/new-from-file.json")?)?;
+    let scripts = config["scripts"].as_array().unwrap();
+    assert_eq!(scripts.len(), 50);
+    assert_eq!(scripts[0]["name"], "target-0");
+    assert_eq!(scripts[49]["command"], "./fuzz.sh 49");
+
+    // Inline mappings are added after the file's.
+    let mut cmd = Command::cargo_bin("bandits")?;
+    cmd.arg("new")
+        .arg("./tests/temp/new-from-file.json")
+        .arg("--from-file")
+        .arg("./tests/temp/new-mappings.txt")
+        .arg("-t")
+        .arg("lint=./lint.sh");
+    cmd.assert().success();
+
+    let config: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string("./tests/temp/new-from-file.json")?)?;
+    let scripts = config["scripts"].as_array().unwrap();
+    assert_eq!(scripts.len(), 51);
+    assert_eq!(scripts[50]["name"], "lint");
+
+    Ok(())
+}
+
+#[test]
+fn new_from_file_bad_line() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("new")
+        .arg("./tests/temp/new-bad-line.json")
+        .arg("--from-file")
+        .arg("./tests/new/bad-line.txt");
+
+    cmd.assert().code(1).stderr(predicate::str::contains(
+        "line 3: Mapping should be in the format key=value",
+    ));
+
+    Ok(())
+}
+
 #[test]
 fn lint_zero_multiple_bias() -> Result<(), Box<dyn std::error::Error>> {
     let mut cmd = Command::cargo_bin("bandits")?;
@@ -146,10 +409,14 @@ fn lint_zero_multiple_bias() -> Result<(), Box<dyn std::error::Error>> {
 
     cmd.assert()
         .success()
-        .stdout(predicate::str::contains("Test 1 Warning: A bias of 0 will only run after all other scripts reach their limit."))
-        .stdout(predicate::str::contains("Test 2 Warning: A bias of 0 will only run after all other scripts reach their limit."))
-        .stdout(predicate::str::contains("Test 2 ERROR: Multiple scripts with bias zero will not be ranked relative to each other. YOU PROBABLY DON\'T WANT THIS."))
-        .stdout(predicate::str::contains("They will always be randomly run with equal probability regardless of interestingness/runtime."));
+        .stdout(predicate::str::contains(
+            "Test 1 Warning: A bias of 0 will only run after all other scripts reach their limit.",
+        ))
+        .stdout(predicate::str::contains(
+            "Test 2 Warning: A bias of 0 will only run after all other scripts reach their limit.",
+        ))
+        // Zero biases are raised to a tiny weight, so they are still ranked against each other.
+        .stdout(predicate::str::contains("ERROR").not());
 
     Ok(())
 }
@@ -179,3 +446,2228 @@ fn lint_zero_limit() -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
+
+#[test]
+fn summarize_by_throughput() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("summarize")
+        .arg("./tests/summarize/throughput.json")
+        .arg("--by-throughput");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "1: New\n- Throughput: unknown (needs sampling)",
+        ))
+        .stdout(predicate::str::contains(
+            "2: Fast\n- Throughput: 100.0000/s",
+        ))
+        .stdout(predicate::str::contains("3: Slow\n- Throughput: 0.4500/s"));
+
+    Ok(())
+}
+
+#[test]
+fn summarize_first_interesting_step() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("summarize")
+        .arg("./tests/summarize/first-interesting.json");
+
+    cmd.assert().success().stdout(predicate::str::contains(
+        "First interesting step:\nEarly: step 2\nQuiet: nothing interesting yet",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn explain_every_candidate() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("rank")
+        .arg("./tests/rank/sort.json")
+        .arg("--explain")
+        .arg("--seed")
+        .arg("3");
+
+    // a has never run, so it scores the maximum and is marked as the winner.
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Sampled scores:\n* a: sampled 0.2032, bias 1, score maximum (unknown runtime)\n",
+        ))
+        .stdout(predicate::str::contains("  b: sampled"))
+        .stdout(predicate::str::contains("  c: sampled"));
+
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("run")
+        .arg("./tests/rank/sort.json")
+        .arg("--explain")
+        .arg("--dry-run")
+        .arg("--steps")
+        .arg("1");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Selection 1:\n* a: "))
+        .stdout(predicate::str::is_match(
+            "  b: sampled [0-9.]+, runtime scaler 10.0000, bias 1, score [0-9.]+\n",
+        )?)
+        .stdout(predicate::str::is_match(
+            "  c: sampled [0-9.]+, runtime scaler 100.0000, bias 1, score [0-9.]+\n",
+        )?);
+
+    Ok(())
+}
+
+#[test]
+fn summarize_markdown() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("summarize")
+        .arg("./tests/rank/sort.json")
+        .arg("--format")
+        .arg("markdown");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::starts_with(
+            "| rank | name | interesting | uninteresting | avg runtime (ms) | bias | score |\n| ---: |",
+        ))
+        .stdout(predicate::str::contains("Plot of").not());
+
+    Ok(())
+}
+
+#[test]
+fn summarize_plot_out() -> Result<(), Box<dyn std::error::Error>> {
+    for path in ["./tests/temp/plots.txt", "./tests/temp/plots.svg"] {
+        let mut cmd = Command::cargo_bin("bandits")?;
+
+        cmd.arg("summarize")
+            .arg("./tests/rank/sort.json")
+            .arg("--plot-out")
+            .arg(path);
+
+        cmd.assert().success();
+    }
+
+    let text = std::fs::read_to_string("./tests/temp/plots.txt")?;
+    assert!(!text.is_empty());
+    assert!(!text.contains('\x1b'));
+    let svg = std::fs::read_to_string("./tests/temp/plots.svg")?;
+    assert!(svg.starts_with("<svg"));
+    assert!(svg.contains("<polyline"));
+
+    Ok(())
+}
+
+#[test]
+fn normalize() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("normalize")
+        .arg("./tests/normalize/biases.json")
+        .arg("-o")
+        .arg("./tests/temp/normalized.json");
+
+    cmd.assert().success().stderr(predicate::str::contains(
+        "Skipping parked: a bias of 0 or below has its own meaning and is kept",
+    ));
+
+    let config: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string("./tests/temp/normalized.json")?)?;
+    let biases = config["scripts"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|script| script["bias"].as_f64().unwrap())
+        .collect::<Vec<_>>();
+    let positive = [biases[0], biases[1], biases[3]];
+    assert!((positive.iter().sum::<f64>() / 3.0 - 1.0).abs() < 1e-9);
+    assert_eq!(biases[2], 0.0);
+
+    Ok(())
+}
+
+#[test]
+fn schema() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("schema");
+
+    let output = cmd.assert().success().get_output().stdout.clone();
+    let schema: serde_json::Value = serde_json::from_slice(&output)?;
+    assert!(schema["properties"]["scripts"].is_object());
+    assert!(schema["definitions"]["Script"]["properties"]["bias"].is_object());
+    assert!(schema["definitions"]["Script"]["required"]
+        .as_array()
+        .unwrap()
+        .contains(&"name".into()));
+
+    Ok(())
+}
+
+#[test]
+fn status_line() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("status")
+        .arg("./tests/summarize/throughput.json")
+        .arg("--seed")
+        .arg("2");
+
+    cmd.assert().success().stdout(predicate::str::diff(
+        "3 scripts, 16 runs, 8 interesting, best=Slow(0.87)\n",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn summarize_curve() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("summarize")
+        .arg("./tests/summarize/throughput.json")
+        .arg("--curve")
+        .arg("Slow");
+
+    let output = cmd.assert().success().get_output().stdout.clone();
+    let output = String::from_utf8(output)?;
+    let mut lines = output.lines();
+    assert_eq!(lines.next(), Some("x,density"));
+    let points = lines
+        .map(|line| {
+            let (x, density) = line.split_once(',').unwrap();
+            (x.parse::<f64>().unwrap(), density.parse::<f64>().unwrap())
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(points.len(), 100);
+    // 8 interesting runs out of 8, so the density piles up near 1.
+    assert!(points[99].1 > points[50].1);
+
+    let mut cmd = Command::cargo_bin("bandits")?;
+    cmd.arg("summarize")
+        .arg("./tests/summarize/throughput.json")
+        .arg("--curve")
+        .arg("Missing");
+    cmd.assert()
+        .code(1)
+        .stderr(predicate::str::contains("Could not find script Missing"));
+
+    Ok(())
+}
+
+#[test]
+fn run_with_priors() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("run")
+        .arg("./config-tests/simple-2-scripts.json")
+        .arg("--priors")
+        .arg("./tests/priors/priors.json")
+        .arg("--steps")
+        .arg("2")
+        .arg("--output")
+        .arg("./tests/temp/priors.json");
+
+    cmd.assert().success();
+
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    // Priors only steer sampling, the stored counts are the two observed runs.
+    cmd.arg("summarize").arg("./tests/temp/priors.json");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("50th percentile: 0.2929"))
+        .stdout(predicate::str::contains("50th percentile: 0.7071"));
+
+    Ok(())
+}
+
+#[test]
+fn run_with_bad_priors() -> Result<(), Box<dyn std::error::Error>> {
+    for (priors, error) in [
+        (
+            "./tests/priors/missing.json",
+            "Could not read ./tests/priors/missing.json: ",
+        ),
+        (
+            "./tests/priors/negative.json",
+            "Could not parse ./tests/priors/negative.json: the prior for exit 1 must have a positive alpha and beta.",
+        ),
+    ] {
+        let mut cmd = Command::cargo_bin("bandits")?;
+
+        cmd.arg("run")
+            .arg("./config-tests/simple-2-scripts.json")
+            .arg("--priors")
+            .arg(priors)
+            .arg("--output")
+            .arg("./tests/temp/bad-priors.json");
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::starts_with(error))
+            .stderr(predicate::str::contains("panicked").not());
+    }
+    assert!(!std::path::Path::new("./tests/temp/bad-priors.json").exists());
+
+    Ok(())
+}
+
+#[test]
+fn rank_explain_top() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("rank")
+        .arg("./tests/rank/dominant.json")
+        .arg("--explain-top");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Often interesting is ranked first. It has found 1000 interesting and 0 uninteresting cases, an expected interesting rate of 0.9990 (90% credible interval [0.9970, 0.9999]).",
+        ))
+        .stdout(predicate::str::contains("It takes 50.00ms on average."))
+        .stdout(predicate::str::contains(
+            "Its median score is 3.9972 (runtime scaler 2.0000, bias 2), compared to a median score of 0.0014 (runtime scaler 2.0000, bias 1) for Rarely interesting.",
+        ))
+        .stdout(predicate::str::contains(
+            "The ranking orders scripts by one random draw from each posterior, not by these medians.",
+        ));
+
+    Ok(())
+}
+
+#[test]
+fn config_set() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("config-set")
+        .arg("./config-tests/simple-2-scripts.json")
+        .arg("--script")
+        .arg("exit 1")
+        .arg("--bias")
+        .arg("2.5")
+        .arg("--limit")
+        .arg("100")
+        .arg("-o")
+        .arg("./tests/temp/config-set.json");
+
+    cmd.assert().success();
+
+    let config = std::fs::read_to_string("./tests/temp/config-set.json")?;
+    assert!(config.contains("\"bias\": 2.5"));
+    assert!(config.contains("\"limit\": 100"));
+
+    Ok(())
+}
+
+#[test]
+fn config_set_clear_limit() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("config-set")
+        .arg("./tests/config-set/limited.json")
+        .arg("--script")
+        .arg("exit 1")
+        .arg("--clear-limit")
+        .arg("-o")
+        .arg("./tests/temp/config-set-clear-limit.json");
+
+    cmd.assert().success();
+
+    let config: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(
+        "./tests/temp/config-set-clear-limit.json",
+    )?)?;
+    assert_eq!(config["scripts"][0]["limit"], serde_json::Value::Null);
+    assert_eq!(config["scripts"][1]["limit"], 3);
+
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("config-set")
+        .arg("./tests/config-set/limited.json")
+        .arg("--script")
+        .arg("exit 1")
+        .arg("--limit")
+        .arg("5")
+        .arg("--clear-limit")
+        .arg("-o")
+        .arg("./tests/temp/config-set-both-limits.json");
+
+    cmd.assert().failure().stderr(predicate::str::contains(
+        "--limit and --clear-limit can't be used together",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn config_set_missing_script() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("config-set")
+        .arg("./config-tests/simple-2-scripts.json")
+        .arg("--script")
+        .arg("not a script")
+        .arg("--bias")
+        .arg("2.0")
+        .arg("-o")
+        .arg("./tests/temp/config-set-missing.json");
+
+    cmd.assert().failure().stderr(predicate::str::contains(
+        "Could not find script not a script in config",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn config_set_negative_bias() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("config-set")
+        .arg("./config-tests/simple-2-scripts.json")
+        .arg("--script")
+        .arg("exit 1")
+        .arg("--bias")
+        .arg("-1.0")
+        .arg("-o")
+        .arg("./tests/temp/config-set-negative.json");
+
+    cmd.assert().failure().stderr(predicate::str::contains(
+        "exit 1 ERROR: A negative bias rewards tests that take more time to find an interesting case.",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn doctor_deterministic() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("doctor")
+        .arg("./tests/doctor/flaky.json")
+        .arg("--script")
+        .arg("always interesting")
+        .arg("--check-determinism")
+        .arg("--runs")
+        .arg("3");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("- Interesting: 3 (100.0%)"))
+        .stdout(predicate::str::contains(
+            "always interesting was deterministic across 3 runs.",
+        ));
+
+    Ok(())
+}
+
+#[test]
+fn doctor_non_deterministic() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("doctor")
+        .arg("./tests/doctor/flaky.json")
+        .arg("--script")
+        .arg("coin flip")
+        .arg("--check-determinism")
+        .arg("--runs")
+        .arg("40");
+
+    cmd.assert().success().stdout(predicate::str::contains(
+        "coin flip Warning: Non-deterministic.",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn rank_sort_runcount() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("rank")
+        .arg("./tests/rank/sort.json")
+        .arg("--sort")
+        .arg("runcount");
+
+    // a and b have equal runcounts, so fall back to name order.
+    cmd.assert().success().stdout(predicate::str::is_match(
+        "^c \\(sampled rank \\d\\)\na \\(sampled rank \\d\\)\nb \\(sampled rank \\d\\)\n$",
+    )?);
+
+    Ok(())
+}
+
+#[test]
+fn rank_sort_runtime() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("rank")
+        .arg("./tests/rank/sort.json")
+        .arg("--sort")
+        .arg("runtime");
+
+    // Unknown runtimes are listed last.
+    cmd.assert().success().stdout(predicate::str::is_match(
+        "^c \\(sampled rank \\d\\)\nb \\(sampled rank \\d\\)\na \\(sampled rank 1\\)\n$",
+    )?);
+
+    Ok(())
+}
+
+#[test]
+fn run_skips_scripts_at_their_limit() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("run")
+        .arg("./tests/run/limited.json")
+        .arg("--steps")
+        .arg("3")
+        .arg("--output")
+        .arg("./tests/temp/limited.json");
+
+    cmd.assert().success();
+
+    let config: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string("./tests/temp/limited.json")?)?;
+    assert_eq!(config["scripts"][0]["runcount"], 3);
+    assert_eq!(config["scripts"][1]["runcount"], 1);
+
+    Ok(())
+}
+
+#[test]
+fn run_maps_selection_back_to_the_config() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("run")
+        .arg("./tests/run/limited-first.json")
+        .arg("--steps")
+        .arg("3")
+        .arg("--output")
+        .arg("./tests/temp/limited-first.json");
+
+    cmd.assert().success();
+
+    // The script at its limit comes first, so the only selectable script is second in the config.
+    let config: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string("./tests/temp/limited-first.json")?)?;
+    assert_eq!(config["scripts"][0]["runcount"], 1);
+    assert_eq!(config["scripts"][1]["runcount"], 3);
+
+    Ok(())
+}
+
+#[test]
+fn run_weights_scripts_by_their_own_bias() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("run")
+        .arg("./tests/run/limited-biases.json")
+        .arg("--ignore-runtime")
+        .arg("--steps")
+        .arg("3")
+        .arg("--output")
+        .arg("./tests/temp/limited-biases.json");
+
+    cmd.assert().success();
+
+    // A bias of 0 zeroes every sample, so the script with a bias of 1 always wins.
+    let config: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(
+        "./tests/temp/limited-biases.json",
+    )?)?;
+    assert_eq!(config["scripts"][1]["runcount"], 0);
+    assert_eq!(config["scripts"][2]["runcount"], 3);
+
+    Ok(())
+}
+
+#[test]
+fn rank_epsilon_greedy() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("rank")
+        .arg("./tests/rank/sort.json")
+        .arg("--strategy")
+        .arg("epsilon-greedy:0.1");
+
+    // Ranked by empirical rate, so the order is deterministic and ties keep config order.
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::diff("b\na\nc\n"));
+
+    Ok(())
+}
+
+#[test]
+fn rank_runtime_reference_from_config() -> Result<(), Box<dyn std::error::Error>> {
+    // fresh has only run once, so with --min-samples 3 it is scored as taking the reference runtime.
+    // That puts it ahead of measured's 50ms with a 10ms reference and behind with a 1000ms one.
+    for (config, expected) in [
+        ("./tests/rank/reference-10.json", "fresh\nmeasured\n"),
+        ("./tests/rank/reference-1000.json", "measured\nfresh\n"),
+    ] {
+        let mut cmd = Command::cargo_bin("bandits")?;
+
+        cmd.arg("--min-samples")
+            .arg("3")
+            .arg("rank")
+            .arg(config)
+            .arg("--seed")
+            .arg("1");
+
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::diff(expected));
+    }
+
+    // --runtime-reference overrides the config's reference.
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("--min-samples")
+        .arg("3")
+        .arg("--runtime-reference")
+        .arg("1s")
+        .arg("rank")
+        .arg("./tests/rank/reference-10.json")
+        .arg("--seed")
+        .arg("1");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::diff("measured\nfresh\n"));
+
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("--runtime-reference")
+        .arg("0ms")
+        .arg("rank")
+        .arg("./tests/rank/reference-10.json");
+
+    cmd.assert().failure().stderr(predicate::str::contains(
+        "The runtime reference must be positive, got 0ms",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn rank_verbose_interval() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("rank")
+        .arg("./tests/rank/sort.json")
+        .arg("-i")
+        .arg("-v");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "b\n- 50th percentile: 0.2644 [0.0628, 0.5818]",
+        ))
+        .stdout(predicate::str::contains(
+            "c\n- 50th percentile: 0.0670 [0.0051, 0.2589]",
+        ));
+
+    Ok(())
+}
+
+#[test]
+fn log_verbosity() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.env_remove("RUST_LOG")
+        .arg("-v")
+        .arg("-v")
+        .arg("rank")
+        .arg("./tests/rank/sort.json");
+
+    cmd.assert()
+        .success()
+        .stderr(predicate::str::contains("DEBUG bandits::thompson"));
+
+    // RUST_LOG takes precedence over the flags.
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.env("RUST_LOG", "off")
+        .arg("-v")
+        .arg("-v")
+        .arg("-v")
+        .arg("rank")
+        .arg("./tests/rank/sort.json");
+
+    cmd.assert().success().stderr(predicate::str::is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn rank_json() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("rank").arg("./tests/rank/sort.json").arg("--json");
+
+    let output = cmd.assert().success().get_output().stdout.clone();
+    let ranking: Vec<serde_json::Value> = serde_json::from_slice(&output)?;
+
+    assert_eq!(ranking.len(), 3);
+    for (i, entry) in ranking.iter().enumerate() {
+        assert_eq!(entry["rank"], i + 1);
+    }
+    let scores = ranking
+        .iter()
+        .map(|entry| entry["score"].as_f64().unwrap())
+        .collect::<Vec<_>>();
+    assert!(scores.windows(2).all(|pair| pair[0] >= pair[1]));
+    // A script without a runtime is always ranked first.
+    assert_eq!(ranking[0]["name"], "a");
+    assert_eq!(ranking[0]["avgruntime_ms"], serde_json::Value::Null);
+    assert_eq!(ranking[0]["interesting"], 1);
+
+    Ok(())
+}
+
+#[test]
+fn rank_min_effective_bias() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("--min-effective-bias")
+        .arg("0.5")
+        .arg("rank")
+        .arg("./tests/rank/small-bias.json")
+        .arg("--json");
+
+    let output = cmd.assert().success().get_output().stdout.clone();
+    let ranking: Vec<serde_json::Value> = serde_json::from_slice(&output)?;
+
+    // The tiny bias is raised to the floor, as when selecting, and the zero bias is left alone.
+    assert_eq!(ranking[0]["name"], "tiny");
+    assert_eq!(ranking[0]["bias"], 0.5);
+    assert_eq!(ranking[1]["name"], "off");
+    assert_eq!(ranking[1]["bias"], 0.0);
+
+    Ok(())
+}
+
+#[test]
+fn rank_json_other_strategy() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("rank")
+        .arg("./tests/rank/sort.json")
+        .arg("--json")
+        .arg("--strategy")
+        .arg("ucb1");
+
+    cmd.assert().failure().stderr(predicate::str::contains(
+        "--json is only supported with the thompson strategy",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn rank_ucb1() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("rank")
+        .arg("./tests/rank/sort.json")
+        .arg("--strategy")
+        .arg("ucb1")
+        .arg("-v");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("1: b\n- UCB1 score: 1.2853"))
+        .stdout(predicate::str::contains("3: c\n- UCB1 score: 0.8089"));
+
+    Ok(())
+}
+
+#[test]
+fn reset_named_script() -> Result<(), Box<dyn std::error::Error>> {
+    std::fs::create_dir_all("./tests/temp")?;
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("reset")
+        .arg("./tests/rank/sort.json")
+        .arg("--script")
+        .arg("a")
+        .arg("-o")
+        .arg("./tests/temp/reset-named.json");
+
+    cmd.assert().success();
+
+    let config: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string("./tests/temp/reset-named.json")?)?;
+    let original: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string("./tests/rank/sort.json")?)?;
+    let scripts = config["scripts"].as_array().unwrap();
+    assert_eq!(scripts[1]["name"], "a");
+    assert_eq!(scripts[1]["results"]["interesting"], 0);
+    assert_eq!(scripts[1]["results"]["uninteresting"], 0);
+    assert_eq!(scripts[1]["runcount"], 0);
+    for index in [0, 2] {
+        assert_eq!(
+            scripts[index]["results"],
+            original["scripts"][index]["results"]
+        );
+        assert_eq!(
+            scripts[index]["runcount"],
+            original["scripts"][index]["runcount"]
+        );
+        assert_eq!(
+            scripts[index]["avgruntime_ms"],
+            original["scripts"][index]["avgruntime_ms"]
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn reset_unknown_script() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("reset")
+        .arg("./tests/rank/sort.json")
+        .arg("--script")
+        .arg("missing")
+        .arg("-o")
+        .arg("./tests/temp/reset-unknown.json");
+
+    cmd.assert().failure().stderr(predicate::str::contains(
+        "Could not find specified script missing to reset",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn explain_bias() -> Result<(), Box<dyn std::error::Error>> {
+    std::fs::create_dir_all("./tests/temp")?;
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("explain-bias")
+        .arg("./tests/rank/dominant.json")
+        .arg("--script")
+        .arg("Rarely interesting")
+        .arg("--image")
+        .arg("./tests/temp/explain-bias.svg");
+
+    cmd.assert().success().stdout(predicate::str::contains(
+        "Rarely interesting overtakes Often interesting at a bias of",
+    ));
+
+    let svg = std::fs::read_to_string("./tests/temp/explain-bias.svg")?;
+    assert!(svg.starts_with("<svg"));
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn records_peak_memory() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("run")
+        .arg("./config-tests/simple-2-scripts.json")
+        .arg("--steps")
+        .arg("2")
+        .arg("--output")
+        .arg("./tests/temp/peak-memory.json");
+
+    cmd.assert().success();
+
+    let config: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string("./tests/temp/peak-memory.json")?)?;
+    for script in config["scripts"].as_array().unwrap() {
+        assert!(script["avgpeakmem_kb"].as_f64().unwrap() > 0.);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn rank_leaves_out_scripts_at_their_limit() -> Result<(), Box<dyn std::error::Error>> {
+    for ignore_runtime in [false, true] {
+        let mut cmd = Command::cargo_bin("bandits")?;
+
+        cmd.arg("rank").arg("./tests/rank/limited.json");
+        if ignore_runtime {
+            cmd.arg("--ignore-runtime");
+        }
+
+        cmd.assert().success().stdout(predicate::str::is_match(
+            "^(open\nunlimited|unlimited\nopen)\n$",
+        )?);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn rank_min_runs() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("rank")
+        .arg("./tests/rank/sort.json")
+        .arg("--sort")
+        .arg("name")
+        .arg("--min-runs")
+        .arg("6");
+
+    cmd.assert().success().stdout(predicate::str::is_match(
+        "^c \\(sampled rank \\d\\)\nUnder-sampled \\(fewer than 6 runs\\):\na\nb\n$",
+    )?);
+
+    Ok(())
+}
+
+#[test]
+fn run_max_duration() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    // Without --steps the duration is the only cap, and the config is still saved when it is hit.
+    cmd.arg("run")
+        .arg("./config-tests/simple-2-scripts.json")
+        .arg("--max-duration")
+        .arg("0s")
+        .arg("--checkpoint-every")
+        .arg("0s")
+        .arg("--output")
+        .arg("./tests/temp/max-duration.json");
+
+    cmd.assert().success().stdout(predicate::str::contains(
+        "Reached max duration after 0 steps, saving and exiting.",
+    ));
+
+    let config: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string("./tests/temp/max-duration.json")?)?;
+    for script in config["scripts"].as_array().unwrap() {
+        assert_eq!(script["runcount"], 0);
+    }
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn run_capture_interesting_output() -> Result<(), Box<dyn std::error::Error>> {
+    let _ = std::fs::remove_dir_all("./tests/temp/capture.captures");
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("run")
+        .arg("./tests/run/capture.json")
+        .arg("--steps")
+        .arg("2")
+        .arg("--strategy")
+        .arg("round-robin")
+        .arg("--output")
+        .arg("./tests/temp/capture.json");
+
+    cmd.assert().success();
+
+    let capture = std::fs::read_to_string("./tests/temp/capture.captures/found_bug-step-1.log")?;
+    assert_eq!(
+        capture,
+        "stdout:\nchecking\nstderr:\nassertion failed: x > 0\n"
+    );
+    // The uninteresting run at step 2 isn't captured.
+    assert_eq!(
+        std::fs::read_dir("./tests/temp/capture.captures")?.count(),
+        1
+    );
+
+    Ok(())
+}
+
+#[test]
+fn run_budget_ms() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("run")
+        .arg("./tests/rank/sort.json")
+        .arg("--budget-ms")
+        .arg("300")
+        .arg("--output")
+        .arg("./tests/temp/budget-ms.json");
+
+    let start = std::time::Instant::now();
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Reached max duration after"));
+    let elapsed = start.elapsed();
+    assert!(elapsed >= std::time::Duration::from_millis(300));
+    assert!(elapsed < std::time::Duration::from_secs(10));
+
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("run")
+        .arg("./tests/rank/sort.json")
+        .arg("--budget-ms")
+        .arg("3600000")
+        .arg("--steps")
+        .arg("3")
+        .arg("--output")
+        .arg("./tests/temp/budget-ms-steps.json");
+
+    cmd.assert().success().stdout(predicate::str::contains(
+        "Completed 3 steps, saving and exiting.",
+    ));
+
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("run")
+        .arg("./tests/rank/sort.json")
+        .arg("--budget-ms")
+        .arg("300")
+        .arg("--max-duration")
+        .arg("1h");
+
+    cmd.assert().failure().stderr(predicate::str::contains(
+        "--budget-ms and --max-duration can't be used together",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn run_all_scripts_exhausted() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("run")
+        .arg("./tests/run/exhausted.json")
+        .arg("--steps")
+        .arg("5")
+        .arg("--output")
+        .arg("./tests/temp/exhausted.json");
+
+    cmd.assert().success().stdout(predicate::str::contains(
+        "All scripts reached their limits, stopping early after 0 steps, saving and exiting.",
+    ));
+
+    let config: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string("./tests/temp/exhausted.json")?)?;
+    for script in config["scripts"].as_array().unwrap() {
+        assert_eq!(script["runcount"], 4);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn run_stop_after_interesting() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("run")
+        .arg("./tests/run/always-interesting.json")
+        .arg("--stop-after-interesting")
+        .arg("3")
+        .arg("--output")
+        .arg("./tests/temp/stop-after-interesting.json");
+
+    cmd.assert().success().stdout(predicate::str::contains(
+        "Found 3 interesting results after 3 steps, saving and exiting.",
+    ));
+
+    let config: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(
+        "./tests/temp/stop-after-interesting.json",
+    )?)?;
+    assert_eq!(config["scripts"][0]["runcount"], 5);
+    assert_eq!(config["scripts"][0]["results"]["interesting"], 5.0);
+
+    Ok(())
+}
+
+#[test]
+fn run_penalty_codes() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("run")
+        .arg("./tests/run/penalty.json")
+        .arg("--steps")
+        .arg("2")
+        .arg("--output")
+        .arg("./tests/temp/penalty.json");
+
+    cmd.assert().success();
+
+    // The first penalty takes back the one interesting case, the second can't go below zero.
+    let config: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string("./tests/temp/penalty.json")?)?;
+    assert_eq!(config["scripts"][0]["runcount"], 3);
+    assert_eq!(config["scripts"][0]["results"]["interesting"], 0.0);
+    assert_eq!(config["scripts"][0]["results"]["uninteresting"], 2.0);
+
+    Ok(())
+}
+
+#[test]
+fn run_tag_filter() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("run")
+        .arg("./tests/tags/components.json")
+        .arg("--tag")
+        .arg("net")
+        .arg("--steps")
+        .arg("10")
+        .arg("--output")
+        .arg("./tests/temp/tags.json");
+
+    cmd.assert().success();
+
+    // The untagged parser would win every selection it was allowed into.
+    let config: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string("./tests/temp/tags.json")?)?;
+    let runcounts = config["scripts"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|script| script["runcount"].as_u64().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(runcounts[1], 0);
+    assert_eq!(runcounts[0] + runcounts[2], 10);
+
+    Ok(())
+}
+
+#[test]
+fn rank_tag_filter() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("rank")
+        .arg("./tests/tags/components.json")
+        .arg("--tag")
+        .arg("net");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("http"))
+        .stdout(predicate::str::contains("dns"))
+        .stdout(predicate::str::contains("parser").not());
+
+    Ok(())
+}
+
+#[test]
+fn run_max_duration_budget() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    // Fast scripts keep running until the budget is spent, then the run stops soon after.
+    cmd.arg("run")
+        .arg("./tests/rank/sort.json")
+        .arg("--max-duration")
+        .arg("500ms")
+        .arg("--output")
+        .arg("./tests/temp/max-duration-budget.json");
+
+    let start = std::time::Instant::now();
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Reached max duration after"));
+    let elapsed = start.elapsed();
+    assert!(elapsed >= std::time::Duration::from_millis(500));
+    assert!(elapsed < std::time::Duration::from_secs(10));
+
+    let config: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(
+        "./tests/temp/max-duration-budget.json",
+    )?)?;
+    let runs: u64 = config["scripts"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|script| script["runcount"].as_u64().unwrap())
+        .sum();
+    // The starting counts are 19, so at least one step ran.
+    assert!(runs > 19);
+
+    // With both limits the run stops at whichever comes first.
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("run")
+        .arg("./tests/rank/sort.json")
+        .arg("--max-duration")
+        .arg("1h")
+        .arg("--steps")
+        .arg("3")
+        .arg("--output")
+        .arg("./tests/temp/max-duration-steps.json");
+
+    cmd.assert().success().stdout(predicate::str::contains(
+        "Completed 3 steps, saving and exiting.",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn hammer_interesting() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("hammer")
+        .arg("./tests/hammer/hammer.json")
+        .arg("--script")
+        .arg("always interesting")
+        .arg("--max-attempts")
+        .arg("3");
+
+    cmd.assert().success().stdout(predicate::str::contains(
+        "always interesting was interesting on attempt 1 of 3, taking",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn hammer_gives_up() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("hammer")
+        .arg("./tests/hammer/hammer.json")
+        .arg("--script")
+        .arg("never interesting")
+        .arg("--max-attempts")
+        .arg("3");
+
+    cmd.assert().success().stdout(predicate::str::contains(
+        "never interesting was not interesting within 3 attempts.",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn summarize_suggest_bias() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("summarize")
+        .arg("./tests/summarize/suggest-bias.json")
+        .arg("--suggest-bias");
+
+    let output = cmd
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Strong: current 1.0000, suggested 1.0000",
+        ))
+        .stdout(predicate::str::contains(
+            "New: current 1.0000, needs a run before a bias can be suggested",
+        ))
+        .get_output()
+        .stdout
+        .clone();
+
+    // At equal runtimes Weak wins half the time once its Beta(1, 2) draw, weighted by about 2.22, beats
+    // Strong's Beta(2, 1) draw. The suggestion is estimated by sampling, so allow some noise.
+    let output = String::from_utf8(output)?;
+    let suggested = output
+        .lines()
+        .find_map(|line| line.strip_prefix("Weak: current 2.0000, suggested "))
+        .expect("Weak has a suggestion")
+        .parse::<f64>()?;
+    assert!((2.0..2.45).contains(&suggested), "{suggested}");
+
+    Ok(())
+}
+
+#[test]
+fn run_tail_output() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("run")
+        .arg("./tests/run/noisy.json")
+        .arg("--steps")
+        .arg("1")
+        .arg("--tail-output")
+        .arg("2")
+        .arg("--output")
+        .arg("./tests/temp/tail-output.json");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Step 1: noisy was interesting. Last 2 lines of output:\n  stdout:\n    second\n    third\n",
+        ))
+        .stdout(predicate::str::contains("first").not());
+
+    Ok(())
+}
+
+#[test]
+fn analyze_runtime_correlation() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("analyze")
+        .arg("./tests/analyze/fast-interesting.json");
+
+    // Posterior means fall by 0.4 for every extra 100ms, and the script without a runtime is skipped.
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Correlation between average runtime and posterior mean across 3 scripts: -1.0000",
+        ))
+        .stdout(predicate::str::contains(
+            "Faster scripts tend to be more interesting.",
+        ));
+
+    Ok(())
+}
+
+#[test]
+fn analyze_too_few_scripts() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("analyze").arg("./tests/run/noisy.json");
+
+    cmd.assert().success().stdout(predicate::str::contains(
+        "Need at least two scripts with a known runtime to compare, found 0.",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn export_csv() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("export")
+        .arg("./tests/export/results.json")
+        .arg("-o")
+        .arg("./tests/temp/results.csv");
+
+    cmd.assert().success();
+
+    assert_eq!(
+        std::fs::read_to_string("./tests/temp/results.csv")?,
+        "name,command,interesting,uninteresting,runcount,avgruntime_ms,bias,limit\n\
+         fuzz parser,\"./fuzz.sh --target parser,lexer\",3,17,21,152.5,2.0,10\n\
+         new script,ls,0,0,0,,1.0,\n"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn export_state() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("export-state")
+        .arg("./tests/analyze/fast-interesting.json")
+        .arg("--output")
+        .arg("./tests/temp/exported-state.json");
+
+    cmd.assert().success();
+
+    let state: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(
+        "./tests/temp/exported-state.json",
+    )?)?;
+    assert_eq!(state["Fast"], serde_json::json!([8, 0, 8, 100.0, 800]));
+    assert_eq!(state["New"], serde_json::json!([0, 0, 0, null, 0]));
+
+    Ok(())
+}
+
+#[test]
+fn merge_configs() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("merge")
+        .arg("./tests/merge/first.json")
+        .arg("./tests/merge/second.json")
+        .arg("-o")
+        .arg("./tests/temp/merged.json");
+
+    cmd.assert().success().stderr(predicate::str::contains(
+        "Warning: bias or limit of Shared differ, keeping the values from ./tests/merge/first.json",
+    ));
+
+    let config: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string("./tests/temp/merged.json")?)?;
+    let scripts = config["scripts"].as_array().unwrap();
+    assert_eq!(scripts.len(), 3);
+
+    let shared = &scripts[0];
+    assert_eq!(
+        shared["results"],
+        serde_json::json!({"interesting": 3, "uninteresting": 5})
+    );
+    assert_eq!(shared["runcount"], 8);
+    // (2 * 10 + 6 * 30) / 8
+    assert_eq!(shared["avgruntime_ms"], 25.0);
+    assert_eq!(shared["bias"], 1.0);
+
+    assert_eq!(scripts[1]["name"], "First only");
+    assert_eq!(scripts[1]["runcount"], 3);
+    assert_eq!(scripts[2]["name"], "Second only");
+    assert_eq!(scripts[2]["command"], "ls -a");
+    assert_eq!(scripts[2]["avgruntime_ms"], 7.5);
+
+    Ok(())
+}
+
+#[test]
+fn import_state_adds() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("import-state")
+        .arg("./tests/analyze/fast-interesting.json")
+        .arg("--state")
+        .arg("./tests/state/state.json")
+        .arg("--output")
+        .arg("./tests/temp/import-add.json");
+
+    cmd.assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "Warning: state for Removed does not match any script in the config",
+        ))
+        .stderr(predicate::str::contains(
+            "Warning: no state for Medium, leaving it unchanged",
+        ));
+
+    let config: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string("./tests/temp/import-add.json")?)?;
+    let fast = &config["scripts"][0];
+    assert_eq!(fast["results"]["interesting"], 10);
+    assert_eq!(fast["results"]["uninteresting"], 6);
+    assert_eq!(fast["runcount"], 16);
+    assert_eq!(fast["avgruntime_ms"], 200.0);
+
+    Ok(())
+}
+
+#[test]
+fn import_state_replaces() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("import-state")
+        .arg("./tests/analyze/fast-interesting.json")
+        .arg("--state")
+        .arg("./tests/state/state.json")
+        .arg("--replace")
+        .arg("--output")
+        .arg("./tests/temp/import-replace.json");
+
+    cmd.assert().success();
+
+    let config: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(
+        "./tests/temp/import-replace.json",
+    )?)?;
+    let fast = &config["scripts"][0];
+    assert_eq!(fast["results"]["interesting"], 2);
+    assert_eq!(fast["results"]["uninteresting"], 6);
+    assert_eq!(fast["runcount"], 8);
+    assert_eq!(fast["avgruntime_ms"], 300.0);
+
+    Ok(())
+}
+
+#[test]
+fn import_state_unparseable() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("import-state")
+        .arg("./tests/analyze/fast-interesting.json")
+        .arg("--state")
+        .arg("./tests/state/truncated.json")
+        .arg("--output")
+        .arg("./tests/temp/import-truncated.json");
+
+    cmd.assert().failure().stderr(predicate::str::contains(
+        "Could not parse ./tests/state/truncated.json:",
+    ));
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn memory_limit_terminates_script() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("run")
+        .arg("./tests/run/memory-bomb.json")
+        .arg("--steps")
+        .arg("1")
+        .arg("--output")
+        .arg("./tests/temp/memory-bomb.json");
+
+    cmd.assert().success().stderr(predicate::str::contains(
+        "Command failed with unknown exit status",
+    ));
+
+    // The failed run counts towards runtime but is neither interesting nor uninteresting.
+    let config: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string("./tests/temp/memory-bomb.json")?)?;
+    let script = &config["scripts"][0];
+    assert_eq!(script["runcount"], 1);
+    assert_eq!(script["results"]["interesting"], 0);
+    assert_eq!(script["results"]["uninteresting"], 0);
+
+    Ok(())
+}
+
+#[test]
+fn run_until_file_exists() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    // The config itself exists, so the run stops before the first step.
+    cmd.arg("run")
+        .arg("./config-tests/simple-2-scripts.json")
+        .arg("--until-file-exists")
+        .arg("./config-tests/simple-2-scripts.json")
+        .arg("--output")
+        .arg("./tests/temp/until-file-exists.json");
+
+    cmd.assert().success().stdout(predicate::str::contains(
+        "Found ./config-tests/simple-2-scripts.json after 0 steps, saving and exiting.",
+    ));
+
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("run")
+        .arg("./config-tests/simple-2-scripts.json")
+        .arg("--until-file-exists")
+        .arg("./tests/temp/does-not-exist")
+        .arg("--steps")
+        .arg("2")
+        .arg("--output")
+        .arg("./tests/temp/until-file-missing.json");
+
+    cmd.assert().success().stdout(predicate::str::contains(
+        "Completed 2 steps, saving and exiting.",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn promote_recommends() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("promote")
+        .arg("./tests/promote/challenger.json")
+        .arg("--output")
+        .arg("./tests/temp/promote-report.json");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "tweaked beats original with probability 1.0000. Recommend promoting it.",
+        ))
+        .stdout(predicate::str::contains(
+            "untested vs original: needs 20 runs of each, has 1 and 50.",
+        ));
+
+    // Without --auto-promote the config is left alone.
+    assert!(!std::path::Path::new("./tests/temp/promote-report.json").exists());
+
+    Ok(())
+}
+
+#[test]
+fn promote_auto_promote() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("promote")
+        .arg("./tests/promote/challenger.json")
+        .arg("--auto-promote")
+        .arg("--output")
+        .arg("./tests/temp/promoted.json");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Promoted tweaked over original."));
+
+    let config: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string("./tests/temp/promoted.json")?)?;
+    let scripts = config["scripts"].as_array().unwrap();
+    assert_eq!(scripts.len(), 2);
+    assert_eq!(scripts[0]["name"], "original");
+    assert_eq!(scripts[0]["command"], "./scripts/exit.sh 1");
+    assert_eq!(scripts[0]["results"]["interesting"], 40);
+    assert_eq!(scripts[1]["name"], "untested");
+
+    Ok(())
+}
+
+#[test]
+fn promote_args_challenger() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("promote")
+        .arg("./tests/promote/args-challenger.json")
+        .arg("--auto-promote")
+        .arg("--output")
+        .arg("./tests/temp/promoted-args.json");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Promoted tweaked over original."));
+
+    // The champion runs the challenger's arguments and limits, not its old ones.
+    let config: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string("./tests/temp/promoted-args.json")?)?;
+    let scripts = config["scripts"].as_array().unwrap();
+    assert_eq!(scripts.len(), 1);
+    assert_eq!(scripts[0]["name"], "original");
+    assert_eq!(scripts[0]["args"], serde_json::json!(["1"]));
+    assert_eq!(scripts[0]["interesting_regex"], "crash");
+    assert_eq!(scripts[0]["rlimit_as_mb"], 512);
+    assert_eq!(scripts[0]["challenger_of"], serde_json::Value::Null);
+
+    Ok(())
+}
+
+#[test]
+fn run_timeout() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("run")
+        .arg("./tests/run/hang.json")
+        .arg("--steps")
+        .arg("1")
+        .arg("--output")
+        .arg("./tests/temp/hang.json");
+
+    cmd.assert()
+        .success()
+        .stderr(predicate::str::contains("hang timed out after"));
+
+    // The timed out run counts towards runtime but is neither interesting nor uninteresting.
+    let config: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string("./tests/temp/hang.json")?)?;
+    let script = &config["scripts"][0];
+    assert_eq!(script["runcount"], 1);
+    assert_eq!(script["results"]["interesting"], 0);
+    assert_eq!(script["results"]["uninteresting"], 0);
+    assert!(script["avgruntime_ms"].as_f64().unwrap() >= 200.0);
+
+    Ok(())
+}
+
+#[test]
+fn run_skips_scripts_that_cannot_start() -> Result<(), Box<dyn std::error::Error>> {
+    std::fs::create_dir_all("./tests/temp")?;
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("run")
+        .arg("./tests/run/missing-binary.json")
+        .arg("--steps")
+        .arg("5")
+        .arg("--output")
+        .arg("./tests/temp/missing-binary.json");
+
+    cmd.assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "Could not run typo: No such file or directory",
+        ))
+        .stderr(predicate::str::contains(
+            "Could not run blank: No command provided. Skipping it for the rest of this run.",
+        ));
+
+    // Each broken script uses up one step, then the run continues with the rest.
+    let config: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(
+        "./tests/temp/missing-binary.json",
+    )?)?;
+    let scripts = config["scripts"].as_array().unwrap();
+    assert_eq!(scripts[0]["runcount"], 0);
+    assert_eq!(scripts[1]["runcount"], 0);
+    assert_eq!(scripts[2]["runcount"], 3);
+    assert_eq!(scripts[2]["results"]["uninteresting"], 3);
+
+    Ok(())
+}
+
+#[test]
+fn run_history() -> Result<(), Box<dyn std::error::Error>> {
+    let history = "./tests/temp/history.jsonl";
+    let _ = std::fs::remove_file(history);
+
+    // A second run appends to the log rather than replacing it.
+    for _ in 0..2 {
+        let mut cmd = Command::cargo_bin("bandits")?;
+
+        cmd.arg("run")
+            .arg("./tests/rank/sort.json")
+            .arg("--steps")
+            .arg("3")
+            .arg("--history")
+            .arg(history)
+            .arg("--output")
+            .arg("./tests/temp/history-config.json");
+
+        cmd.assert().success().stdout(
+            predicate::str::is_match("Estimated regret: [0-9.]+ interesting cases over 3 steps.")
+                .unwrap(),
+        );
+    }
+
+    let records = std::fs::read_to_string(history)?
+        .lines()
+        .map(serde_json::from_str::<serde_json::Value>)
+        .collect::<Result<Vec<_>, _>>()?;
+    assert_eq!(records.len(), 6);
+    for (record, step) in records.iter().zip([1, 2, 3, 1, 2, 3]) {
+        assert_eq!(record["step"], step);
+        let index = record["script_index"].as_u64().unwrap();
+        assert_eq!(record["script_name"], ["b", "a", "c"][index as usize]);
+        // ls exits 0, which is uninteresting.
+        assert_eq!(record["interesting"], 0);
+        assert_eq!(record["uninteresting"], 1);
+        assert!(record["runtime_ms"].is_u64());
+        assert!(record["timestamp"].as_u64().unwrap() > 0);
+        assert!(record["regret"].as_f64().unwrap() >= 0.0);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn run_dry_run() -> Result<(), Box<dyn std::error::Error>> {
+    std::fs::create_dir_all("./tests/temp")?;
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("run")
+        .arg("./tests/run/dry-run.json")
+        .arg("--dry-run")
+        .arg("--steps")
+        .arg("5")
+        .arg("--seed")
+        .arg("1")
+        .arg("--output")
+        .arg("./tests/temp/dry-run.json");
+
+    // marks b has never run, so its unknown runtime wins every step since nothing is recorded.
+    cmd.assert().success().stdout(predicate::str::diff(
+        "Step 1: marks b\nStep 2: marks b\nStep 3: marks b\nStep 4: marks b\nStep 5: marks b\n",
+    ));
+
+    // Neither command was spawned and nothing was saved.
+    assert!(!std::path::Path::new("./tests/temp/dry-run-ran-a").exists());
+    assert!(!std::path::Path::new("./tests/temp/dry-run-ran-b").exists());
+    assert!(!std::path::Path::new("./tests/temp/dry-run.json").exists());
+
+    Ok(())
+}
+
+#[test]
+fn run_reports_filtered_scripts() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("run")
+        .arg("./tests/rank/limited.json")
+        .arg("--dry-run")
+        .arg("--steps")
+        .arg("3");
+
+    // Reported once, not on every step.
+    let output = cmd.assert().success().get_output().stderr.clone();
+    let stderr = String::from_utf8(output)?;
+    assert_eq!(
+        stderr
+            .matches("done is left out of selection: it reached its limit.")
+            .count(),
+        1
+    );
+    assert!(!stderr.contains("open is left out"));
+
+    Ok(())
+}
+
+#[test]
+fn run_seed_is_reproducible() -> Result<(), Box<dyn std::error::Error>> {
+    let selections = |name: &str| -> Result<Vec<u64>, Box<dyn std::error::Error>> {
+        let history = format!("./tests/temp/seed-{name}.jsonl");
+        let _ = std::fs::remove_file(&history);
+        let mut cmd = Command::cargo_bin("bandits")?;
+
+        // Runtimes vary between runs, so only a selection that ignores them is reproducible.
+        cmd.arg("run")
+            .arg("./tests/rank/sort.json")
+            .arg("-i")
+            .arg("--steps")
+            .arg("20")
+            .arg("--seed")
+            .arg("42")
+            .arg("--history")
+            .arg(&history)
+            .arg("--output")
+            .arg(format!("./tests/temp/seed-{name}.json"));
+
+        cmd.assert().success();
+
+        Ok(std::fs::read_to_string(&history)?
+            .lines()
+            .map(|line| {
+                let record: serde_json::Value = serde_json::from_str(line).unwrap();
+                record["script_index"].as_u64().unwrap()
+            })
+            .collect())
+    };
+
+    let first = selections("first")?;
+    assert_eq!(first.len(), 20);
+    assert_eq!(first, selections("second")?);
+
+    Ok(())
+}
+
+#[test]
+fn run_jobs_matches_serial_counts() -> Result<(), Box<dyn std::error::Error>> {
+    let original: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string("./tests/rank/sort.json")?)?;
+
+    for jobs in ["1", "4"] {
+        let history = format!("./tests/temp/jobs-{jobs}.jsonl");
+        let output = format!("./tests/temp/jobs-{jobs}.json");
+        let _ = std::fs::remove_file(&history);
+        let mut cmd = Command::cargo_bin("bandits")?;
+
+        cmd.arg("run")
+            .arg("./tests/rank/sort.json")
+            .arg("--steps")
+            .arg("8")
+            .arg("--jobs")
+            .arg(jobs)
+            .arg("--history")
+            .arg(&history)
+            .arg("--output")
+            .arg(&output);
+
+        cmd.assert().success();
+
+        // Every step ran exactly one script and recorded one uninteresting result, as in a serial run.
+        let config: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&output)?)?;
+        let mut runs = 0;
+        for (script, before) in config["scripts"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .zip(original["scripts"].as_array().unwrap())
+        {
+            let added = script["runcount"].as_u64().unwrap() - before["runcount"].as_u64().unwrap();
+            assert_eq!(
+                script["results"]["uninteresting"].as_u64().unwrap()
+                    - before["results"]["uninteresting"].as_u64().unwrap(),
+                added
+            );
+            assert_eq!(
+                script["results"]["interesting"],
+                before["results"]["interesting"]
+            );
+            runs += added;
+        }
+        assert_eq!(runs, 8);
+
+        let steps = std::fs::read_to_string(&history)?
+            .lines()
+            .map(|line| {
+                let record: serde_json::Value = serde_json::from_str(line).unwrap();
+                record["step"].as_u64().unwrap()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(steps, (1..=8).collect::<Vec<_>>());
+    }
+
+    Ok(())
+}
+
+#[test]
+fn run_jobs_zero() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("run")
+        .arg("./tests/rank/sort.json")
+        .arg("--jobs")
+        .arg("0");
+
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("--jobs must be at least 1"));
+
+    Ok(())
+}
+
+#[test]
+fn run_timeout_override() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("run")
+        .arg("./tests/run/hang.json")
+        .arg("--steps")
+        .arg("1")
+        .arg("--timeout")
+        .arg("50")
+        .arg("--output")
+        .arg("./tests/temp/hang-override.json");
+
+    cmd.assert()
+        .success()
+        .stderr(predicate::str::contains("hang timed out after"));
+
+    let config: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string("./tests/temp/hang-override.json")?)?;
+    assert!(config["scripts"][0]["avgruntime_ms"].as_f64().unwrap() < 200.0);
+
+    Ok(())
+}
+
+#[test]
+fn custom_exit_codes() -> Result<(), Box<dyn std::error::Error>> {
+    for (script, expected) in [
+        ("exit 2 interesting", "- Interesting: 2 (100.0%)"),
+        ("exit 1 not listed", "- Unrecognized: 2 (100.0%)"),
+        ("exit 3 uninteresting", "- Uninteresting: 2 (100.0%)"),
+    ] {
+        let mut cmd = Command::cargo_bin("bandits")?;
+
+        cmd.arg("doctor")
+            .arg("./tests/run/exit-codes.json")
+            .arg("--script")
+            .arg(script)
+            .arg("--check-determinism")
+            .arg("--runs")
+            .arg("2");
+
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains(expected));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn success_is_interesting() -> Result<(), Box<dyn std::error::Error>> {
+    for (script, expected) in [
+        ("success default", "- Uninteresting: 2 (100.0%)"),
+        ("success flipped", "- Interesting: 2 (100.0%)"),
+        ("failure flipped", "- Uninteresting: 2 (100.0%)"),
+        ("exit 2 flipped", "- Unrecognized: 2 (100.0%)"),
+        ("listed code flipped", "- Uninteresting: 2 (100.0%)"),
+    ] {
+        let mut cmd = Command::cargo_bin("bandits")?;
+
+        cmd.arg("doctor")
+            .arg("./tests/run/success-is-interesting.json")
+            .arg("--script")
+            .arg(script)
+            .arg("--check-determinism")
+            .arg("--runs")
+            .arg("2");
+
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains(expected));
+    }
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn crash_is_interesting() -> Result<(), Box<dyn std::error::Error>> {
+    for (script, expected) in [
+        ("crash interesting", "- Interesting: 2 (100.0%)"),
+        ("crash ignored", "- Unrecognized: 2 (100.0%)"),
+    ] {
+        let mut cmd = Command::cargo_bin("bandits")?;
+
+        cmd.arg("doctor")
+            .arg("./tests/run/crash.json")
+            .arg("--script")
+            .arg(script)
+            .arg("--check-determinism")
+            .arg("--runs")
+            .arg("2");
+
+        // The crash is warned about whether or not it counts.
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains(expected))
+            .stderr(predicate::str::contains(
+                "Command was terminated by signal 11",
+            ));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn toml_config() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("run")
+        .arg("./tests/toml/simple.toml")
+        .arg("--steps")
+        .arg("4")
+        .arg("--output")
+        .arg("./tests/temp/simple.toml");
+
+    cmd.assert().success();
+
+    let output = std::fs::read_to_string("./tests/temp/simple.toml")?;
+    assert!(output.contains("[[scripts]]"));
+
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("summarize").arg("./tests/temp/simple.toml");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Ranking"));
+
+    Ok(())
+}
+
+#[test]
+fn unsupported_config_extension() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("summarize").arg("./README.md");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Unsupported config extension .md for ./README.md. Expected .json or .toml",
+        ))
+        .stderr(predicate::str::contains("panicked").not());
+
+    // Checked before running anything, so no run's results are lost.
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("run")
+        .arg("./config-tests/simple-2-scripts.json")
+        .arg("--steps")
+        .arg("1")
+        .arg("-o")
+        .arg("./tests/temp/out.txt");
+    cmd.assert()
+        .failure()
+        .stdout(predicate::str::is_empty())
+        .stderr(predicate::str::diff(
+            "Unsupported config extension .txt for ./tests/temp/out.txt. Expected .json or .toml\n",
+        ));
+
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("config-set")
+        .arg("./config-tests/simple-2-scripts.json")
+        .arg("--script")
+        .arg("exit 1")
+        .arg("--bias")
+        .arg("2.0")
+        .arg("-o")
+        .arg("./tests/temp/out.yaml");
+    cmd.assert().failure().stderr(predicate::str::diff(
+        "Unsupported config extension .yaml for ./tests/temp/out.yaml. Expected .json or .toml\n",
+    ));
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn shell_commands() -> Result<(), Box<dyn std::error::Error>> {
+    for script in ["pipe", "redirect"] {
+        let mut cmd = Command::cargo_bin("bandits")?;
+
+        cmd.arg("doctor")
+            .arg("./tests/run/shell.json")
+            .arg("--script")
+            .arg(script)
+            .arg("--check-determinism")
+            .arg("--runs")
+            .arg("1");
+
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("- Interesting: 1 (100.0%)"));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn run_round_robin_dry_run() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("run")
+        .arg("./tests/run/dry-run.json")
+        .arg("--dry-run")
+        .arg("--steps")
+        .arg("4")
+        .arg("--strategy")
+        .arg("round-robin");
+
+    // Unlike thompson sampling, the baseline takes turns whatever the recorded results.
+    cmd.assert().success().stdout(predicate::str::diff(
+        "Step 1: marks a\nStep 2: marks b\nStep 3: marks a\nStep 4: marks b\n",
+    ));
+
+    let mut cmd = Command::cargo_bin("bandits")?;
+    cmd.arg("rank")
+        .arg("./tests/run/dry-run.json")
+        .arg("--strategy")
+        .arg("uniform");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("don't rank scripts"));
+
+    Ok(())
+}
+
+#[test]
+fn rank_uses_script_prior() -> Result<(), Box<dyn std::error::Error>> {
+    // Equal results, but a strong interesting prior on the second script, so it ranks first like it
+    // would be selected first by run.
+    for seed in 0..10 {
+        let mut cmd = Command::cargo_bin("bandits")?;
+
+        cmd.arg("rank")
+            .arg("./tests/rank/prior.json")
+            .arg("--seed")
+            .arg(seed.to_string());
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::starts_with("believed\nplain\n"));
+
+        let mut cmd = Command::cargo_bin("bandits")?;
+
+        cmd.arg("rank")
+            .arg("./tests/rank/prior.json")
+            .arg("--seed")
+            .arg(seed.to_string())
+            .arg("--explain");
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("Sampled scores:\n* believed"));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn simulate_finds_best_truth() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("simulate")
+        .arg("./tests/simulate/three.json")
+        .arg("--truth")
+        .arg("rare=0.05")
+        .arg("--truth")
+        .arg("common=0.5")
+        .arg("--truth")
+        .arg("never=0")
+        .arg("--steps")
+        .arg("300")
+        .arg("--seed")
+        .arg("3");
+
+    // The script with the highest rate gets the most runs, so it is listed first.
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Simulated 300 steps.\n1: common\n",
+        ))
+        .stdout(predicate::str::contains(
+            "expected interesting cases missed against always running common.",
+        ));
+
+    let mut cmd = Command::cargo_bin("bandits")?;
+    cmd.arg("simulate")
+        .arg("./tests/simulate/three.json")
+        .arg("--truth")
+        .arg("common=0.5");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("No --truth given for rare"));
+
+    Ok(())
+}
+
+#[test]
+fn run_empty_config() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("run").arg("./tests/run/empty.json");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::diff("No scripts in config. Exiting...\n"));
+
+    Ok(())
+}
+
+#[test]
+fn rank_empty_config() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("rank").arg("./tests/run/empty.json");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::diff("No scripts in config. Exiting...\n"));
+
+    Ok(())
+}
+
+#[test]
+fn summarize_empty_config() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("summarize").arg("./tests/run/empty.json");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::diff("No scripts in config. Exiting...\n"));
+
+    Ok(())
+}
+
+#[test]
+fn run_in_place_accumulates() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("bandits")?;
+
+    cmd.arg("run")
+        .arg("./tests/run/always-interesting.json")
+        .arg("--steps")
+        .arg("0")
+        .arg("--output")
+        .arg("./tests/temp/in-place.json");
+    cmd.assert().success();
+
+    for _ in 0..2 {
+        let mut cmd = Command::cargo_bin("bandits")?;
+
+        // -o is ignored, so nothing is written there.
+        cmd.arg("run")
+            .arg("./tests/temp/in-place.json")
+            .arg("--steps")
+            .arg("2")
+            .arg("--in-place")
+            .arg("-o")
+            .arg("./tests/temp/in-place-ignored.json");
+        cmd.assert().success();
+    }
+    assert!(!std::path::Path::new("./tests/temp/in-place-ignored.json").exists());
+
+    let mut cmd = Command::cargo_bin("bandits")?;
+    cmd.arg("summarize").arg("./tests/temp/in-place.json");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Runs: 6"));
+
+    let mut cmd = Command::cargo_bin("bandits")?;
+    cmd.arg("run").arg("-").arg("--in-place");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("--in-place needs a config file"));
+
+    Ok(())
+}